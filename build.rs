@@ -0,0 +1,28 @@
+use std::{env, process::Command};
+
+// Records the rustc version rscript is built with (`RSCRIPT_RUSTC_VERSION`), it's part of the ABI tag of DynamicLib scripts
+fn main() {
+    println!("cargo:rerun-if-env-changed=RUSTC");
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".into());
+    let verbose_version = Command::new(rustc)
+        .arg("-vV")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .unwrap_or_default();
+    let field = |name: &str| {
+        verbose_version
+            .lines()
+            .find_map(|line| line.strip_prefix(name))
+            .map(str::trim)
+            .unwrap_or("unknown")
+            .to_string()
+    };
+    let commit = field("commit-hash:");
+    // `release` and the short commit hash, so it fits the handshake and still tells nightlies apart
+    println!(
+        "cargo:rustc-env=RSCRIPT_RUSTC_VERSION={} {}",
+        field("release:"),
+        &commit[..commit.len().min(9)]
+    );
+}
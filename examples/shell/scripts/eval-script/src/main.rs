@@ -1,4 +1,7 @@
-use rscript::{scripting::Scripter, Hook, VersionReq};
+use rscript::{
+    scripting::{HookHandlers, Scripter},
+    VersionReq,
+};
 
 struct Evaluator;
 impl Scripter for Evaluator {
@@ -10,32 +13,12 @@ impl Scripter for Evaluator {
         rscript::ScriptType::OneShot
     }
 
-    fn hooks() -> &'static [&'static str] {
-        &[shell_api::Eval::NAME, shell_api::Shutdown::NAME]
-    }
     fn version_requirement() -> VersionReq {
         VersionReq::parse(">=0.1.0").expect("correct version requirement")
     }
 }
 
 impl Evaluator {
-    fn run(&self, hook: &str) {
-        match hook {
-            shell_api::Eval::NAME => {
-                let eval_hook: shell_api::Eval = Self::read();
-                let shell_api::Eval(input) = eval_hook;
-                let output = self.eval(&input);
-                Self::write::<shell_api::Eval>(&output);
-            }
-            shell_api::Shutdown::NAME => {
-                let _eval_hook: shell_api::Shutdown = Self::read();
-                // stderr is *not* piped so it can be used by scripts
-                eprintln!("bye from shell-script");
-            }
-
-            _ => unreachable!(),
-        }
-    }
     fn eval(&self, input: &str) -> String {
         let mut input = input.split_whitespace();
         String::from_utf8(
@@ -51,7 +34,13 @@ impl Evaluator {
 
 fn main() {
     let evaluator = Evaluator;
-    Evaluator::execute(&mut |hook| {
-        evaluator.run(hook);
-    }).unwrap();
+    let mut handlers = HookHandlers::default();
+    // The listened hooks are the ones that have a handler
+    handlers
+        .on(|shell_api::Eval(input)| evaluator.eval(&input))
+        .on(|_: shell_api::Shutdown| {
+            // stderr is *not* piped so it can be used by scripts
+            eprintln!("bye from shell-script");
+        });
+    Evaluator::run(&mut handlers).unwrap();
 }
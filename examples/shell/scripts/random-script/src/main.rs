@@ -50,7 +50,12 @@ impl Randomize {
 
 fn main() {
     let randomize = Randomize;
-    Randomize::execute(&mut |hook| {
-        randomize.run(hook);
-    }).unwrap();
+    Randomize::execute_with_shutdown(
+        &mut |hook| {
+            randomize.run(hook);
+        },
+        // Daemons are asked to shutdown when the main program exits
+        &mut || eprintln!("bye from random-script"),
+    )
+    .unwrap();
 }
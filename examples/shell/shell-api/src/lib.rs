@@ -1,26 +1,14 @@
 use rscript::Hook;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Hook)]
+#[hook(output = String)]
 pub struct Eval(pub String);
-impl Hook for Eval {
-    const NAME: &'static str = "Eval";
 
-    type Output = String;
-}
-
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Hook)]
+#[hook(output = ())]
 pub struct Shutdown;
-impl Hook for Shutdown {
-    const NAME: &'static str = "Shutdown";
-
-    type Output = ();
-}
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Hook)]
+#[hook(output = usize)]
 pub struct RandomNumber;
-impl Hook for RandomNumber {
-    const NAME: &'static str = "RandomNumber";
-
-    type Output = usize;
-}
@@ -59,6 +59,7 @@ fn main() {
     }
 
     // Give a chance for all listening scripts to cleanup
+    // Daemon scripts are also asked to shutdown when the script manager is dropped
     script_manager
         .trigger(shell_api::Shutdown)
         .for_each(|_result| {});
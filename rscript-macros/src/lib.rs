@@ -0,0 +1,455 @@
+#![warn(missing_docs)]
+
+//! Procedural macros for [rscript](https://docs.rs/rscript)\
+//! They are re-exported by rscript when its `macros` feature is enabled
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use syn::{
+    parse_macro_input, parse_quote, spanned::Spanned, Data, DeriveInput, Error, Fields, FnArg,
+    Generics, Ident, ImplItem, ItemImpl, LitStr, Type,
+};
+
+/// Turn an impl block of hook handlers into a `ScriptType::DynamicLib` script\
+/// Each method handles the hook of its first argument type and returns the hook output, it can take the host functions (`&FFiHost`) as a second argument\
+/// The macro generates the exported `SCRIPT` (`DynamicScript`), the `ScriptInfo` listening to the handled hooks (with their fingerprints) and the dispatch code, panics are caught with `DynamicScript::catch_panic`
+///
+/// Arguments:
+/// - `version_requirement`: the version requirement of the main program, it's checked at compile time
+/// - `name` (optional): the script name, defaults to the type name
+/// - `codec` (optional): the `Codec` variant the script uses, `codec = Json` for example, defaults to `Bincode`
+///
+/// ```rust, no_run
+/// use rscript::{dynamic_script, scripting::FFiHost, Hook, HostFunction};
+///
+/// // Declared on the api crate
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct Eval(String);
+/// impl Hook for Eval {
+///     const NAME: &'static str = "Eval";
+///     type Output = String;
+/// }
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct Prompt;
+/// impl Hook for Prompt {
+///     const NAME: &'static str = "Prompt";
+///     type Output = String;
+/// }
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct CurrentDir;
+/// impl HostFunction for CurrentDir {
+///     const NAME: &'static str = "CurrentDir";
+///     type Output = String;
+/// }
+///
+/// struct Echo;
+///
+/// #[dynamic_script(version_requirement = ">=0.1.0")]
+/// impl Echo {
+///     fn eval(hook: Eval) -> String {
+///         hook.0
+///     }
+///     fn prompt(_hook: Prompt, host: &FFiHost) -> String {
+///         let dir = rscript::scripting::DynamicScript::call(host, &CurrentDir).unwrap();
+///         format!("{}> ", dir)
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn dynamic_script(args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut name = None;
+    let mut version_requirement = None;
+    let mut codec = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("name") {
+            name = Some(meta.value()?.parse::<LitStr>()?);
+            Ok(())
+        } else if meta.path.is_ident("version_requirement") {
+            version_requirement = Some(meta.value()?.parse::<LitStr>()?);
+            Ok(())
+        } else if meta.path.is_ident("codec") {
+            codec = Some(meta.value()?.parse::<Ident>()?);
+            Ok(())
+        } else {
+            Err(meta.error("expected `name`, `version_requirement` or `codec`"))
+        }
+    });
+    parse_macro_input!(args with parser);
+    let item = parse_macro_input!(input as ItemImpl);
+
+    match expand_dynamic_script(name, version_requirement, codec, item) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand_dynamic_script(
+    name: Option<LitStr>,
+    version_requirement: Option<LitStr>,
+    codec: Option<Ident>,
+    item: ItemImpl,
+) -> syn::Result<TokenStream2> {
+    let version_requirement = version_requirement.ok_or_else(|| {
+        Error::new(
+            item.impl_token.span(),
+            "missing `version_requirement = \"..\"` argument",
+        )
+    })?;
+    if let Err(e) = semver::VersionReq::parse(&version_requirement.value()) {
+        return Err(Error::new(
+            version_requirement.span(),
+            format!("invalid version requirement: {}", e),
+        ));
+    }
+    if let Some((_, path, _)) = &item.trait_ {
+        return Err(Error::new(
+            path.span(),
+            "expected an inherent impl block, not a trait impl",
+        ));
+    }
+    if !item.generics.params.is_empty() {
+        return Err(Error::new(
+            item.generics.span(),
+            "the script type can't be generic",
+        ));
+    }
+    let self_ty = &item.self_ty;
+    let name = match (name, &**self_ty) {
+        (Some(name), _) => name,
+        (None, Type::Path(path)) => {
+            let ident = &path
+                .path
+                .segments
+                .last()
+                .expect("a path is not empty")
+                .ident;
+            LitStr::new(&ident.to_string(), ident.span())
+        }
+        (None, ty) => {
+            return Err(Error::new(
+                ty.span(),
+                "can't name the script after this type, use the `name = \"..\"` argument",
+            ))
+        }
+    };
+
+    let codec = match codec {
+        Some(codec) => quote!(::rscript::Codec::#codec),
+        None => quote!(::rscript::Codec::Bincode),
+    };
+
+    let mut hooks = vec![];
+    let mut handlers = vec![];
+    let mut uses_host = false;
+    for impl_item in &item.items {
+        let method = match impl_item {
+            ImplItem::Fn(method) => method,
+            other => {
+                return Err(Error::new(
+                    other.span(),
+                    "only hook handlers are allowed here, move this item to another impl block",
+                ))
+            }
+        };
+        let ident = &method.sig.ident;
+        let mut inputs = method.sig.inputs.iter();
+        let hook = match inputs.next() {
+            Some(FnArg::Typed(arg)) => &arg.ty,
+            Some(FnArg::Receiver(receiver)) => {
+                return Err(Error::new(
+                    receiver.span(),
+                    "hook handlers are called without an instance, remove `self`",
+                ))
+            }
+            None => {
+                return Err(Error::new(
+                    method.sig.span(),
+                    "a hook handler takes the hook as its first argument",
+                ))
+            }
+        };
+        let host = match inputs.next() {
+            Some(_) => {
+                uses_host = true;
+                quote!(, host)
+            }
+            None => quote!(),
+        };
+        if let Some(arg) = inputs.next() {
+            return Err(Error::new(
+                arg.span(),
+                "a hook handler takes the hook and optionally the host functions (`&FFiHost`)",
+            ));
+        }
+
+        hooks.push(hook);
+        handlers.push(quote! {
+            if name == <#hook as ::rscript::Hook>::NAME {
+                let hook: #hook = ::rscript::scripting::DynamicScript::read(hook);
+                let output: <#hook as ::rscript::Hook>::Output = <#self_ty>::#ident(hook #host);
+                return ::rscript::scripting::DynamicScript::write_with::<#hook>(#codec, &output);
+            }
+        });
+    }
+    let host = if uses_host {
+        quote!(host)
+    } else {
+        quote!(_host)
+    };
+
+    let mut tokens = item.to_token_stream();
+    tokens.extend(quote! {
+        #[no_mangle]
+        pub static SCRIPT: ::rscript::scripting::DynamicScript =
+            ::rscript::scripting::DynamicScript::new(__rscript_script_info, __rscript_script)
+                .with_codec(#codec);
+
+        extern "C" fn __rscript_script_info() -> ::rscript::scripting::FFiResult {
+            ::rscript::scripting::DynamicScript::catch_panic(|| {
+                ::rscript::ScriptInfo::new(
+                    #name,
+                    ::rscript::ScriptType::DynamicLib,
+                    &[#(<#hooks as ::rscript::Hook>::NAME),*],
+                    ::rscript::VersionReq::parse(#version_requirement)
+                        .expect("the version requirement is checked at compile time"),
+                )
+                #(.with_fingerprint::<#hooks>())*
+                .into_ffi_data()
+            })
+        }
+
+        extern "C" fn __rscript_script(
+            name: ::rscript::scripting::FFiStr,
+            hook: ::rscript::scripting::FFiData,
+            #host: &::rscript::scripting::FFiHost,
+        ) -> ::rscript::scripting::FFiResult {
+            ::rscript::scripting::DynamicScript::catch_panic(move || {
+                let name = name.as_str();
+                #(#handlers)*
+                panic!("the hook {} is not handled by this script", name)
+            })
+        }
+    });
+    Ok(tokens)
+}
+
+/// Implement `Hook` for a type, the hook is configured with the `#[hook(..)]` attribute:
+/// - `output = T`: the hook output type
+/// - `name = ".."` (optional): the hook name, defaults to the module qualified type name (`my_api::Eval` for example)\
+///   It can't contain `::`, which is reserved for the default names
+/// - `no_fingerprint` (optional): don't compute `Hook::FINGERPRINT`
+///
+/// Two hooks with the same name in the same crate fail to build (the check happens when the crate is built, not with `cargo check`)\
+/// This covers default names too, two types with the same name can be in the same module if they are declared in functions
+///
+/// The derive also implements `Schema` for the hook, and `Hook::FINGERPRINT` is computed from the hook name and the `Schema` of the hook and its output, so the output type needs to implement `Schema` too (see `#[derive(Schema)]`)
+///
+/// ```rust
+/// use rscript::Hook;
+///
+/// #[derive(serde::Serialize, serde::Deserialize, Hook)]
+/// #[hook(output = Option<String>)]
+/// struct Eval(String);
+///
+/// #[derive(serde::Serialize, serde::Deserialize, Hook)]
+/// #[hook(output = (), name = "Shutdown")]
+/// struct Shutdown;
+///
+/// assert!(Eval::NAME.ends_with("::Eval"));
+/// assert_eq!(Shutdown::NAME, "Shutdown");
+/// ```
+#[proc_macro_derive(Hook, attributes(hook))]
+pub fn derive_hook(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand_hook(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand_hook(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let mut output = None;
+    let mut name = None;
+    let mut fingerprint = true;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("hook"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("output") {
+                output = Some(meta.value()?.parse::<Type>()?);
+                Ok(())
+            } else if meta.path.is_ident("name") {
+                name = Some(meta.value()?.parse::<LitStr>()?);
+                Ok(())
+            } else if meta.path.is_ident("no_fingerprint") {
+                fingerprint = false;
+                Ok(())
+            } else {
+                Err(meta.error("expected `output`, `name` or `no_fingerprint`"))
+            }
+        })?;
+    }
+    let output = output.ok_or_else(|| {
+        Error::new(
+            Span::call_site(),
+            "missing `#[hook(output = ..)]` attribute",
+        )
+    })?;
+
+    let ident = &input.ident;
+    // The fingerprint needs the hook and its output to implement `Schema`
+    let generics = if fingerprint {
+        let mut generics = schema_bounds(&input.generics);
+        generics
+            .make_where_clause()
+            .predicates
+            .push(parse_quote!(#output: ::rscript::Schema));
+        generics
+    } else {
+        input.generics.clone()
+    };
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let name = match name {
+        Some(name) if name.value().contains("::") => {
+            return Err(Error::new(
+                name.span(),
+                "`::` is reserved for the default hook names (`module::Type`)",
+            ))
+        }
+        Some(name) => name.into_token_stream(),
+        None => quote!(concat!(module_path!(), "::", stringify!(#ident))),
+    };
+    // Symbols share one namespace, so a second hook with the same name in the crate fails to build
+    // The crate name keeps hooks of different crates apart
+    let unique_name = quote! {
+        const _: () = {
+            #[unsafe(export_name = concat!("rscript_hook_name ", env!("CARGO_CRATE_NAME"), " ", #name))]
+            static RSCRIPT_HOOK_NAME: u8 = 0;
+        };
+    };
+
+    let (fingerprint, schema) = if fingerprint {
+        let fingerprint = quote! {
+            const FINGERPRINT: ::core::option::Option<u64> = ::core::option::Option::Some(
+                ::rscript::SchemaHasher::new()
+                    .str(<Self as ::rscript::Hook>::NAME)
+                    .hash(<Self as ::rscript::Schema>::SCHEMA_HASH)
+                    .hash(<#output as ::rscript::Schema>::SCHEMA_HASH)
+                    .finish(),
+            );
+        };
+        (fingerprint, expand_schema(&input)?)
+    } else {
+        (quote!(), quote!())
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::rscript::Hook for #ident #ty_generics #where_clause {
+            const NAME: &'static str = #name;
+            type Output = #output;
+            #fingerprint
+        }
+        #schema
+        #unique_name
+    })
+}
+
+/// Implement `Schema` for a type, its hash is computed from the field and variant names and the `Schema` of the field types\
+/// The type name is not part of the hash, so renaming a type doesn't change it
+///
+/// ```rust
+/// use rscript::{Hook, Schema};
+///
+/// #[derive(serde::Serialize, serde::Deserialize, Schema)]
+/// enum Answer {
+///     Value(String),
+///     Error { message: String },
+/// }
+///
+/// #[derive(serde::Serialize, serde::Deserialize, Hook)]
+/// #[hook(output = Answer)]
+/// struct Eval(String);
+///
+/// assert!(Eval::FINGERPRINT.is_some());
+/// ```
+#[proc_macro_derive(Schema)]
+pub fn derive_schema(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand_schema(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand_schema(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let hash = match &input.data {
+        Data::Struct(data) => {
+            let fields = fields_hash(&data.fields);
+            quote!(::rscript::SchemaHasher::new().str("struct") #fields)
+        }
+        Data::Enum(data) => {
+            let variants = data.variants.iter().map(|variant| {
+                let name = variant.ident.to_string();
+                let fields = fields_hash(&variant.fields);
+                quote!(.str(#name) #fields)
+            });
+            quote!(::rscript::SchemaHasher::new().str("enum") #(#variants)*)
+        }
+        Data::Union(data) => {
+            return Err(Error::new(
+                data.union_token.span(),
+                "unions can't be serialized, so they can't implement `Schema`",
+            ))
+        }
+    };
+
+    let ident = &input.ident;
+    let generics = schema_bounds(&input.generics);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::rscript::Schema for #ident #ty_generics #where_clause {
+            const SCHEMA_HASH: u64 = #hash.finish();
+        }
+    })
+}
+
+/// The `SchemaHasher` calls hashing the fields shape, names and types
+fn fields_hash(fields: &Fields) -> TokenStream2 {
+    let (kind, names): (_, Vec<_>) = match fields {
+        Fields::Named(fields) => (
+            "named",
+            fields
+                .named
+                .iter()
+                .map(|field| {
+                    let name = field.ident.as_ref().expect("named field").to_string();
+                    quote!(.str(#name))
+                })
+                .collect(),
+        ),
+        Fields::Unnamed(fields) => ("unnamed", fields.unnamed.iter().map(|_| quote!()).collect()),
+        Fields::Unit => ("unit", vec![]),
+    };
+    let types = fields.iter().map(|field| &field.ty);
+    quote!(.str(#kind) #(#names .hash(<#types as ::rscript::Schema>::SCHEMA_HASH))*)
+}
+
+/// Require every type parameter to implement `Schema`
+fn schema_bounds(generics: &Generics) -> Generics {
+    let mut generics = generics.clone();
+    let params: Vec<_> = generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect();
+    let where_clause = generics.make_where_clause();
+    for param in params {
+        where_clause
+            .predicates
+            .push(parse_quote!(#param: ::rscript::Schema));
+    }
+    generics
+}
@@ -0,0 +1,10 @@
+//! Compile time checks of `#[derive(Hook)]`
+
+#[test]
+fn derive_hook() {
+    let tests = trybuild::TestCases::new();
+    tests.pass("tests/ui/distinct_names.rs");
+    tests.compile_fail("tests/ui/duplicate_name.rs");
+    tests.compile_fail("tests/ui/duplicate_default_name.rs");
+    tests.compile_fail("tests/ui/qualified_name.rs");
+}
@@ -0,0 +1,20 @@
+// Names that only differ by characters that are not valid in identifiers
+use rscript::Hook;
+
+#[derive(serde::Serialize, serde::Deserialize, Hook)]
+#[hook(output = (), name = "my-hook")]
+struct Dashed;
+
+#[derive(serde::Serialize, serde::Deserialize, Hook)]
+#[hook(output = (), name = "my_hook")]
+struct Underscored;
+
+#[derive(serde::Serialize, serde::Deserialize, Hook)]
+#[hook(output = (), name = "my.hook")]
+struct Dotted;
+
+fn main() {
+    assert_eq!(Dashed::NAME, "my-hook");
+    assert_eq!(Underscored::NAME, "my_hook");
+    assert_eq!(Dotted::NAME, "my.hook");
+}
@@ -0,0 +1,17 @@
+// Types declared in functions share the module path
+fn first() {
+    #[derive(serde::Serialize, serde::Deserialize, rscript::Hook)]
+    #[hook(output = ())]
+    struct Eval;
+}
+
+fn second() {
+    #[derive(serde::Serialize, serde::Deserialize, rscript::Hook)]
+    #[hook(output = ())]
+    struct Eval;
+}
+
+fn main() {
+    first();
+    second();
+}
@@ -0,0 +1,13 @@
+use rscript::Hook;
+
+#[derive(serde::Serialize, serde::Deserialize, Hook)]
+#[hook(output = (), name = "Eval")]
+struct Eval;
+
+mod other {
+    #[derive(serde::Serialize, serde::Deserialize, rscript::Hook)]
+    #[hook(output = (), name = "Eval")]
+    struct Eval;
+}
+
+fn main() {}
@@ -0,0 +1,14 @@
+// An explicit name could be the default name of another hook
+use rscript::Hook;
+
+mod api {
+    #[derive(serde::Serialize, serde::Deserialize, rscript::Hook)]
+    #[hook(output = ())]
+    pub struct Eval;
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Hook)]
+#[hook(output = (), name = "qualified_name::api::Eval")]
+struct Eval;
+
+fn main() {}
@@ -0,0 +1,734 @@
+//! Async version of [ScriptManager](crate::ScriptManager), enabled with the `tokio` feature
+
+#[cfg(feature = "json")]
+use crate::json_lines::{self, Step};
+use crate::{
+    check_version,
+    event::decode_event,
+    frame::{self, Kind},
+    is_disconnected, is_dynamic_library, open_dynamic_library,
+    process::{self, Launcher},
+    script_paths,
+    stderr::StderrSink,
+    trace, trigger_dynamic_library, Codec, Error, Greeting, Handshake, Hook, HostFunction,
+    HostFunctions, Message, ProtocolChannel, ScriptEvent, ScriptId, ScriptInfo, ScriptLaunchConfig,
+    ScriptMessage, ScriptStderr, ScriptType, State, Version, Wire, DEFAULT_SHUTDOWN_GRACE,
+    EXECUTE_LINE,
+};
+use futures_util::{
+    future::{join_all, FutureExt},
+    stream::{FuturesUnordered, Stream},
+};
+use serde::de::DeserializeOwned;
+use std::{
+    collections::VecDeque,
+    future::Future,
+    io,
+    path::{Path, PathBuf},
+    pin::Pin,
+    process::Stdio,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+#[cfg(unix)]
+use tokio::net::unix::pipe;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, ReadBuf},
+    process::{Child, ChildStderr, ChildStdin, ChildStdout, Command},
+};
+
+/// Same as [ScriptManager](crate::ScriptManager) but process scripts are driven with `tokio::process`, so triggering a hook doesn't block the executor\
+/// It must be used inside a tokio runtime with the process and time drivers enabled
+///
+/// It doesn't support yet these features of [ScriptManager](crate::ScriptManager):
+/// - Non-fatal discovery ([ScriptManager::discover_scripts_by_path](crate::ScriptManager::discover_scripts_by_path)), reloading and watching the scripts folders
+/// - Crash detection and restart policies ([Script::set_restart_policy](crate::Script::set_restart_policy)), triggering a daemon that exited returns an [Error::Io]
+/// - Checking the hook fingerprints when scripts are loaded ([ScriptManager::register_hook](crate::ScriptManager::register_hook)), they are checked when a hook is triggered
+/// - Hook specific timeouts ([ScriptManager::set_hook_timeout](crate::ScriptManager::set_hook_timeout)) and [Script::trigger_with_timeout](crate::Script::trigger_with_timeout)
+/// - Sandboxes (`ScriptManager::set_sandbox_policy`) and resource limits (`ScriptManager::set_resource_limits`), scripts always run unrestricted
+/// - Tagged outputs ([ScriptManager::trigger_tagged](crate::ScriptManager::trigger_tagged)) and looking up scripts by id ([ScriptManager::script](crate::ScriptManager::script))
+///
+/// ```rust, no_run
+/// # use rscript::*;
+/// # #[derive(serde::Serialize, serde::Deserialize)]
+/// # struct Eval(String);
+/// # impl Hook for Eval {
+/// #   const NAME: &'static str = "Eval";
+/// #   type Output = String;
+/// # }
+/// # async fn run() {
+/// use futures_util::StreamExt;
+///
+/// let mut sm = AsyncScriptManager::default();
+/// sm.add_scripts_by_path("/path/to/scripts", Version::new(0, 1, 0)).await.unwrap();
+/// // Every listening script runs concurrently, outputs are yielded as they arrive
+/// let mut outputs = sm.trigger(Eval("1 + 1".into()));
+/// while let Some(output) = outputs.next().await {
+///     println!("{:?}", output);
+/// }
+/// # }
+/// ```
+#[derive(Default)]
+pub struct AsyncScriptManager {
+    scripts: Vec<AsyncScript>,
+    greeting_timeout: Option<Duration>,
+    execute_timeout: Option<Duration>,
+    shutdown_grace: Option<Duration>,
+    launcher: Launcher,
+    host_functions: HostFunctions,
+}
+
+impl AsyncScriptManager {
+    /// Same as [ScriptManager::add_scripts_by_path](crate::ScriptManager::add_scripts_by_path)
+    pub async fn add_scripts_by_path<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        version: Version,
+    ) -> Result<(), Error> {
+        let span = trace::discovery(path.as_ref());
+        span.run_async(async {
+            let mut scripts = vec![];
+            for path in script_paths(path.as_ref())? {
+                if is_dynamic_library(&path) {
+                    continue;
+                }
+                // On error, the already started scripts are killed when dropped
+                scripts.push(
+                    start_script(&path, &version, self.greeting_timeout, &self.launcher).await?,
+                );
+            }
+            self.scripts.extend(scripts);
+            Ok(())
+        })
+        .await
+    }
+    /// Same as [ScriptManager::add_dynamic_scripts_by_path](crate::ScriptManager::add_dynamic_scripts_by_path)\
+    /// Dynamic library scripts are called directly on the executor thread
+    ///
+    /// # Safety
+    /// See <https://docs.rs/libloading/0.7.1/libloading/struct.Library.html#safety>
+    pub unsafe fn add_dynamic_scripts_by_path<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        version: Version,
+    ) -> Result<(), Error> {
+        trace::discovery(path.as_ref()).run(|| {
+            let mut scripts = vec![];
+            for path in script_paths(path.as_ref())? {
+                if is_dynamic_library(&path) {
+                    let (lib, metadata, codec) = open_dynamic_library(&path, &version)?;
+                    scripts.push(AsyncScript {
+                        id: ScriptId::next(),
+                        metadata,
+                        wire: Wire::Native(codec),
+                        script: AsyncScriptType::DynamicLib(lib),
+                        launcher: Launcher::default(),
+                        stderr: StderrSink::new(&path),
+                        state: State::Active,
+                        events: VecDeque::new(),
+                    });
+                }
+            }
+            self.scripts.extend(scripts);
+            Ok(())
+        })
+    }
+    /// Same as [ScriptManager::set_greeting_timeout](crate::ScriptManager::set_greeting_timeout)
+    pub fn set_greeting_timeout(&mut self, timeout: Option<Duration>) {
+        self.greeting_timeout = timeout;
+    }
+    /// Same as [ScriptManager::set_execute_timeout](crate::ScriptManager::set_execute_timeout)
+    pub fn set_execute_timeout(&mut self, timeout: Option<Duration>) {
+        self.execute_timeout = timeout;
+    }
+    /// Same as [ScriptManager::set_protocol_channel](crate::ScriptManager::set_protocol_channel)
+    pub fn set_protocol_channel(&mut self, channel: ProtocolChannel) {
+        self.launcher.channel = channel;
+    }
+    /// Same as [ScriptManager::set_launch_config](crate::ScriptManager::set_launch_config)
+    pub fn set_launch_config(&mut self, config: ScriptLaunchConfig) {
+        self.launcher.config = config;
+    }
+    /// Same as [ScriptManager::set_script_launch_config](crate::ScriptManager::set_script_launch_config)
+    pub fn set_script_launch_config<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        config: Option<ScriptLaunchConfig>,
+    ) {
+        let path = process::canonical(path.as_ref());
+        match config {
+            Some(config) => self.launcher.script_configs.insert(path, config),
+            None => self.launcher.script_configs.remove(&path),
+        };
+    }
+    /// Same as [ScriptManager::set_shutdown_grace](crate::ScriptManager::set_shutdown_grace), it's used by [AsyncScriptManager::shutdown]
+    pub fn set_shutdown_grace(&mut self, grace: Duration) {
+        self.shutdown_grace = Some(grace);
+    }
+    /// Trigger a hook\
+    /// All scripts that are *active* and that are listening for this particular hook will receive it concurrently, the returned stream yields their outputs as they arrive
+    pub fn trigger<'a, H: 'static + Hook>(
+        &'a mut self,
+        hook: H,
+    ) -> impl Stream<Item = Result<<H as Hook>::Output, Error>> + 'a {
+        let hook = Arc::new(hook);
+        let timeout = self.execute_timeout;
+        let host = &self.host_functions;
+        self.scripts
+            .iter_mut()
+            .filter(|script| script.is_active() && script.is_listening_for::<H>())
+            .map(|script| {
+                let hook = hook.clone();
+                async move { script.trigger_internal(&*hook, timeout, host).await }
+            })
+            .collect::<FuturesUnordered<_>>()
+    }
+    /// Same as [ScriptManager::register_host_function](crate::ScriptManager::register_host_function)
+    pub fn register_host_function<F: HostFunction>(
+        &mut self,
+        handler: impl Fn(F) -> <F as HostFunction>::Output + Send + Sync + 'static,
+    ) {
+        self.host_functions.register(handler);
+    }
+    /// Same as [ScriptManager::poll_events](crate::ScriptManager::poll_events), it doesn't wait for the scripts\
+    /// A script that breaks the protocol is marked as failed since it can't be restarted
+    pub fn poll_events(&mut self) -> impl Iterator<Item = Result<ScriptEvent, Error>> + '_ {
+        self.scripts.iter_mut().flat_map(AsyncScript::poll_events)
+    }
+    /// Ask the daemons to shutdown and wait for them to exit, scripts still running after the shutdown grace are killed\
+    /// Since there is no async drop, dropping the manager without calling this kills the daemons
+    pub async fn shutdown(&mut self) {
+        let grace = self.shutdown_grace.unwrap_or(DEFAULT_SHUTDOWN_GRACE);
+        join_all(
+            self.scripts
+                .iter_mut()
+                .filter_map(|script| match &mut script.script {
+                    AsyncScriptType::Daemon(process) => Some(process.shutdown(script.wire, grace)),
+                    _ => None,
+                }),
+        )
+        .await;
+    }
+    /// List of current scripts
+    pub fn scripts(&self) -> &[AsyncScript] {
+        &self.scripts
+    }
+    /// Mutable list of current scripts, useful for activating/deactivating a script
+    pub fn scripts_mut(&mut self) -> &mut [AsyncScript] {
+        &mut self.scripts
+    }
+}
+
+/// A script managed by an [AsyncScriptManager]
+// The user should not be able to construct a Script manually
+#[derive(Debug)]
+pub struct AsyncScript {
+    id: ScriptId,
+    metadata: ScriptInfo,
+    wire: Wire,
+    script: AsyncScriptType,
+    // Needed to start OneShot scripts
+    launcher: Launcher,
+    // Kept across OneShot triggers
+    stderr: StderrSink,
+    state: State,
+    // Events (name and serialized data) received but not yet returned by [AsyncScriptManager::poll_events]
+    events: VecDeque<(String, Vec<u8>)>,
+}
+
+#[derive(Debug)]
+enum AsyncScriptType {
+    // Boxed since the process is much larger than the other variants
+    Daemon(Box<AsyncProcess>),
+    OneShot(PathBuf),
+    DynamicLib(libloading::Library),
+}
+
+impl AsyncScript {
+    //public
+    /// Returns the script id
+    pub fn id(&self) -> ScriptId {
+        self.id
+    }
+    /// Returns the script metadata
+    pub fn metadata(&self) -> &ScriptInfo {
+        &self.metadata
+    }
+    /// Same as [Script::codec](crate::Script::codec)
+    pub fn codec(&self) -> Codec {
+        self.wire.codec()
+    }
+    /// Same as [Script::stderr](crate::Script::stderr)
+    pub fn stderr(&self) -> Vec<String> {
+        self.stderr.lines()
+    }
+    /// Activate a script, inactive scripts will not react to hooks
+    pub fn activate(&mut self) {
+        self.state = State::Active;
+    }
+    /// Deactivate a script, inactive scripts will not react to hooks
+    pub fn deactivate(&mut self) {
+        self.state = State::Inactive;
+    }
+    /// Query the script state
+    pub fn is_active(&self) -> bool {
+        matches!(self.state, State::Active)
+    }
+    /// Check if the script was disabled because it failed, for example because it timed out
+    pub fn has_failed(&self) -> bool {
+        matches!(self.state, State::Failed)
+    }
+    /// Check if a script is listening for a hook
+    pub fn is_listening_for<H: Hook>(&self) -> bool {
+        self.metadata
+            .hooks
+            .iter()
+            .any(|hook| hook.as_str() == H::NAME)
+    }
+    /// Trigger a hook on the script, this disregards the script state as in the hook will be triggered even if the script is inactive\
+    /// If the script is not listening for the specified hook, an error will be returned\
+    /// The host functions registered on the [AsyncScriptManager] are not available to the script when triggered this way
+    pub async fn trigger<H: Hook>(&mut self, hook: &H) -> Result<<H as Hook>::Output, Error> {
+        if self.is_listening_for::<H>() {
+            self.trigger_internal(hook, None, &HostFunctions::default())
+                .await
+        } else {
+            Err(Error::ScriptIsNotListeningForHook)
+        }
+    }
+}
+
+impl AsyncScript {
+    // private
+    async fn trigger_internal<H: Hook>(
+        &mut self,
+        hook: &H,
+        timeout: Option<Duration>,
+        host: &HostFunctions,
+    ) -> Result<<H as Hook>::Output, Error> {
+        if !fingerprint_matches::<H>(&self.metadata) {
+            return Err(Error::HookMismatch {
+                script: self.metadata.name.clone(),
+                hook: H::NAME.into(),
+            });
+        }
+        let wire = self.wire;
+        let span = trace::trigger(&self.metadata, H::NAME);
+        let result = span
+            .run_async(async {
+                match &mut self.script {
+                    AsyncScriptType::Daemon(process) => {
+                        let execute = process.execute(hook, wire, false, host, &mut self.events);
+                        with_timeout(execute, timeout).await
+                    }
+                    AsyncScriptType::OneShot(path) => {
+                        // The process is killed when dropped if it timed out
+                        let mut process = AsyncProcess::spawn(path, &self.launcher, &self.stderr)?;
+                        let execute = process.execute(hook, wire, true, host, &mut self.events);
+                        with_timeout(execute, timeout).await
+                    }
+                    AsyncScriptType::DynamicLib(lib) => unsafe {
+                        trigger_dynamic_library(lib, &self.metadata.name, wire.codec(), hook, host)
+                    },
+                }
+            })
+            .await;
+        // A script that timed out or broke the protocol is in an unknown state, so kill it and mark it as failed
+        if let Err(Error::Timeout(_) | Error::ProtocolViolation { .. }) = result {
+            if let AsyncScriptType::Daemon(process) = &mut self.script {
+                let _ = process.child.start_kill();
+            }
+            self.state = State::Failed;
+        }
+        result
+    }
+    /// Receive the events that a daemon already sent, along with the ones received while triggering hooks\
+    /// A daemon that sends anything else is in an unknown state, so it's killed and marked as failed
+    fn poll_events(&mut self) -> Vec<Result<ScriptEvent, Error>> {
+        let mut error = None;
+        let (failed, wire) = (self.has_failed(), self.wire);
+        if let (AsyncScriptType::Daemon(process), false) = (&mut self.script, failed) {
+            loop {
+                let received = process.try_read_with(|bytes| decode_event(wire, bytes));
+                match received {
+                    Ok(Some(event)) => self.events.push_back(event),
+                    Ok(None) => break,
+                    Err(e) => {
+                        error = Some(e);
+                        break;
+                    }
+                }
+            }
+            if error.is_some() {
+                let _ = process.child.start_kill();
+                self.state = State::Failed;
+            }
+        }
+        let (id, name, codec) = (self.id, &self.metadata.name, self.codec());
+        self.events
+            .drain(..)
+            .map(|(event, data)| Ok(ScriptEvent::new(id, name.clone(), event, codec, data)))
+            .chain(error.map(Err))
+            .collect()
+    }
+}
+
+/// Whether the script wasn't built with a different definition of `H`, hooks without a fingerprint are not checked
+fn fingerprint_matches<H: Hook>(metadata: &ScriptInfo) -> bool {
+    match (metadata.fingerprints.get(H::NAME), H::FINGERPRINT) {
+        (Some(advertised), Some(fingerprint)) => *advertised == fingerprint,
+        _ => true,
+    }
+}
+
+async fn with_timeout<T>(
+    future: impl Future<Output = Result<T, Error>>,
+    timeout: Option<Duration>,
+) -> Result<T, Error> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, future)
+            .await
+            .unwrap_or(Err(Error::Timeout(timeout))),
+        None => future.await,
+    }
+}
+
+async fn start_script(
+    path: &Path,
+    version: &Version,
+    timeout: Option<Duration>,
+    launcher: &Launcher,
+) -> Result<AsyncScript, Error> {
+    let stderr = StderrSink::new(path);
+    let span = trace::greeting(path);
+    // The process is killed when dropped if it timed out
+    let greeting = with_timeout(greet(path, version, launcher, &stderr), timeout);
+    let (script, metadata, wire) = span.run_async(greeting).await?;
+    span.record_script(&metadata);
+    stderr.set_name(&metadata.name);
+
+    // Save script depending on its type
+    let script = if matches!(metadata.script_type, ScriptType::Daemon) {
+        AsyncScriptType::Daemon(Box::new(script))
+    } else {
+        AsyncScriptType::OneShot(path.to_path_buf())
+    };
+    Ok(AsyncScript {
+        id: ScriptId::next(),
+        metadata,
+        wire,
+        script,
+        launcher: launcher.clone(),
+        stderr,
+        state: State::Active,
+        events: VecDeque::new(),
+    })
+}
+
+/// Start a script and greet it
+async fn greet(
+    path: &Path,
+    version: &Version,
+    launcher: &Launcher,
+    stderr: &StderrSink,
+) -> Result<(AsyncProcess, ScriptInfo, Wire), Error> {
+    let mut script = AsyncProcess::spawn(path, launcher, stderr)?;
+
+    // Send Greeting Message
+    let greeting = Greeting::of(path);
+    // A script that exits without reading it is recognized from its answer, like one that answers something else
+    match script.write_all(&greeting.message()).await {
+        Err(e) if !is_disconnected(&e) => return Err(e),
+        _ => (),
+    }
+
+    let json_lines = match greeting {
+        Greeting::JsonLines => true,
+        Greeting::Line => script.peek().await? == Some(b'{'),
+        Greeting::Native => false,
+    };
+    let (metadata, wire) = if json_lines {
+        greet_json_lines(&mut script, path).await?
+    } else {
+        // Receive the handshake, an incompatible script could send anything after it
+        let handshake = script
+            .read_with(|bytes| Handshake::decode_prefix(bytes, path))
+            .await
+            .map_err(|e| Handshake::missing(e, path))?;
+        let codec = handshake.check(path)?;
+        // Receive ScriptInfo
+        let metadata: ScriptInfo = script.read_frame(codec, Kind::ScriptInfo).await?;
+        (metadata, Wire::Native(codec))
+    };
+    check_version(&metadata, version)?;
+    Ok((script, metadata, wire))
+}
+
+/// Same as [StderrSink::capture] but the stderr is read by a task of the runtime
+fn capture_stderr(sink: &StderrSink, stderr: ChildStderr, mode: ScriptStderr) {
+    let sink = sink.clone();
+    tokio::spawn(async move {
+        let mut stderr = BufReader::new(stderr);
+        let mut line = Vec::new();
+        while matches!(stderr.read_until(b'\n', &mut line).await, Ok(n) if n > 0) {
+            sink.push(mode, &line);
+            line.clear();
+        }
+    });
+}
+
+/// Read the `info` line of a JSON-lines script
+#[cfg(feature = "json")]
+async fn greet_json_lines(
+    script: &mut AsyncProcess,
+    path: &Path,
+) -> Result<(ScriptInfo, Wire), Error> {
+    let line = script.read_with(json_lines::decode_line).await?;
+    Ok((json_lines::script_info(line, path)?, Wire::JsonLines))
+}
+
+#[cfg(not(feature = "json"))]
+async fn greet_json_lines(
+    _script: &mut AsyncProcess,
+    path: &Path,
+) -> Result<(ScriptInfo, Wire), Error> {
+    Err(Error::UnsupportedCodec {
+        path: path.to_path_buf(),
+        codec: "json-lines".into(),
+    })
+}
+
+/// A spawned script, the protocol is spoken on its stdin/stdout or on the pipes of [ProtocolChannel::Pipes]
+#[derive(Debug)]
+struct AsyncProcess {
+    child: Child,
+    // `None` once the script was asked to shutdown
+    input: Option<Input>,
+    output: Output,
+    // Bytes read from the output that are not yet deserialized
+    buffer: Vec<u8>,
+}
+
+impl AsyncProcess {
+    /// The piped stderr of the script goes to `stderr`
+    fn spawn(path: &Path, launcher: &Launcher, stderr: &StderrSink) -> io::Result<Self> {
+        let config = launcher.config(path);
+        let mut command = Command::from(config.command(path)?);
+        command.kill_on_drop(true);
+        let (mut child, input, output) = match launcher.channel {
+            ProtocolChannel::Stdio => {
+                let mut child = command
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .spawn()?;
+                let input = Input::Stdin(child.stdin.take().expect("stdin is piped"));
+                let output = Output::Stdout(child.stdout.take().expect("stdout is piped"));
+                (child, input, output)
+            }
+            #[cfg(unix)]
+            ProtocolChannel::Pipes => {
+                use std::os::unix::io::OwnedFd;
+                let (reader, writer) = crate::process::pipes(command.as_std_mut())?;
+                let child = command.spawn()?;
+                let input = Input::Pipe(pipe::Sender::from_owned_fd(OwnedFd::from(writer))?);
+                let output = Output::Pipe(pipe::Receiver::from_owned_fd(OwnedFd::from(reader))?);
+                (child, input, output)
+            }
+        };
+        if let Some(piped) = child.stderr.take() {
+            capture_stderr(stderr, piped, config.stderr_mode());
+        }
+        Ok(Self {
+            child,
+            input: Some(input),
+            output,
+            buffer: Vec::new(),
+        })
+    }
+    /// `first` is true if it's the first message the process receives
+    async fn execute<H: Hook>(
+        &mut self,
+        hook: &H,
+        wire: Wire,
+        first: bool,
+        host: &HostFunctions,
+        events: &mut VecDeque<(String, Vec<u8>)>,
+    ) -> Result<<H as Hook>::Output, Error> {
+        #[cfg(feature = "json")]
+        if let Wire::JsonLines = wire {
+            return self.execute_json_lines(hook, host, events).await;
+        }
+        let codec = wire.codec();
+        let mut message = if first {
+            EXECUTE_LINE.as_bytes().to_vec()
+        } else {
+            frame::encode(codec, Kind::Message, &Message::Execute)?
+        };
+        message.extend(frame::encode(codec, Kind::HookName, H::NAME)?);
+        message.extend(frame::encode(codec, Kind::Hook, hook)?);
+        self.write_all(&message).await?;
+        // Answer the host functions calls until the script sends the output
+        loop {
+            match self.read_frame(codec, Kind::ScriptMessage).await? {
+                ScriptMessage::Output => return self.read_frame(codec, Kind::Output).await,
+                ScriptMessage::HostCall { name, data } => {
+                    let reply = host.call(&name, codec, &data);
+                    self.write_all(&frame::encode(codec, Kind::HostReply, &reply)?)
+                        .await?
+                }
+                ScriptMessage::Event { name, data } => events.push_back((name, data)),
+            }
+        }
+    }
+    /// Same as [AsyncProcess::execute] for a JSON-lines script
+    #[cfg(feature = "json")]
+    async fn execute_json_lines<H: Hook>(
+        &mut self,
+        hook: &H,
+        host: &HostFunctions,
+        events: &mut VecDeque<(String, Vec<u8>)>,
+    ) -> Result<<H as Hook>::Output, Error> {
+        self.write_all(&json_lines::execute_line(hook)?).await?;
+        loop {
+            let line = self.read_with(json_lines::decode_line).await?;
+            match json_lines::step(line, host, events)? {
+                Step::Output(output) => return Ok(output),
+                Step::Reply(reply) => self.write_all(&reply).await?,
+                Step::Continue => (),
+            }
+        }
+    }
+    async fn write_all(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let input = self
+            .input
+            .as_mut()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        input.write_all(bytes).await?;
+        input.flush().await?;
+        Ok(())
+    }
+    /// Read a frame of `kind`
+    async fn read_frame<T: DeserializeOwned>(
+        &mut self,
+        codec: Codec,
+        kind: Kind,
+    ) -> Result<T, Error> {
+        self.read_with(|bytes| frame::decode_prefix(bytes, codec, kind))
+            .await?
+    }
+    /// Decode a value with `decode`, which returns the value and the number of bytes it spans, or `None` if it needs more bytes
+    async fn read_with<T>(
+        &mut self,
+        mut decode: impl FnMut(&[u8]) -> Result<Option<(T, usize)>, Error>,
+    ) -> Result<T, Error> {
+        loop {
+            if let Some((value, len)) = decode(&self.buffer)? {
+                self.buffer.drain(..len);
+                return Ok(value);
+            }
+            if self.output.read_buf(&mut self.buffer).await? == 0 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+        }
+    }
+    /// Returns the next byte of the output without consuming it, `None` on EOF
+    async fn peek(&mut self) -> Result<Option<u8>, Error> {
+        while self.buffer.is_empty() {
+            if self.output.read_buf(&mut self.buffer).await? == 0 {
+                return Ok(None);
+            }
+        }
+        Ok(Some(self.buffer[0]))
+    }
+    /// Same as [AsyncProcess::read_with] but returns `None` instead of waiting for more bytes
+    fn try_read_with<T>(
+        &mut self,
+        decode: impl FnOnce(&[u8]) -> Result<Option<(T, usize)>, Error>,
+    ) -> Result<Option<T>, Error> {
+        // Reading is cancel safe, so polling it once and dropping it doesn't lose bytes
+        while let Some(read) = self.output.read_buf(&mut self.buffer).now_or_never() {
+            if read? == 0 {
+                break;
+            }
+        }
+        Ok(decode(&self.buffer)?.map(|(value, len)| {
+            self.buffer.drain(..len);
+            value
+        }))
+    }
+    async fn shutdown(&mut self, wire: Wire, grace: Duration) {
+        // This errors if the script has already exited
+        // We don't care about this error
+        // A hung daemon that doesn't read it is killed once the grace elapses
+        if let Ok(message) = wire.shutdown() {
+            let _ = tokio::time::timeout(grace, self.write_all(&message)).await;
+        }
+        // Closing the input signals EOF to scripts that don't handle the shutdown message
+        self.input = None;
+        if tokio::time::timeout(grace, self.child.wait())
+            .await
+            .is_err()
+        {
+            let _ = self.child.kill().await;
+        }
+    }
+}
+
+/// The main program end of the channel the script reads its messages from
+#[derive(Debug)]
+enum Input {
+    Stdin(ChildStdin),
+    #[cfg(unix)]
+    Pipe(pipe::Sender),
+}
+
+impl AsyncWrite for Input {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Stdin(stdin) => Pin::new(stdin).poll_write(cx, buf),
+            #[cfg(unix)]
+            Self::Pipe(pipe) => Pin::new(pipe).poll_write(cx, buf),
+        }
+    }
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Stdin(stdin) => Pin::new(stdin).poll_flush(cx),
+            #[cfg(unix)]
+            Self::Pipe(pipe) => Pin::new(pipe).poll_flush(cx),
+        }
+    }
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Stdin(stdin) => Pin::new(stdin).poll_shutdown(cx),
+            #[cfg(unix)]
+            Self::Pipe(pipe) => Pin::new(pipe).poll_shutdown(cx),
+        }
+    }
+}
+
+/// The main program end of the channel the script writes its messages to
+#[derive(Debug)]
+enum Output {
+    Stdout(ChildStdout),
+    #[cfg(unix)]
+    Pipe(pipe::Receiver),
+}
+
+impl AsyncRead for Output {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Stdout(stdout) => Pin::new(stdout).poll_read(cx, buf),
+            #[cfg(unix)]
+            Self::Pipe(pipe) => Pin::new(pipe).poll_read(cx, buf),
+        }
+    }
+}
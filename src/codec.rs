@@ -0,0 +1,163 @@
+//! Serialization formats used to talk to scripts, see [Codec]
+
+use crate::Error;
+use serde::{de::DeserializeOwned, Serialize};
+use std::io::{Read, Write};
+
+/// The serialization format of the messages exchanged with a script\
+/// Scripts choose it ([Scripter::codec](crate::scripting::Scripter::codec) or [DynamicScript::with_codec](crate::scripting::DynamicScript::with_codec)) and advertise it in their handshake, the main program refuses scripts using a codec it wasn't built with\
+/// Bincode is always available, the other codecs are enabled with the `json`, `msgpack` and `cbor` features
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Codec {
+    /// [bincode](https://docs.rs/bincode) 1.x, the default
+    #[default]
+    Bincode = 0,
+    /// JSON, each value is followed by a newline
+    #[cfg(feature = "json")]
+    Json = 1,
+    /// MessagePack, structs are encoded as maps
+    #[cfg(feature = "msgpack")]
+    MessagePack = 2,
+    /// CBOR
+    #[cfg(feature = "cbor")]
+    Cbor = 3,
+}
+
+// Indexed by codec id, including the codecs that are not enabled
+const NAMES: [&str; 4] = ["bincode", "json", "msgpack", "cbor"];
+
+impl Codec {
+    /// The codec name
+    pub fn name(self) -> &'static str {
+        NAMES[self as usize]
+    }
+    /// The id sent in the handshake
+    pub(crate) fn id(self) -> u8 {
+        self as u8
+    }
+    /// The codec with this id, or the name of the codec if it's known but not enabled
+    pub(crate) fn from_id(id: u8) -> Result<Self, String> {
+        match id {
+            0 => Ok(Self::Bincode),
+            #[cfg(feature = "json")]
+            1 => Ok(Self::Json),
+            #[cfg(feature = "msgpack")]
+            2 => Ok(Self::MessagePack),
+            #[cfg(feature = "cbor")]
+            3 => Ok(Self::Cbor),
+            id => Err(NAMES
+                .get(id as usize)
+                .map_or_else(|| format!("unknown codec {}", id), |name| name.to_string())),
+        }
+    }
+    /// Serialize a value
+    pub(crate) fn encode<T: Serialize + ?Sized>(self, value: &T) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::Bincode => Ok(bincode::serialize(value)?),
+            #[cfg(feature = "json")]
+            Self::Json => {
+                let mut bytes = serde_json::to_vec(value).map_err(|e| self.error(e))?;
+                // Delimits the value, so reading it doesn't need to look past its end
+                bytes.push(b'\n');
+                Ok(bytes)
+            }
+            #[cfg(feature = "msgpack")]
+            Self::MessagePack => rmp_serde::to_vec_named(value).map_err(|e| self.error(e)),
+            #[cfg(feature = "cbor")]
+            Self::Cbor => {
+                let mut bytes = vec![];
+                ciborium::into_writer(value, &mut bytes).map_err(|e| self.error(e))?;
+                Ok(bytes)
+            }
+        }
+    }
+    /// Serialize a value into `writer`
+    pub(crate) fn write<W: Write, T: Serialize + ?Sized>(
+        self,
+        mut writer: W,
+        value: &T,
+    ) -> Result<(), Error> {
+        writer.write_all(&self.encode(value)?)?;
+        Ok(())
+    }
+    /// Deserialize a value that spans all of `bytes`
+    pub(crate) fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, Error> {
+        match self {
+            Self::Bincode => Ok(bincode::deserialize(bytes)?),
+            #[cfg(feature = "json")]
+            Self::Json => serde_json::from_slice(bytes).map_err(|e| self.error(e)),
+            #[cfg(feature = "msgpack")]
+            Self::MessagePack => rmp_serde::from_slice(bytes).map_err(|e| self.error(e)),
+            #[cfg(feature = "cbor")]
+            Self::Cbor => ciborium::from_reader(bytes).map_err(|e| self.read_error(e)),
+        }
+    }
+    /// Deserialize one value from `reader`, without reading past its end\
+    /// Running out of bytes is reported as [std::io::ErrorKind::UnexpectedEof], see [is_eof]
+    pub(crate) fn read<R: Read, T: DeserializeOwned>(self, reader: R) -> Result<T, Error> {
+        match self {
+            Self::Bincode => Ok(bincode::deserialize_from(reader)?),
+            #[cfg(feature = "json")]
+            Self::Json => {
+                let mut deserializer = serde_json::Deserializer::from_reader(reader);
+                match T::deserialize(&mut deserializer) {
+                    Ok(value) => Ok(value),
+                    Err(e) if e.is_eof() => {
+                        Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into())
+                    }
+                    Err(e) if e.is_io() => Err(std::io::Error::from(e).into()),
+                    Err(e) => Err(self.error(e)),
+                }
+            }
+            #[cfg(feature = "msgpack")]
+            Self::MessagePack => match rmp_serde::from_read(reader) {
+                Ok(value) => Ok(value),
+                Err(
+                    rmp_serde::decode::Error::InvalidMarkerRead(e)
+                    | rmp_serde::decode::Error::InvalidDataRead(e),
+                ) => Err(e.into()),
+                Err(e) => Err(self.error(e)),
+            },
+            #[cfg(feature = "cbor")]
+            Self::Cbor => ciborium::from_reader(reader).map_err(|e| self.read_error(e)),
+        }
+    }
+    /// Deserialize the value at the start of `bytes`, returns it with the number of bytes it spans, or `None` if it's not fully received yet
+    pub(crate) fn read_prefix<T: DeserializeOwned>(
+        self,
+        bytes: &[u8],
+    ) -> Result<Option<(T, usize)>, Error> {
+        let mut rest = bytes;
+        match self.read(&mut rest) {
+            Ok(value) => Ok(Some((value, bytes.len() - rest.len()))),
+            Err(e) if is_eof(&e) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+    #[cfg(any(feature = "json", feature = "msgpack", feature = "cbor"))]
+    pub(crate) fn error(self, error: impl std::fmt::Display) -> Error {
+        Error::Codec {
+            codec: self,
+            message: error.to_string(),
+        }
+    }
+    #[cfg(feature = "cbor")]
+    fn read_error(self, error: ciborium::de::Error<std::io::Error>) -> Error {
+        match error {
+            ciborium::de::Error::Io(e) => e.into(),
+            e => self.error(e),
+        }
+    }
+}
+
+/// Whether the value is not fully received yet, values are not length prefixed so running out of bytes is the only way to know it
+fn is_eof(error: &Error) -> bool {
+    match error {
+        Error::Io(e) => e.kind() == std::io::ErrorKind::UnexpectedEof,
+        Error::Bincode(e) => {
+            matches!(&**e, bincode::ErrorKind::Io(e) if e.kind() == std::io::ErrorKind::UnexpectedEof)
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,76 @@
+use crate::Error;
+use std::path::{Path, PathBuf};
+
+/// The outcome of trying to load a single file while discovering scripts
+#[derive(Debug)]
+pub enum DiscoveryStatus {
+    /// The script was loaded and added to the [ScriptManager](crate::ScriptManager)
+    Loaded,
+    /// The file was ignored, for example a dynamic library found while looking for executable scripts
+    Skipped,
+    /// The script could not be loaded, the error explains why
+    Failed(Error),
+    /// The script was loaded (it has a [DiscoveryStatus::Loaded] entry too) but it was built with a different definition of a hook registered with [ScriptManager::register_hook](crate::ScriptManager::register_hook), the hook is disabled for it
+    HookMismatch(MismatchedHook),
+}
+
+/// A hook disabled for a script because the script was built with a different definition of it, see [Script::mismatched_hooks](crate::Script::mismatched_hooks)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MismatchedHook {
+    /// The hook name
+    pub hook: String,
+    /// The [Hook::FINGERPRINT](crate::Hook::FINGERPRINT) of the main program
+    pub program_fingerprint: u64,
+    /// The fingerprint advertised by the script
+    pub script_fingerprint: u64,
+}
+
+/// Per path report returned by [ScriptManager::discover_scripts_by_path](crate::ScriptManager::discover_scripts_by_path) and [ScriptManager::discover_dynamic_scripts_by_path](crate::ScriptManager::discover_dynamic_scripts_by_path)
+#[derive(Debug, Default)]
+pub struct DiscoveryReport {
+    entries: Vec<(PathBuf, DiscoveryStatus)>,
+}
+
+impl DiscoveryReport {
+    /// All the visited paths with their status, in the order they were found\
+    /// A loaded script is followed by one [DiscoveryStatus::HookMismatch] entry per hook disabled for it
+    pub fn entries(&self) -> &[(PathBuf, DiscoveryStatus)] {
+        &self.entries
+    }
+    /// Paths of the scripts that were loaded successfully
+    pub fn loaded(&self) -> impl Iterator<Item = &Path> {
+        self.entries
+            .iter()
+            .filter(|(_, status)| matches!(status, DiscoveryStatus::Loaded))
+            .map(|(path, _)| path.as_path())
+    }
+    /// Paths that were ignored
+    pub fn skipped(&self) -> impl Iterator<Item = &Path> {
+        self.entries
+            .iter()
+            .filter(|(_, status)| matches!(status, DiscoveryStatus::Skipped))
+            .map(|(path, _)| path.as_path())
+    }
+    /// Paths of the scripts that failed to load, with the corresponding error
+    pub fn failed(&self) -> impl Iterator<Item = (&Path, &Error)> {
+        self.entries
+            .iter()
+            .filter_map(|(path, status)| match status {
+                DiscoveryStatus::Failed(error) => Some((path.as_path(), error)),
+                _ => None,
+            })
+    }
+    /// Paths of the scripts that were loaded with disabled hooks, with the corresponding mismatch
+    pub fn mismatched_hooks(&self) -> impl Iterator<Item = (&Path, &MismatchedHook)> {
+        self.entries
+            .iter()
+            .filter_map(|(path, status)| match status {
+                DiscoveryStatus::HookMismatch(mismatch) => Some((path.as_path(), mismatch)),
+                _ => None,
+            })
+    }
+
+    pub(crate) fn push(&mut self, path: PathBuf, status: DiscoveryStatus) {
+        self.entries.push((path, status));
+    }
+}
@@ -1,4 +1,5 @@
-use crate::{Version, VersionReq};
+use crate::{BuildInfo, Codec, Version, VersionReq};
+use std::{path::PathBuf, process::ExitStatus, time::Duration};
 
 /// Rscript public error
 #[derive(Debug)]
@@ -7,6 +8,13 @@ pub enum Error {
     Io(std::io::Error),
     /// Bincode error
     Bincode(bincode::Error),
+    /// Serialization error of a [Codec] other than bincode
+    Codec {
+        /// The codec that failed
+        codec: Codec,
+        /// The error message
+        message: String,
+    },
     /// This error is raised if the user attempts to trigger manually a hook on a script and the script is not listening for the specified hook
     ScriptIsNotListeningForHook,
     /// The script is written for a different version of the program
@@ -18,12 +26,95 @@ pub enum Error {
     },
     /// Failed to load a dynamic libaray
     DynamicLibError(libloading::Error),
+    /// The script did not answer within the specified duration, it was killed
+    Timeout(Duration),
+    /// A [ScriptType::Daemon](crate::ScriptType::Daemon) script exited while it was expected to keep running
+    ScriptCrashed {
+        /// The script name
+        name: String,
+        /// The script exit status, it contains the exit code or the signal that terminated it
+        status: ExitStatus,
+    },
+    /// A [HostFunction](crate::HostFunction) called by the script failed or is not registered by the main program
+    HostFunction(String),
+    /// A [ScriptType::DynamicLib](crate::ScriptType::DynamicLib) script panicked, the panic was caught by [DynamicScript::catch_panic](crate::scripting::DynamicScript::catch_panic)
+    ScriptPanicked {
+        /// The script name, or its path if it panicked while sending its [ScriptInfo](crate::ScriptInfo)
+        name: String,
+        /// The panic message
+        message: String,
+    },
+    /// The script was built with a different definition of the hook (see [Hook::FINGERPRINT](crate::Hook::FINGERPRINT)), the hook is disabled for this script
+    HookMismatch {
+        /// The script name
+        script: String,
+        /// The hook name
+        hook: String,
+    },
+    /// The script was built against an incompatible rscript protocol, see [PROTOCOL_VERSION](crate::PROTOCOL_VERSION) (or [JSON_LINES_VERSION](crate::JSON_LINES_VERSION) for JSON-lines scripts)
+    ProtocolMismatch {
+        /// The script path
+        path: PathBuf,
+        /// The protocol version of the script, `None` if it didn't send a handshake (it sent something else or exited before sending it), it was likely built with an rscript version that predates it
+        script_version: Option<u32>,
+        /// The protocol version of the main program
+        program_version: u32,
+        /// Set when a DynamicLib script speaks the same protocol but is built with other rscript or rustc versions than the main program ([BuildInfo::current])
+        script_build: Option<BuildInfo>,
+    },
+    /// The script sent bytes that are not a valid message, for example because it printed to stdout\
+    /// A [ScriptType::Daemon](crate::ScriptType::Daemon) script is killed, then restarted according to its [RestartPolicy](crate::RestartPolicy) like a crashed one
+    ProtocolViolation {
+        /// The offending bytes: what was received after the last valid message, 1KiB at most
+        bytes: Vec<u8>,
+    },
+    /// The script uses a [Codec] (or the JSON-lines protocol) that the main program wasn't built with, its cargo feature needs to be enabled
+    UnsupportedCodec {
+        /// The script path
+        path: PathBuf,
+        /// The codec name
+        codec: String,
+    },
+    /// The script was killed because it broke its sandbox policy, by opening a network socket or calling a denied syscall (`sandbox` feature)\
+    /// A [ScriptType::Daemon](crate::ScriptType::Daemon) script is restarted according to its [RestartPolicy](crate::RestartPolicy) like a crashed one
+    SandboxViolation {
+        /// The script path
+        path: PathBuf,
+    },
+    /// The sandbox of a script could not be set up, for example because the kernel doesn't support Landlock (`sandbox` feature)
+    SandboxSetup(String),
+    /// The script was killed because it exceeded one of its [ResourceLimits](crate::ResourceLimits) (Unix only)\
+    /// A [ScriptType::Daemon](crate::ScriptType::Daemon) script is restarted according to its [RestartPolicy](crate::RestartPolicy) like a crashed one
+    ResourceLimitExceeded {
+        /// The script path
+        path: PathBuf,
+        /// The exceeded limit
+        limit: ResourceLimit,
+    },
+}
+
+/// A resource limit that a script can be killed for exceeding, see [Error::ResourceLimitExceeded]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceLimit {
+    /// The memory (address space) limit
+    Memory,
+    /// The CPU time limit
+    CpuTime,
+}
+impl std::fmt::Display for ResourceLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResourceLimit::Memory => write!(f, "memory"),
+            ResourceLimit::CpuTime => write!(f, "CPU time"),
+        }
+    }
 }
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Error::Io(error) => std::fmt::Display::fmt(error, f),
             Error::Bincode(error) => std::fmt::Display::fmt(error, f),
+            Error::Codec { codec, message } => write!(f, "{} error: {}", codec.name(), message),
             Error::ScriptIsNotListeningForHook => write!(
                 f,
                 "Could not trigger the hook, because the script is not listening for it"
@@ -41,6 +132,78 @@ impl std::fmt::Display for Error {
             Error::DynamicLibError(error) => {
                 write!(f, "Failed to load dynamic library:\n{}", error)
             }
+            Error::Timeout(timeout) => {
+                write!(f, "The script did not answer within {:?}", timeout)
+            }
+            Error::ScriptCrashed { name, status } => {
+                write!(f, "The script {} crashed ({})", name, status)
+            }
+            Error::HostFunction(error) => write!(f, "Host function call failed: {}", error),
+            Error::ScriptPanicked { name, message } => {
+                write!(f, "The script {} panicked: {}", name, message)
+            }
+            Error::HookMismatch { script, hook } => write!(
+                f,
+                "The script {} was built with a different definition of the hook {}, the hook is disabled for this script",
+                script, hook
+            ),
+            Error::ProtocolMismatch {
+                path,
+                script_build: Some(script_build),
+                ..
+            } => write!(
+                f,
+                "The script {} is {} but the program is {}, dynamic libraries must be built with the same versions",
+                path.display(),
+                script_build,
+                BuildInfo::current()
+            ),
+            Error::ProtocolMismatch {
+                path,
+                script_version: Some(script_version),
+                program_version,
+                ..
+            } => write!(
+                f,
+                "The script {} uses rscript protocol version {} but the program uses version {}",
+                path.display(),
+                script_version,
+                program_version
+            ),
+            Error::ProtocolMismatch {
+                path,
+                script_version: None,
+                program_version,
+                ..
+            } => write!(
+                f,
+                "The script {} didn't send an rscript handshake, it's likely built with an older rscript or it's a JSON-lines script without the `rscript: json-lines` marker (the program uses protocol version {})",
+                path.display(),
+                program_version
+            ),
+            Error::ProtocolViolation { bytes } => write!(
+                f,
+                "The script broke the protocol, it sent \"{}\"",
+                String::from_utf8_lossy(bytes).escape_debug()
+            ),
+            Error::UnsupportedCodec { path, codec } => write!(
+                f,
+                "The script {} uses the {} codec, but the program wasn't built with it",
+                path.display(),
+                codec
+            ),
+            Error::SandboxViolation { path } => write!(
+                f,
+                "The script {} was killed because it broke its sandbox policy",
+                path.display()
+            ),
+            Error::SandboxSetup(error) => write!(f, "Failed to set up the script sandbox: {}", error),
+            Error::ResourceLimitExceeded { path, limit } => write!(
+                f,
+                "The script {} was killed because it exceeded its {} limit",
+                path.display(),
+                limit
+            ),
         }
     }
 }
@@ -0,0 +1,142 @@
+use crate::{
+    frame::{self, Kind},
+    Codec, Error, Event, Script, ScriptId, ScriptManager, ScriptMessage, ScriptTypeInternal, State,
+    Wire,
+};
+
+/// The name and the encoded data of an event
+type RawEvent = (String, Vec<u8>);
+
+/// Decode the event at the start of `bytes`, returns it with its length, or `None` if it's not fully received yet\
+/// No hook is triggered while events are polled, so any other message is reported with [Error::ProtocolViolation]
+pub(crate) fn decode_event(wire: Wire, bytes: &[u8]) -> Result<Option<(RawEvent, usize)>, Error> {
+    let (event, len) = match wire {
+        Wire::Native(codec) => {
+            match frame::decode_prefix::<ScriptMessage>(bytes, codec, Kind::ScriptMessage)? {
+                Some((message, len)) => (message?.into_event(), len),
+                None => return Ok(None),
+            }
+        }
+        #[cfg(feature = "json")]
+        Wire::JsonLines => match crate::json_lines::decode_line(bytes)? {
+            Some((line, len)) => (line.into_event(), len),
+            None => return Ok(None),
+        },
+    };
+    match event {
+        Some(event) => Ok(Some((event, len))),
+        None => Err(frame::violation(&bytes[..len])),
+    }
+}
+
+/// An [Event] sent by a script, returned by [ScriptManager::poll_events]\
+/// Use [ScriptEvent::get] to decode it
+#[derive(Debug)]
+pub struct ScriptEvent {
+    /// The id of the script that sent the event
+    pub id: ScriptId,
+    /// The name of the script that sent the event
+    pub name: String,
+    event: String,
+    codec: Codec,
+    data: Vec<u8>,
+}
+
+impl ScriptEvent {
+    pub(crate) fn new(
+        id: ScriptId,
+        name: String,
+        event: String,
+        codec: Codec,
+        data: Vec<u8>,
+    ) -> Self {
+        Self {
+            id,
+            name,
+            event,
+            codec,
+            data,
+        }
+    }
+    /// The [Event::NAME] of the event
+    pub fn event_name(&self) -> &str {
+        &self.event
+    }
+    /// Check if the event is an `E`
+    pub fn is<E: Event>(&self) -> bool {
+        self.event == E::NAME
+    }
+    /// Decode the event as an `E`, `None` is returned if it's a different event
+    pub fn get<E: Event>(&self) -> Option<Result<E, Error>> {
+        if self.is::<E>() {
+            Some(self.codec.decode(&self.data))
+        } else {
+            None
+        }
+    }
+}
+
+impl ScriptManager {
+    /// Collect the events sent by the scripts since the last call, it doesn't block\
+    /// Events received while triggering hooks are kept until they are polled\
+    /// A script that sends something other than an event while no hook is triggered is reported with [Error::ProtocolViolation] and restarted like a crashed one (see [Script::set_restart_policy])
+    ///
+    /// ```rust, no_run
+    /// # use rscript::*;
+    /// # #[derive(serde::Serialize, serde::Deserialize)]
+    /// # struct FileChanged(std::path::PathBuf);
+    /// # impl Event for FileChanged {
+    /// #   const NAME: &'static str = "FileChanged";
+    /// # }
+    /// # let mut sm = ScriptManager::default();
+    /// for event in sm.poll_events() {
+    ///     match event {
+    ///         Ok(event) => {
+    ///             if let Some(Ok(FileChanged(path))) = event.get::<FileChanged>() {
+    ///                 println!("{} changed {}", event.name, path.display());
+    ///             }
+    ///         }
+    ///         Err(e) => eprintln!("{}", e),
+    ///     }
+    /// }
+    /// ```
+    pub fn poll_events(&mut self) -> impl Iterator<Item = Result<ScriptEvent, Error>> + '_ {
+        self.scripts.iter_mut().flat_map(Script::poll_events)
+    }
+}
+
+impl Script {
+    /// Receive the events that a daemon already sent, along with the ones received while triggering hooks
+    fn poll_events(&mut self) -> Vec<Result<ScriptEvent, Error>> {
+        let mut error = None;
+        let (failed, wire) = (self.has_failed(), self.wire);
+        if let (ScriptTypeInternal::Daemon(process), false) = (&mut self.script, failed) {
+            loop {
+                let received = process.try_read_with(|bytes| decode_event(wire, bytes));
+                match received {
+                    Ok(Some(event)) => self.events.push_back(event),
+                    Ok(None) => break,
+                    Err(e) => {
+                        error = Some(e);
+                        break;
+                    }
+                }
+            }
+            // The script is in an unknown state, daemons that broke the protocol are restarted like crashed ones
+            if let Some(error) = &error {
+                process.kill();
+                if let Error::ProtocolViolation { .. } = error {
+                    self.restart();
+                } else {
+                    self.state = State::Failed;
+                }
+            }
+        }
+        let (id, name, codec) = (self.id, &self.metadata.name, self.codec());
+        self.events
+            .drain(..)
+            .map(|(event, data)| Ok(ScriptEvent::new(id, name.clone(), event, codec, data)))
+            .chain(error.map(Err))
+            .collect()
+    }
+}
@@ -0,0 +1,141 @@
+//! Framing of the messages exchanged with [Scripter](crate::scripting::Scripter) scripts after the handshake
+//!
+//! A frame is a 7 bytes header followed by the payload, which is encoded with the script [Codec]:
+//! - 2 bytes of magic, its first byte is 0xFF which never appears in UTF-8 text, so text printed to stdout by mistake can't be taken for a frame
+//! - 1 byte for the [Kind] of the payload
+//! - The payload length, a little endian u32, at most [MAX_PAYLOAD_LEN]
+
+use crate::{trace, Codec, Error};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    convert::TryInto,
+    io::{Read, Write},
+};
+
+const MAGIC: [u8; 2] = [0xFF, b'R'];
+const HEADER_LEN: usize = 7;
+/// Payloads are limited so a corrupted header can't make the reader allocate gigabytes, a longer one is a [Error::ProtocolViolation]
+const MAX_PAYLOAD_LEN: usize = 64 << 20;
+/// How many of the offending bytes are kept in [Error::ProtocolViolation]
+const VIOLATION_BYTES: usize = 1024;
+
+/// What a frame carries, the reader checks that it's what it expects
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Kind {
+    /// [Message](crate::Message), sent by the main program
+    Message = 0,
+    /// The name of the triggered hook
+    HookName = 1,
+    /// The triggered hook
+    Hook = 2,
+    /// The answer to a [HostFunction](crate::HostFunction) call
+    HostReply = 3,
+    /// [ScriptInfo](crate::ScriptInfo), sent by the script after the handshake
+    ScriptInfo = 4,
+    /// [ScriptMessage](crate::ScriptMessage)
+    ScriptMessage = 5,
+    /// The hook output
+    Output = 6,
+}
+
+/// Encode `value` in a frame
+pub(crate) fn encode<T: Serialize + ?Sized>(
+    codec: Codec,
+    kind: Kind,
+    value: &T,
+) -> Result<Vec<u8>, Error> {
+    let payload = codec.encode(value)?;
+    if payload.len() > MAX_PAYLOAD_LEN {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!(
+                "message of {} bytes is over the {} bytes limit",
+                payload.len(),
+                MAX_PAYLOAD_LEN
+            ),
+        )
+        .into());
+    }
+    if let Kind::Hook = kind {
+        trace::hook_bytes(payload.len());
+    }
+    let mut frame = Vec::with_capacity(HEADER_LEN + payload.len());
+    frame.extend(MAGIC);
+    frame.push(kind as u8);
+    frame.extend((payload.len() as u32).to_le_bytes());
+    frame.extend(payload);
+    Ok(frame)
+}
+
+/// Encode `value` in a frame and write it to `writer`
+pub(crate) fn write<W: Write, T: Serialize + ?Sized>(
+    mut writer: W,
+    codec: Codec,
+    kind: Kind,
+    value: &T,
+) -> Result<(), Error> {
+    writer.write_all(&encode(codec, kind, value)?)?;
+    Ok(())
+}
+
+/// Read a frame from `reader`, it blocks until the whole frame is received
+pub(crate) fn read<R: Read, T: DeserializeOwned>(
+    mut reader: R,
+    codec: Codec,
+    kind: Kind,
+) -> Result<T, Error> {
+    let mut header = [0; HEADER_LEN];
+    reader.read_exact(&mut header)?;
+    let len = check_header(&header, kind).ok_or_else(|| violation(&header))?;
+    let mut payload = vec![0; len];
+    reader.read_exact(&mut payload)?;
+    codec.decode(&payload)
+}
+
+/// A frame decoded by [decode_prefix]: the value, or the error if the payload failed to decode, and the frame length
+pub(crate) type Decoded<T> = Option<(Result<T, Error>, usize)>;
+
+/// Decode the frame at the start of `bytes`, returns the value and the frame length, or `None` if the frame is not fully received yet\
+/// Bytes that can't start a frame are reported as soon as they are received\
+/// The length is returned even if the payload fails to decode, so the reader can skip the frame
+pub(crate) fn decode_prefix<T: DeserializeOwned>(
+    bytes: &[u8],
+    codec: Codec,
+    kind: Kind,
+) -> Result<Decoded<T>, Error> {
+    if bytes.len() < HEADER_LEN {
+        return if MAGIC.starts_with(&bytes[..bytes.len().min(MAGIC.len())])
+            && bytes.get(MAGIC.len()).is_none_or(|tag| *tag == kind as u8)
+        {
+            Ok(None)
+        } else {
+            Err(violation(bytes))
+        };
+    }
+    let len = check_header(&bytes[..HEADER_LEN], kind).ok_or_else(|| violation(bytes))?;
+    match bytes.get(HEADER_LEN..HEADER_LEN + len) {
+        Some(payload) => {
+            if let Kind::Output = kind {
+                trace::output_bytes(len);
+            }
+            Ok(Some((codec.decode(payload), HEADER_LEN + len)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Returns the payload length if the header is valid
+fn check_header(header: &[u8], kind: Kind) -> Option<usize> {
+    if header[..MAGIC.len()] != MAGIC || header[MAGIC.len()] != kind as u8 {
+        return None;
+    }
+    let len: [u8; 4] = header[MAGIC.len() + 1..HEADER_LEN].try_into().ok()?;
+    Some(u32::from_le_bytes(len) as usize).filter(|len| *len <= MAX_PAYLOAD_LEN)
+}
+
+pub(crate) fn violation(bytes: &[u8]) -> Error {
+    Error::ProtocolViolation {
+        bytes: bytes[..bytes.len().min(VIOLATION_BYTES)].to_vec(),
+    }
+}
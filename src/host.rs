@@ -0,0 +1,63 @@
+use crate::{
+    scripting::{self, FFiData, FFiHost, FFiStr},
+    Codec, HostFunction,
+};
+use std::{collections::HashMap, ffi::c_void, panic::AssertUnwindSafe};
+
+type Handler = Box<dyn Fn(Codec, &[u8]) -> Result<Vec<u8>, String> + Send + Sync>;
+
+/// Handlers of the [HostFunction]s registered by the main crate
+#[derive(Default)]
+pub(crate) struct HostFunctions(HashMap<&'static str, Handler>);
+
+impl HostFunctions {
+    pub(crate) fn register<F: HostFunction>(
+        &mut self,
+        handler: impl Fn(F) -> <F as HostFunction>::Output + Send + Sync + 'static,
+    ) {
+        self.0.insert(
+            F::NAME,
+            Box::new(move |codec, data| {
+                let function: F = codec.decode(data).map_err(|e| e.to_string())?;
+                codec.encode(&handler(function)).map_err(|e| e.to_string())
+            }),
+        );
+    }
+    /// Run the handler of the function `name` on its argument encoded with `codec`, returns the encoded output
+    pub(crate) fn call(&self, name: &str, codec: Codec, data: &[u8]) -> Result<Vec<u8>, String> {
+        match self.0.get(name) {
+            Some(handler) => handler(codec, data),
+            None => Err(format!("host function {} is not registered", name)),
+        }
+    }
+    /// Expose the handlers to [ScriptType::DynamicLib](crate::ScriptType::DynamicLib) scripts, `codec` is the script codec\
+    /// The returned value must not outlive `self`
+    pub(crate) fn as_ffi(&self, codec: Codec) -> FFiHost {
+        extern "C" fn call(ctx: *const c_void, name: FFiStr, data: FFiData) -> FFiData {
+            let host = unsafe { &*(ctx as *const HostFunctions) };
+            // Answer with the codec of the call
+            let reply = match data.codec() {
+                // A panic can't unwind across the C ABI (it aborts the main program), so it's sent to the script as an error
+                Ok(codec) => std::panic::catch_unwind(AssertUnwindSafe(|| {
+                    host.call(name.as_str(), codec, data.as_bytes())
+                }))
+                .unwrap_or_else(|payload| {
+                    Err(format!(
+                        "host function {} panicked: {}",
+                        name.as_str(),
+                        scripting::panic_message(&*payload)
+                    ))
+                }),
+                Err(codec) => Err(format!("unsupported codec {}", codec)),
+            };
+            let codec = data.codec().unwrap_or_default();
+            FFiData::serialize_from(codec, &reply)
+                .expect("Result<Vec<u8>, String> is always serialize-able")
+        }
+        FFiHost {
+            ctx: self as *const Self as *const c_void,
+            call,
+            codec: codec.id(),
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! The JSON-lines protocol, spoken by scripts that are not written in Rust\
+//! Every message is a JSON object on its own line, tagged by its `type` field, it's specified in `JSON_LINES.md`
+
+use crate::{
+    frame, process::Process, trace, Codec, Error, Hook, HostFunctions, ScriptInfo, ScriptType,
+    VersionReq, JSON_LINES_VERSION,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::VecDeque, io::Write, path::Path};
+
+/// Asks a daemon to cleanup and exit
+pub(crate) const SHUTDOWN_LINE: &str = "{\"type\":\"shutdown\"}\n";
+
+/// Lines sent by the main program, besides the greeting and the shutdown
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum HostLine<'a, H> {
+    /// Trigger the hook named `hook`
+    Execute { hook: &'a str, data: &'a H },
+    /// The answer to a `host_call`, only one of its fields is present
+    HostReply {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        ok: Option<Value>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+}
+
+/// Lines sent by the script
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum ScriptLine {
+    /// The answer to the greeting
+    Info {
+        protocol: u32,
+        name: String,
+        script_type: ScriptType,
+        #[serde(default)]
+        hooks: Box<[String]>,
+        version_requirement: VersionReq,
+    },
+    /// The output of the triggered hook
+    Output {
+        #[serde(default)]
+        data: Value,
+    },
+    /// Call a [HostFunction](crate::HostFunction) while handling a hook
+    HostCall {
+        name: String,
+        #[serde(default)]
+        data: Value,
+    },
+    /// Send an [Event](crate::Event)
+    Event {
+        name: String,
+        #[serde(default)]
+        data: Value,
+    },
+}
+
+impl ScriptLine {
+    /// The event name and data encoded with [Codec::Json], `None` if it's not an event
+    pub(crate) fn into_event(self) -> Option<(String, Vec<u8>)> {
+        match self {
+            Self::Event { name, data } => Some((name, data.to_string().into_bytes())),
+            _ => None,
+        }
+    }
+}
+
+/// Check the `info` line of the script at `path`
+pub(crate) fn script_info(line: ScriptLine, path: &Path) -> Result<ScriptInfo, Error> {
+    match line {
+        ScriptLine::Info {
+            protocol,
+            name,
+            script_type: script_type @ (ScriptType::Daemon | ScriptType::OneShot),
+            hooks,
+            version_requirement,
+        } => {
+            if protocol != JSON_LINES_VERSION {
+                return Err(Error::ProtocolMismatch {
+                    path: path.to_path_buf(),
+                    script_version: Some(protocol),
+                    program_version: JSON_LINES_VERSION,
+                    script_build: None,
+                });
+            }
+            Ok(ScriptInfo {
+                name,
+                script_type,
+                hooks,
+                version_requirement,
+                // Fingerprints are computed by the Rust hook definitions, they can't be checked
+                fingerprints: Default::default(),
+            })
+        }
+        ScriptLine::Info { .. } => Err(invalid("script_type must be Daemon or OneShot")),
+        _ => Err(invalid("script must answer the greeting with an info line")),
+    }
+}
+
+/// Trigger the hook `H` on a JSON-lines script and answer its host functions calls until it sends the output
+pub(crate) fn execute<H: Hook>(
+    script: &mut Process,
+    hook: &H,
+    host: &HostFunctions,
+    events: &mut VecDeque<(String, Vec<u8>)>,
+) -> Result<<H as Hook>::Output, Error> {
+    script.input()?.write_all(&execute_line(hook)?)?;
+    loop {
+        match step(script.read_with(decode_line)?, host, events)? {
+            Step::Output(output) => return Ok(output),
+            Step::Reply(reply) => script.input()?.write_all(&reply)?,
+            Step::Continue => (),
+        }
+    }
+}
+
+/// Decode the line at the start of `bytes`, returns it with its length (newline included), or `None` if it's not fully received yet\
+/// A line that is not a message of the protocol, such as text printed to stdout by mistake, is reported with [Error::ProtocolViolation]
+pub(crate) fn decode_line(bytes: &[u8]) -> Result<Option<(ScriptLine, usize)>, Error> {
+    let len = match bytes.iter().position(|byte| *byte == b'\n') {
+        Some(end) => end + 1,
+        None => return Ok(None),
+    };
+    match serde_json::from_slice(&bytes[..len]) {
+        Ok(line) => {
+            if let ScriptLine::Output { .. } = line {
+                trace::output_bytes(len);
+            }
+            Ok(Some((line, len)))
+        }
+        Err(_) => Err(frame::violation(&bytes[..len])),
+    }
+}
+
+/// The line that triggers `hook`
+pub(crate) fn execute_line<H: Hook>(hook: &H) -> Result<Vec<u8>, Error> {
+    let line = Codec::Json.encode(&HostLine::Execute {
+        hook: H::NAME,
+        data: hook,
+    })?;
+    trace::hook_bytes(line.len());
+    Ok(line)
+}
+
+/// What to do after receiving a line while a hook is triggered
+pub(crate) enum Step<O> {
+    /// The script sent the hook output
+    Output(O),
+    /// Send this line to the script, then keep reading
+    Reply(Vec<u8>),
+    /// Keep reading
+    Continue,
+}
+
+/// Handle a line received while a hook is triggered, events are pushed to `events`
+pub(crate) fn step<O: DeserializeOwned>(
+    line: ScriptLine,
+    host: &HostFunctions,
+    events: &mut VecDeque<(String, Vec<u8>)>,
+) -> Result<Step<O>, Error> {
+    match line {
+        ScriptLine::Output { data } => Ok(Step::Output(
+            serde_json::from_value(data).map_err(|e| Codec::Json.error(e))?,
+        )),
+        ScriptLine::HostCall { name, data } => {
+            let (ok, error) = match host.call(&name, Codec::Json, data.to_string().as_bytes()) {
+                Ok(output) => (Some(Codec::Json.decode(&output)?), None),
+                Err(error) => (None, Some(error)),
+            };
+            let reply: HostLine<()> = HostLine::HostReply { ok, error };
+            Ok(Step::Reply(Codec::Json.encode(&reply)?))
+        }
+        line @ ScriptLine::Event { .. } => {
+            events.extend(line.into_event());
+            Ok(Step::Continue)
+        }
+        ScriptLine::Info { .. } => Err(invalid(
+            "script sent an info line while a hook was triggered",
+        )),
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message).into()
+}
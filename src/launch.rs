@@ -0,0 +1,125 @@
+use std::{
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+/// How a process script ([ScriptType::OneShot](crate::ScriptType::OneShot) or [ScriptType::Daemon](crate::ScriptType::Daemon)) is started, see [ScriptManager::set_launch_config](crate::ScriptManager::set_launch_config)\
+/// It's used every time the script is spawned: when it's greeted, and for every trigger of a OneShot script\
+/// By default the script inherits the main program environment, working directory and stderr, and receives no arguments
+///
+/// ```rust, no_run
+/// # use rscript::*;
+/// let mut config = ScriptLaunchConfig::default();
+/// config
+///     .env_allowlist(["PATH", "HOME"])
+///     .env("MY_PROJECT_SCRIPT", "1")
+///     .arg("--quiet")
+///     .current_dir("/tmp")
+///     .stderr(ScriptStderr::Discard);
+/// let mut sm = ScriptManager::default();
+/// sm.set_launch_config(config);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ScriptLaunchConfig {
+    env_allowlist: Option<Vec<OsString>>,
+    envs: Vec<(OsString, OsString)>,
+    args: Vec<OsString>,
+    current_dir: Option<PathBuf>,
+    stderr: ScriptStderr,
+}
+
+/// What happens to the stderr of a script, see [ScriptLaunchConfig::stderr]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScriptStderr {
+    /// The script writes to the main program stderr
+    #[default]
+    Inherit,
+    /// The script stderr is discarded
+    Discard,
+    /// The script stderr is piped and its last `lines` lines are kept, see [Script::stderr](crate::Script::stderr)
+    Capture {
+        /// How many lines are kept, older lines are dropped
+        lines: usize,
+    },
+    /// The script stderr is piped and every line is logged with the [log](https://docs.rs/log) crate (`log` feature)\
+    /// The records have the target `rscript::stderr`, the info level and the script name as prefix
+    #[cfg(feature = "log")]
+    Log,
+}
+
+impl ScriptLaunchConfig {
+    /// Only pass the variables named in `keys` from the main program environment to the script, an empty list passes none\
+    /// The variables set with [ScriptLaunchConfig::env] are always passed
+    pub fn env_allowlist<I, K>(&mut self, keys: I) -> &mut Self
+    where
+        I: IntoIterator<Item = K>,
+        K: Into<OsString>,
+    {
+        self.env_allowlist = Some(keys.into_iter().map(Into::into).collect());
+        self
+    }
+    /// Set the variable `key` to `value` in the script environment
+    pub fn env<K: Into<OsString>, V: Into<OsString>>(&mut self, key: K, value: V) -> &mut Self {
+        self.envs.push((key.into(), value.into()));
+        self
+    }
+    /// Pass `arg` to the script, after the previously added arguments
+    pub fn arg<A: Into<OsString>>(&mut self, arg: A) -> &mut Self {
+        self.args.push(arg.into());
+        self
+    }
+    /// Run the script in `dir` instead of the main program working directory
+    pub fn current_dir<P: Into<PathBuf>>(&mut self, dir: P) -> &mut Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+    /// Set what happens to the script stderr, it's inherited by default
+    pub fn stderr(&mut self, stderr: ScriptStderr) -> &mut Self {
+        self.stderr = stderr;
+        self
+    }
+
+    /// How the script stderr is handled
+    pub(crate) fn stderr_mode(&self) -> ScriptStderr {
+        self.stderr
+    }
+    /// The command that spawns the script at `path`, its stdin and stdout are left to the caller
+    pub(crate) fn command(&self, path: &Path) -> io::Result<Command> {
+        let mut command = match &self.current_dir {
+            // A relative path would be resolved from the new working directory
+            Some(dir) => {
+                let mut command = Command::new(std::path::absolute(path)?);
+                command.current_dir(dir);
+                command
+            }
+            None => Command::new(path),
+        };
+        if let Some(allowlist) = &self.env_allowlist {
+            command.env_clear().envs(
+                allowlist
+                    .iter()
+                    .filter_map(|key| Some((key, std::env::var_os(key)?))),
+            );
+        }
+        // A main program that is a script itself doesn't pass its own channel down, the pipes of [ProtocolChannel::Pipes](crate::ProtocolChannel) set it again
+        command.env_remove(crate::PROTOCOL_FDS_VAR);
+        command.envs(self.envs.iter().map(|(key, value)| (key, value)));
+        command.args(&self.args);
+        match self.stderr {
+            ScriptStderr::Inherit => {}
+            ScriptStderr::Discard => {
+                command.stderr(Stdio::null());
+            }
+            ScriptStderr::Capture { .. } => {
+                command.stderr(Stdio::piped());
+            }
+            #[cfg(feature = "log")]
+            ScriptStderr::Log => {
+                command.stderr(Stdio::piped());
+            }
+        }
+        Ok(command)
+    }
+}
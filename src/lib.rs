@@ -25,13 +25,28 @@
 //!     - DynamicLibary: [vim_mode](https://github.com/sigmaSd/IRust/tree/master/script_examples/irust_vim_dylib)
 //!
 //! Check out the [examples](https://github.com/sigmaSd/Rscript/tree/master/examples) for more info.
+//!
+//! Scripts that are not written in Rust can speak the [JSON-lines protocol](https://github.com/sigmaSd/Rscript/blob/master/JSON_LINES.md) instead, it's enabled with the `json` feature.
+//!
+//! With the `tracing` feature, rscript emits [tracing](https://docs.rs/tracing) spans when it discovers scripts (`discovery`), greets them (`greeting`), loads dynamic libraries (`dylib_load`) and triggers hooks (`trigger`), they carry the script name and type, the hook name, the payload sizes, the duration and the error if any.
 
+use process::{Launcher, Process};
 use scripting::{FFiData, FFiStr};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
+    collections::{HashMap, VecDeque},
+    convert::TryInto,
     env,
-    path::Path,
-    process::{Child, Stdio},
+    ffi::OsStr,
+    fs::File,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 
 // Rexport Version, VersionReq
@@ -45,7 +60,65 @@ pub use semver::VersionReq;
 pub mod scripting;
 
 mod error;
-pub use error::Error;
+pub use error::{Error, ResourceLimit};
+
+mod discovery;
+pub use discovery::{DiscoveryReport, DiscoveryStatus, MismatchedHook};
+
+mod process;
+
+mod codec;
+pub use codec::Codec;
+
+mod host;
+use host::HostFunctions;
+
+mod frame;
+use frame::Kind;
+
+#[cfg(all(feature = "sandbox", target_os = "linux"))]
+mod sandbox;
+#[cfg(all(feature = "sandbox", target_os = "linux"))]
+pub use sandbox::SandboxPolicy;
+
+mod launch;
+pub use launch::{ScriptLaunchConfig, ScriptStderr};
+
+mod stderr;
+use stderr::StderrSink;
+
+mod trace;
+
+mod parallel;
+use parallel::ParallelOutputs;
+
+#[cfg(unix)]
+mod limits;
+#[cfg(unix)]
+pub use limits::ResourceLimits;
+
+#[cfg(feature = "json")]
+mod json_lines;
+
+mod schema;
+pub use schema::{Schema, SchemaHasher};
+
+mod event;
+pub use event::ScriptEvent;
+
+#[cfg(feature = "tokio")]
+mod asynchronous;
+#[cfg(feature = "tokio")]
+pub use asynchronous::{AsyncScript, AsyncScriptManager};
+
+#[cfg(feature = "macros")]
+pub use rscript_macros::{dynamic_script, Hook, Schema};
+
+mod reload;
+pub use reload::ReloadEvent;
+#[cfg(all(feature = "watch", target_os = "linux"))]
+pub use reload::ScriptWatcher;
+use reload::{file_stamp, FileStamp, WatchedDir};
 
 use crate::scripting::DynamicScript;
 
@@ -60,6 +133,8 @@ pub struct ScriptInfo {
     pub hooks: Box<[String]>,
     /// The version requirement of the program that the script will run against
     pub version_requirement: VersionReq,
+    /// The [Hook::FINGERPRINT] of the hooks that have one, keyed by hook name
+    pub fingerprints: HashMap<String, u64>,
 }
 
 impl ScriptInfo {
@@ -75,12 +150,21 @@ impl ScriptInfo {
             script_type,
             hooks: hooks.iter().map(|hook| String::from(*hook)).collect(),
             version_requirement,
+            fingerprints: HashMap::new(),
         }
     }
+    /// Advertise the [Hook::FINGERPRINT] of `H` (if it has one), so the main program can detect that it was built with a different definition of the hook\
+    /// [Scripter::run](scripting::Scripter::run) and the `dynamic_script` macro do it for every handled hook
+    pub fn with_fingerprint<H: Hook>(mut self) -> Self {
+        if let Some(fingerprint) = H::FINGERPRINT {
+            self.fingerprints.insert(H::NAME.into(), fingerprint);
+        }
+        self
+    }
     /// Serialize `ScriptInfo` into `FFiData`
     /// This is needed for writing [ScriptType::DynamicLib] scripts
     pub fn into_ffi_data(self) -> FFiData {
-        FFiData::serialize_from(&self).expect("ScriptInfo is always serialize-able")
+        FFiData::serialize_from(Codec::Bincode, &self).expect("ScriptInfo is always serialize-able")
     }
 }
 
@@ -103,26 +187,316 @@ pub enum ScriptType {
 }
 
 /// ScriptManager holds all the scripts found, it can be constructed with [ScriptManager::default]\
-/// Initially its empty, to populate it, we can use one of the methods to add scripts, currently only [ScriptManager::add_scripts_by_path] is provided
+/// Initially its empty, to populate it, we can use one of the methods to add scripts: [ScriptManager::add_scripts_by_path], [ScriptManager::discover_scripts_by_path] or their dynamic library counterparts
 #[derive(Default)]
 pub struct ScriptManager {
     scripts: Vec<Script>,
+    greeting_timeout: Option<Duration>,
+    execute_timeout: Option<Duration>,
+    launcher: Launcher,
+    hook_timeouts: HashMap<&'static str, Duration>,
+    shutdown_grace: Option<Duration>,
+    // Folders scripts were added from, used by [ScriptManager::reload]
+    watched_dirs: Vec<WatchedDir>,
+    // Scripts that failed to load with the stamp of their file, so reload doesn't retry them until they change
+    failed_paths: HashMap<PathBuf, Option<FileStamp>>,
+    // Shared with the threads of [ScriptManager::trigger_parallel]
+    host_functions: Arc<HostFunctions>,
+    // Fingerprints of the hooks registered with [ScriptManager::register_hook]
+    hook_fingerprints: HashMap<&'static str, u64>,
+}
+
+/// How long daemons have to exit after being asked to, if not specified with [ScriptManager::set_shutdown_grace]
+const DEFAULT_SHUTDOWN_GRACE: Duration = Duration::from_secs(1);
+
+/// Version of the protocol used between the main program and the scripts\
+/// It covers the messages exchanged with [Scripter](scripting::Scripter) scripts and the [DynamicScript] layout, including the bincode encoding of the handshake\
+/// It's bumped on every incompatible change, scripts built against a different version are refused with [Error::ProtocolMismatch]
+pub const PROTOCOL_VERSION: u32 = 7;
+
+/// Version of the JSON-lines protocol (specified in `JSON_LINES.md`), sent in the greeting and in the `info` line of JSON-lines scripts\
+/// It's separate from [PROTOCOL_VERSION] so the scripts written in other languages keep working when only the Rust side changes
+pub const JSON_LINES_VERSION: u32 = 4;
+
+/// Sent by scripts before anything else when greeted (always with bincode), and stored first in [DynamicScript]\
+/// The magic number detects scripts that predate the handshake, `codec` is the [Codec] id the script uses for everything else\
+/// The ABI tag (rscript and rustc versions, see [BuildInfo]) comes last, so scripts with another protocol version are refused before it's read
+#[repr(C)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Handshake {
+    magic: u32,
+    protocol_version: u32,
+    codec: u8,
+    rscript_version: [u8; 32],
+    rustc_version: [u8; 32],
+}
+
+impl Handshake {
+    pub(crate) const CURRENT: Self = Self {
+        magic: u32::from_le_bytes(*b"RSCR"),
+        protocol_version: PROTOCOL_VERSION,
+        codec: Codec::Bincode as u8,
+        rscript_version: tag(env!("CARGO_PKG_VERSION")),
+        rustc_version: tag(env!("RSCRIPT_RUSTC_VERSION")),
+    };
+    pub(crate) const fn with_codec(self, codec: Codec) -> Self {
+        Self {
+            codec: codec as u8,
+            ..self
+        }
+    }
+    /// Check that the script at `path` speaks the same protocol as the main program, returns the codec it uses
+    pub(crate) fn check(&self, path: &Path) -> Result<Codec, Error> {
+        if (self.magic, self.protocol_version)
+            != (Self::CURRENT.magic, Self::CURRENT.protocol_version)
+        {
+            return Err(Error::ProtocolMismatch {
+                path: path.to_path_buf(),
+                script_version: (self.magic == Self::CURRENT.magic)
+                    .then_some(self.protocol_version),
+                program_version: PROTOCOL_VERSION,
+                script_build: None,
+            });
+        }
+        Codec::from_id(self.codec).map_err(|codec| Error::UnsupportedCodec {
+            path: path.to_path_buf(),
+            codec,
+        })
+    }
+    /// Check that the DynamicLib script at `path` is built with the same rscript and rustc versions as the main program, they share their memory layout\
+    /// Process scripts don't need it, their messages are serialized
+    pub(crate) fn check_build(&self, path: &Path) -> Result<(), Error> {
+        if (self.rscript_version, self.rustc_version)
+            != (Self::CURRENT.rscript_version, Self::CURRENT.rustc_version)
+        {
+            return Err(Error::ProtocolMismatch {
+                path: path.to_path_buf(),
+                script_version: Some(self.protocol_version),
+                program_version: PROTOCOL_VERSION,
+                script_build: Some(self.build_info()),
+            });
+        }
+        Ok(())
+    }
+    /// Decode the handshake at the start of `bytes`, see [Process::read_with]\
+    /// Anything else is refused as soon as its first bytes are received, it would never make a whole handshake\
+    /// The protocol version is checked before the rest of the handshake is received, since its layout might be different
+    pub(crate) fn decode_prefix(bytes: &[u8], path: &Path) -> Result<Option<(Self, usize)>, Error> {
+        let word = |at: usize| {
+            bytes
+                .get(at..at + 4)
+                .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+        };
+        match (word(0), word(4)) {
+            (Some(magic), _) if magic != Self::CURRENT.magic => Err(Self::unrecognized(path)),
+            (_, Some(protocol_version)) if protocol_version != PROTOCOL_VERSION => {
+                Err(Error::ProtocolMismatch {
+                    path: path.to_path_buf(),
+                    script_version: Some(protocol_version),
+                    program_version: PROTOCOL_VERSION,
+                    script_build: None,
+                })
+            }
+            _ => Codec::Bincode.read_prefix(bytes),
+        }
+    }
+    /// Map `error`, raised while reading the handshake, a script that exited or closed its output without sending it is refused like one that sent something else
+    pub(crate) fn missing(error: Error, path: &Path) -> Error {
+        if is_disconnected(&error) {
+            Self::unrecognized(path)
+        } else {
+            error
+        }
+    }
+    /// The script didn't send a handshake, it's likely built with an rscript version that predates it
+    fn unrecognized(path: &Path) -> Error {
+        Error::ProtocolMismatch {
+            path: path.to_path_buf(),
+            script_version: None,
+            program_version: PROTOCOL_VERSION,
+            script_build: None,
+        }
+    }
+    pub(crate) fn build_info(&self) -> BuildInfo {
+        let untag = |tag: &[u8]| {
+            let len = tag.iter().position(|&byte| byte == 0).unwrap_or(tag.len());
+            String::from_utf8_lossy(&tag[..len]).into_owned()
+        };
+        BuildInfo {
+            rscript_version: untag(&self.rscript_version),
+            rustc_version: untag(&self.rustc_version),
+        }
+    }
 }
 
-/// Message that is sent from the main crate to the script each time it wants to interact with it\
-/// Greeting message must be sent when looking for scripts\
-/// Execute message must be sent each time a hook is triggered
+/// A version string stored in a fixed size field of the handshake, padded with zeros (longer strings are truncated)
+const fn tag(version: &str) -> [u8; 32] {
+    let bytes = version.as_bytes();
+    let mut tag = [0; 32];
+    let mut i = 0;
+    while i < bytes.len() && i < tag.len() {
+        tag[i] = bytes[i];
+        i += 1;
+    }
+    tag
+}
+
+/// The rscript and rustc versions a script was built with, see [DynamicScript::build_info]\
+/// DynamicLib scripts must be built with the same versions as the main program, they are refused with [Error::ProtocolMismatch] otherwise
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildInfo {
+    /// The rscript crate version
+    pub rscript_version: String,
+    /// The rustc release and short commit hash, like `1.87.0 17067e9ac`
+    pub rustc_version: String,
+}
+
+impl BuildInfo {
+    /// The versions the main program is built with
+    pub fn current() -> Self {
+        Handshake::CURRENT.build_info()
+    }
+}
+
+impl std::fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "rscript {} built with rustc {}",
+            self.rscript_version, self.rustc_version
+        )
+    }
+}
+
+/// How a script process is greeted when looking for scripts, it's chosen from the script file before anything is written, since the script could predate the handshake\
+/// [Scripter](scripting::Scripter) scripts understand both greetings and answer with a [Handshake] then their [ScriptInfo], JSON-lines scripts answer with an `info` line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Greeting {
+    /// The script has the JSON-lines marker, it's greeted with a JSON line
+    JsonLines,
+    /// A text file run by an interpreter (it starts with `#!`), it's greeted with a JSON line and its answer tells which protocol it speaks: it starts with `{` for JSON-lines scripts and with the [Handshake] magic number for [Scripter](scripting::Scripter) scripts (run by rust-script for example)
+    Line,
+    /// A compiled executable, it's greeted with [NATIVE_GREETING] and must answer with a [Handshake]
+    Native,
+}
+
+impl Greeting {
+    pub(crate) fn of(path: &Path) -> Self {
+        let mut head = vec![];
+        // A script that can't be read can't be started either
+        let _ = File::open(path).and_then(|file| file.take(1024).read_to_end(&mut head));
+        if head.split(|byte| *byte == b'\n').take(2).any(|line| {
+            line.windows(JSON_LINES_MARKER.len())
+                .any(|window| window == JSON_LINES_MARKER)
+        }) {
+            Self::JsonLines
+        } else if head.starts_with(b"#!") {
+            Self::Line
+        } else {
+            Self::Native
+        }
+    }
+    pub(crate) fn message(self) -> Vec<u8> {
+        match self {
+            Self::JsonLines | Self::Line => format!(
+                "{{\"type\":\"greeting\",\"protocol\":{}}}\n",
+                JSON_LINES_VERSION
+            )
+            .into_bytes(),
+            Self::Native => NATIVE_GREETING.to_vec(),
+        }
+    }
+}
+
+/// The greeting of compiled scripts: the bincode `Message::Greeting` of the rscript versions that predate the handshake, so they answer it and are refused with [Error::ProtocolMismatch] instead of failing on a message they can't decode\
+/// The newline lets a JSON-lines script without the marker read it and fail at once instead of waiting for the rest of the line
+pub(crate) const NATIVE_GREETING: &[u8] = b"\0\0\0\0\n";
+
+/// Whether `line` is the greeting of a main program, whatever its protocol version is
+pub(crate) fn is_greeting_line(line: &[u8]) -> bool {
+    line.starts_with(br#"{"type":"greeting","#)
+}
+
+/// The first message sent to a [ScriptType::OneShot] [Scripter](scripting::Scripter) script when triggering a hook, the hook name and the hook follow it
+pub(crate) const EXECUTE_LINE: &str = "{\"type\":\"execute\"}\n";
+
+/// Message that is sent from the main crate to a [ScriptType::Daemon] script, after the greeting, each time it wants to interact with it\
+/// Like every message exchanged with [Scripter](scripting::Scripter) scripts after the handshake, it's sent in a frame (see `frame.rs`) and encoded with the script [Codec]\
+/// Execute message must be sent each time a hook is triggered\
+/// Shutdown message is sent to daemons when the [ScriptManager] is dropped
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub(crate) enum Message {
-    /// Greet a script, the script must respond with [ScriptInfo]
-    Greeting,
     /// Must be sent each time a hook is triggered
     Execute,
+    /// Ask a daemon to cleanup and exit
+    Shutdown,
+}
+
+/// How the main program talks to a process script, it's decided by the script answer to the greeting
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Wire {
+    /// The rscript protocol, spoken by [Scripter](scripting::Scripter) scripts, messages are encoded with the script [Codec]
+    Native(Codec),
+    /// The JSON-lines protocol, spoken by scripts that are not written in Rust
+    #[cfg(feature = "json")]
+    JsonLines,
+}
+
+impl Wire {
+    /// The codec of the hooks, outputs and events
+    fn codec(self) -> Codec {
+        match self {
+            Self::Native(codec) => codec,
+            #[cfg(feature = "json")]
+            Self::JsonLines => Codec::Json,
+        }
+    }
+    /// The message that asks a daemon to exit
+    fn shutdown(self) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::Native(codec) => frame::encode(codec, Kind::Message, &Message::Shutdown),
+            #[cfg(feature = "json")]
+            Self::JsonLines => Ok(json_lines::SHUTDOWN_LINE.as_bytes().to_vec()),
+        }
+    }
+}
+
+/// Message that is sent from the script to the main crate while it's handling a hook\
+/// [ScriptMessage::Event] can also be sent by daemons at any time
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) enum ScriptMessage {
+    /// The hook output follows
+    Output,
+    /// Call a [HostFunction], the main crate answers with `Result<Vec<u8>, String>` (the serialized output or an error)
+    HostCall {
+        /// [HostFunction::NAME]
+        name: String,
+        /// The serialized function argument
+        data: Vec<u8>,
+    },
+    /// Send an [Event] to the main crate, it doesn't expect an answer
+    Event {
+        /// [Event::NAME]
+        name: String,
+        /// The serialized event
+        data: Vec<u8>,
+    },
+}
+
+impl ScriptMessage {
+    /// The event name and data, `None` if it's not an event
+    fn into_event(self) -> Option<(String, Vec<u8>)> {
+        match self {
+            Self::Event { name, data } => Some((name, data)),
+            _ => None,
+        }
+    }
 }
 
 impl ScriptManager {
     /// Look for scripts in the specified folder\
     /// It requires specifying a [VersionReq] so the script manager can check for incompatibility and if that's the case it will return an error: [Error::ScriptVersionMismatch]\
+    /// If any script fails to load, no script from this folder is added, see [ScriptManager::discover_scripts_by_path] for a non-fatal alternative
     ///
     /// ```rust, no_run
     /// # use rscript::*;
@@ -136,54 +510,87 @@ impl ScriptManager {
         path: P,
         version: Version,
     ) -> Result<(), Error> {
-        fn start_script(path: &Path, version: &Version) -> Result<Script, Error> {
-            let mut script = std::process::Command::new(path)
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .spawn()?;
-
-            // Send Greeting Message
-            let stdin = script.stdin.as_mut().expect("stdin is piped");
-            bincode::serialize_into(stdin, &Message::Greeting)?;
-
-            // Receive ScriptInfo
-            let stdout = script.stdout.as_mut().expect("stdout is piped");
-            let metadata: ScriptInfo = bincode::deserialize_from(stdout)?;
-
-            // Check if the provided version matches the script version
-            if !metadata.version_requirement.matches(version) {
-                return Err(Error::ScriptVersionMismatch {
-                    program_actual_version: version.clone(),
-                    program_required_version: metadata.version_requirement,
-                });
+        trace::discovery(path.as_ref()).run(|| {
+            let mut scripts = vec![];
+            for path in script_paths(path.as_ref())? {
+                if is_dynamic_library(&path) {
+                    continue;
+                }
+                match start_script(
+                    &path,
+                    &version,
+                    self.greeting_timeout,
+                    &self.launcher,
+                    StderrSink::new(&path),
+                ) {
+                    Ok(script) => scripts.push(script),
+                    Err(e) => {
+                        // Don't leave the manager half populated
+                        scripts
+                            .iter_mut()
+                            .for_each(|script| script.end(Instant::now()));
+                        return Err(e);
+                    }
+                }
             }
-
-            // Save script depending on its type
-            let script = if matches!(metadata.script_type, ScriptType::Daemon) {
-                ScriptTypeInternal::Daemon(script)
-            } else {
-                ScriptTypeInternal::OneShot(path.to_path_buf())
-            };
-            Ok(Script {
-                script,
-                metadata,
-                state: State::Active,
-            })
-        }
-        let path = path.as_ref();
-        for entry in std::fs::read_dir(path)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_file() {
-                if let Some(ext) = path.extension() {
-                    if ext == env::consts::DLL_EXTENSION {
-                        continue;
+            for script in &mut scripts {
+                script.check_fingerprints(&self.hook_fingerprints);
+            }
+            self.scripts.extend(scripts);
+            self.watch_dir(path.as_ref(), &version, false);
+            Ok(())
+        })
+    }
+    /// Same as [ScriptManager::add_scripts_by_path] but a script that fails to load doesn't prevent the rest from loading\
+    /// Every file found is reported as loaded, skipped or failed (with the corresponding error)\
+    /// An error is only returned if the folder itself can't be read
+    ///
+    /// ```rust, no_run
+    /// # use rscript::*;
+    /// let mut sm = ScriptManager::default();
+    /// let scripts_path: std::path::PathBuf = todo!(); // Defined by the user
+    /// let report = sm.discover_scripts_by_path(scripts_path, Version::new(0, 1, 0)).unwrap();
+    /// for (path, error) in report.failed() {
+    ///     eprintln!("failed to load {}: {}", path.display(), error);
+    /// }
+    /// ```
+    pub fn discover_scripts_by_path<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        version: Version,
+    ) -> Result<DiscoveryReport, Error> {
+        trace::discovery(path.as_ref()).run(|| {
+            let dir = path.as_ref();
+            let mut report = DiscoveryReport::default();
+            for path in script_paths(dir)? {
+                if is_dynamic_library(&path) {
+                    report.push(path, DiscoveryStatus::Skipped);
+                    continue;
+                }
+                match start_script(
+                    &path,
+                    &version,
+                    self.greeting_timeout,
+                    &self.launcher,
+                    StderrSink::new(&path),
+                ) {
+                    Ok(mut script) => {
+                        let mismatches = script.check_fingerprints(&self.hook_fingerprints);
+                        self.scripts.push(script);
+                        report.push(path.clone(), DiscoveryStatus::Loaded);
+                        for mismatch in mismatches {
+                            report.push(path.clone(), DiscoveryStatus::HookMismatch(mismatch));
+                        }
+                    }
+                    Err(e) => {
+                        self.failed_paths.insert(path.clone(), file_stamp(&path));
+                        report.push(path, DiscoveryStatus::Failed(e));
                     }
                 }
-                self.scripts.push(start_script(&path, &version)?);
             }
-        }
-        Ok(())
+            self.watch_dir(dir, &version, false);
+            Ok(report)
+        })
     }
     /// Same as [ScriptManager::add_scripts_by_path] but looks for dynamic libraries instead
     ///
@@ -194,37 +601,145 @@ impl ScriptManager {
         path: P,
         version: Version,
     ) -> Result<(), Error> {
-        fn load_dynamic_library(path: &Path, version: &Version) -> Result<Script, Error> {
-            let lib = unsafe { libloading::Library::new(path)? };
-            let script: libloading::Symbol<&DynamicScript> =
-                unsafe { lib.get(DynamicScript::NAME)? };
-
-            let metadata: ScriptInfo = (script.script_info)().deserialize()?;
-            if !metadata.version_requirement.matches(version) {
-                return Err(Error::ScriptVersionMismatch {
-                    program_actual_version: version.clone(),
-                    program_required_version: metadata.version_requirement,
-                });
+        trace::discovery(path.as_ref()).run(|| {
+            let mut scripts = vec![];
+            for path in script_paths(path.as_ref())? {
+                if is_dynamic_library(&path) {
+                    scripts.push(load_dynamic_library(&path, &version)?);
+                }
             }
-            Ok(Script {
-                script: ScriptTypeInternal::DynamicLib(lib),
-                metadata,
-                state: State::Active,
-            })
-        }
-        let path = path.as_ref();
-        for entry in std::fs::read_dir(path)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_file() {
-                if let Some(ext) = path.extension() {
-                    if ext == env::consts::DLL_EXTENSION {
-                        self.scripts.push(load_dynamic_library(&path, &version)?);
+            for script in &mut scripts {
+                script.check_fingerprints(&self.hook_fingerprints);
+            }
+            self.scripts.extend(scripts);
+            self.watch_dir(path.as_ref(), &version, true);
+            Ok(())
+        })
+    }
+    /// Same as [ScriptManager::discover_scripts_by_path] but looks for dynamic libraries instead
+    ///
+    /// # Safety
+    /// See <https://docs.rs/libloading/0.7.1/libloading/struct.Library.html#safety>
+    pub unsafe fn discover_dynamic_scripts_by_path<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        version: Version,
+    ) -> Result<DiscoveryReport, Error> {
+        trace::discovery(path.as_ref()).run(|| {
+            let dir = path.as_ref();
+            let mut report = DiscoveryReport::default();
+            for path in script_paths(dir)? {
+                if !is_dynamic_library(&path) {
+                    report.push(path, DiscoveryStatus::Skipped);
+                    continue;
+                }
+                match load_dynamic_library(&path, &version) {
+                    Ok(mut script) => {
+                        let mismatches = script.check_fingerprints(&self.hook_fingerprints);
+                        self.scripts.push(script);
+                        report.push(path.clone(), DiscoveryStatus::Loaded);
+                        for mismatch in mismatches {
+                            report.push(path.clone(), DiscoveryStatus::HookMismatch(mismatch));
+                        }
+                    }
+                    Err(e) => {
+                        self.failed_paths.insert(path.clone(), file_stamp(&path));
+                        report.push(path, DiscoveryStatus::Failed(e));
                     }
                 }
             }
-        }
-        Ok(())
+            self.watch_dir(dir, &version, true);
+            Ok(report)
+        })
+    }
+    /// Set how long a script has to read and answer the greeting when it's being added, `None` (the default) waits forever\
+    /// A script that doesn't answer in time is killed and reported with [Error::Timeout]
+    pub fn set_greeting_timeout(&mut self, timeout: Option<Duration>) {
+        self.greeting_timeout = timeout;
+    }
+    /// Set the channel process scripts speak the protocol on, it applies to the scripts added (or restarted, or reloaded) afterwards, see [ProtocolChannel]
+    pub fn set_protocol_channel(&mut self, channel: ProtocolChannel) {
+        self.launcher.channel = channel;
+    }
+    /// Set how process scripts are started: their environment, arguments, working directory and stderr, see [ScriptLaunchConfig]\
+    /// It applies to the scripts added (or restarted, or reloaded) afterwards that don't have their own config ([ScriptManager::set_script_launch_config])
+    pub fn set_launch_config(&mut self, config: ScriptLaunchConfig) {
+        self.launcher.config = config;
+    }
+    /// Set how the script at `path` is started, it replaces [ScriptManager::set_launch_config] for this script\
+    /// `None` removes the config of the script
+    pub fn set_script_launch_config<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        config: Option<ScriptLaunchConfig>,
+    ) {
+        let path = process::canonical(path.as_ref());
+        match config {
+            Some(config) => self.launcher.script_configs.insert(path, config),
+            None => self.launcher.script_configs.remove(&path),
+        };
+    }
+    /// Set the sandbox policy of process scripts, `None` (the default) runs them unrestricted, see [SandboxPolicy]\
+    /// It applies to the scripts added (or restarted, or reloaded) afterwards that don't have their own policy ([ScriptManager::set_script_sandbox_policy])
+    #[cfg(all(feature = "sandbox", target_os = "linux"))]
+    pub fn set_sandbox_policy(&mut self, policy: Option<SandboxPolicy>) {
+        self.launcher.sandbox = policy;
+    }
+    /// Set the sandbox policy of the script at `path`, it takes precedence over [ScriptManager::set_sandbox_policy]\
+    /// `None` removes the policy of the script
+    #[cfg(all(feature = "sandbox", target_os = "linux"))]
+    pub fn set_script_sandbox_policy<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        policy: Option<SandboxPolicy>,
+    ) {
+        let path = process::canonical(path.as_ref());
+        match policy {
+            Some(policy) => self.launcher.script_sandboxes.insert(path, policy),
+            None => self.launcher.script_sandboxes.remove(&path),
+        };
+    }
+    /// Set the resource limits of process scripts, `None` (the default) doesn't limit them, see [ResourceLimits]\
+    /// It applies to the scripts added (or restarted, or reloaded) afterwards that don't have their own limits ([ScriptManager::set_script_resource_limits])
+    #[cfg(unix)]
+    pub fn set_resource_limits(&mut self, limits: Option<ResourceLimits>) {
+        self.launcher.limits = limits;
+    }
+    /// Set the resource limits of the script at `path`, they take precedence over [ScriptManager::set_resource_limits]\
+    /// `None` removes the limits of the script
+    #[cfg(unix)]
+    pub fn set_script_resource_limits<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        limits: Option<ResourceLimits>,
+    ) {
+        let path = process::canonical(path.as_ref());
+        match limits {
+            Some(limits) => self.launcher.script_limits.insert(path, limits),
+            None => self.launcher.script_limits.remove(&path),
+        };
+    }
+    /// Set how long a script has to read a triggered hook and answer it, `None` (the default) waits forever\
+    /// A script that doesn't answer in time is killed, marked as failed (see [Script::has_failed]) and reported with [Error::Timeout]\
+    /// This doesn't apply to [ScriptType::DynamicLib] scripts since they run in the main program
+    pub fn set_execute_timeout(&mut self, timeout: Option<Duration>) {
+        self.execute_timeout = timeout;
+    }
+    /// Same as [ScriptManager::set_execute_timeout] but only for the hook `H`, it takes precedence over the manager execute timeout\
+    /// `None` removes the hook specific timeout
+    pub fn set_hook_timeout<H: Hook>(&mut self, timeout: Option<Duration>) {
+        match timeout {
+            Some(timeout) => self.hook_timeouts.insert(H::NAME, timeout),
+            None => self.hook_timeouts.remove(H::NAME),
+        };
+    }
+    /// Set how long [ScriptType::Daemon] scripts have to exit after being asked to shutdown, when the [ScriptManager] is dropped\
+    /// Scripts still running after that are killed, the default is 1 second
+    pub fn set_shutdown_grace(&mut self, grace: Duration) {
+        self.shutdown_grace = Some(grace);
+    }
+    fn shutdown_grace(&self) -> Duration {
+        self.shutdown_grace.unwrap_or(DEFAULT_SHUTDOWN_GRACE)
     }
     /// Trigger a hook
     /// All scripts that are *active* and that are listening for this particular hook will receive it
@@ -232,14 +747,112 @@ impl ScriptManager {
         &'a mut self,
         hook: H,
     ) -> impl Iterator<Item = Result<<H as Hook>::Output, Error>> + 'a {
+        self.trigger_tagged(hook).map(|output| output.result)
+    }
+    /// Same as [ScriptManager::trigger] but each output is tagged with the script that produced it\
+    /// The [ScriptId] can be used to find the script with [ScriptManager::script_mut], to deactivate it for example
+    ///
+    /// ```rust, no_run
+    /// # use rscript::*;
+    /// # #[derive(serde::Serialize, serde::Deserialize)]
+    /// # struct Eval(String);
+    /// # impl Hook for Eval {
+    /// #   const NAME: &'static str = "Eval";
+    /// #   type Output = String;
+    /// # }
+    /// # let mut sm = ScriptManager::default();
+    /// let failed: Vec<ScriptId> = sm
+    ///     .trigger_tagged(Eval("1 + 1".into()))
+    ///     .filter(|output| output.result.is_err())
+    ///     .map(|output| output.id)
+    ///     .collect();
+    /// for id in failed {
+    ///     sm.script_mut(id).unwrap().deactivate();
+    /// }
+    /// ```
+    pub fn trigger_tagged<'a, H: 'static + Hook>(
+        &'a mut self,
+        hook: H,
+    ) -> impl Iterator<Item = ScriptOutput<<H as Hook>::Output>> + 'a {
+        let timeout = self.hook_timeout::<H>();
+        let host = &self.host_functions;
         self.scripts.iter_mut().filter_map(move |script| {
             if script.is_active() && script.is_listening_for::<H>() {
-                Some(script.trigger_internal(&hook))
+                Some(script.trigger_tagged(&hook, timeout, host))
             } else {
                 None
             }
         })
     }
+    /// Same as [ScriptManager::trigger_tagged] but [ScriptType::Daemon] and [ScriptType::OneShot] scripts receive the hook at the same time, each one on its own thread\
+    /// The outputs are yielded as they arrive, so a slow script doesn't delay the others\
+    /// [ScriptType::DynamicLib] scripts are called on the caller thread while the processes are busy\
+    /// Dropping the iterator waits for the scripts that are still running
+    pub fn trigger_parallel<'a, H>(
+        &'a mut self,
+        hook: H,
+    ) -> impl Iterator<Item = ScriptOutput<<H as Hook>::Output>> + 'a
+    where
+        H: 'static + Hook + Send + Sync,
+        <H as Hook>::Output: Send,
+    {
+        let timeout = self.hook_timeout::<H>();
+        ParallelOutputs::new(
+            &mut self.scripts,
+            hook,
+            timeout,
+            self.host_functions.clone(),
+        )
+    }
+    /// Register the handler of a [HostFunction], scripts can call it while handling hooks with [Scripter::call](scripting::Scripter::call) or [DynamicScript::call]\
+    /// Registering a handler for the same function again replaces the previous one
+    ///
+    /// ```rust
+    /// # use rscript::*;
+    /// #[derive(serde::Serialize, serde::Deserialize)]
+    /// struct ConfigValue(String);
+    /// impl HostFunction for ConfigValue {
+    ///     const NAME: &'static str = "ConfigValue";
+    ///     type Output = Option<String>;
+    /// }
+    ///
+    /// let mut sm = ScriptManager::default();
+    /// sm.register_host_function(|ConfigValue(key)| std::env::var(key).ok());
+    /// ```
+    pub fn register_host_function<F: HostFunction>(
+        &mut self,
+        handler: impl Fn(F) -> <F as HostFunction>::Output + Send + Sync + 'static,
+    ) {
+        Arc::get_mut(&mut self.host_functions)
+            .expect("the threads of trigger_parallel are done when it's dropped")
+            .register(handler);
+    }
+    /// Check the [Hook::FINGERPRINT] of `H` when scripts are loaded, including the already loaded ones\
+    /// A script built with a different definition of the hook keeps running but the hook is disabled for it, triggering it returns [Error::HookMismatch] (see [Script::mismatched_hooks])\
+    /// The scripts loaded afterwards report it in [DiscoveryReport] and [ReloadEvent::HookMismatch], so register the hooks before adding the scripts\
+    /// Hooks that are not registered are checked the first time they are triggered
+    pub fn register_hook<H: Hook>(&mut self) {
+        if let Some(fingerprint) = H::FINGERPRINT {
+            self.hook_fingerprints.insert(H::NAME, fingerprint);
+            for script in &mut self.scripts {
+                script.check_fingerprints(&self.hook_fingerprints);
+            }
+        }
+    }
+    fn hook_timeout<H: Hook>(&self) -> Option<Duration> {
+        self.hook_timeouts
+            .get(H::NAME)
+            .copied()
+            .or(self.execute_timeout)
+    }
+    /// Find a script by its id
+    pub fn script(&self, id: ScriptId) -> Option<&Script> {
+        self.scripts.iter().find(|script| script.id == id)
+    }
+    /// Find a script by its id, mutably
+    pub fn script_mut(&mut self, id: ScriptId) -> Option<&mut Script> {
+        self.scripts.iter_mut().find(|script| script.id == id)
+    }
     /// List of current scripts
     pub fn scripts(&self) -> &[Script] {
         &self.scripts
@@ -250,9 +863,276 @@ impl ScriptManager {
     }
 }
 
+/// Files found in the scripts folder
+fn script_paths(path: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut paths = vec![];
+    for entry in std::fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.is_file() {
+            paths.push(path);
+        }
+    }
+    Ok(paths)
+}
+
+fn is_dynamic_library(path: &Path) -> bool {
+    path.extension() == Some(OsStr::new(env::consts::DLL_EXTENSION))
+}
+
+/// Scripts can declare that they speak the JSON-lines protocol with this marker in one of their first two lines
+const JSON_LINES_MARKER: &[u8] = b"rscript: json-lines";
+
+fn start_script(
+    path: &Path,
+    version: &Version,
+    timeout: Option<Duration>,
+    launcher: &Launcher,
+    stderr: StderrSink,
+) -> Result<Script, Error> {
+    let span = trace::greeting(path);
+    let (script, metadata, wire) = span.run(|| {
+        let mut script = Process::spawn(path, launcher, &stderr)?;
+        // A restricted script could be killed while greeted
+        let (metadata, wire) =
+            greet(&mut script, path, timeout).map_err(|e| script.violation(path).unwrap_or(e))?;
+
+        // Check if the provided version matches the script version
+        check_version(&metadata, version)?;
+        Ok((script, metadata, wire))
+    })?;
+    span.record_script(&metadata);
+    stderr.set_name(&metadata.name);
+
+    // Save script depending on its type
+    let script = if matches!(metadata.script_type, ScriptType::Daemon) {
+        ScriptTypeInternal::Daemon(Box::new(script))
+    } else {
+        ScriptTypeInternal::OneShot(path.to_path_buf())
+    };
+    Ok(Script {
+        script,
+        metadata,
+        wire,
+        state: State::Active,
+        path: path.to_path_buf(),
+        version: version.clone(),
+        greeting_timeout: timeout,
+        launcher: launcher.clone(),
+        stderr,
+        restart_policy: None,
+        restarts: 0,
+        id: ScriptId::next(),
+        stamp: file_stamp(path),
+        events: VecDeque::new(),
+        mismatched_hooks: vec![],
+        hook_fingerprints: HashMap::new(),
+    })
+}
+
+/// Send the greeting to a script and read its answer
+fn greet(
+    script: &mut Process,
+    path: &Path,
+    timeout: Option<Duration>,
+) -> Result<(ScriptInfo, Wire), Error> {
+    script.set_timeout(timeout);
+    // Send Greeting Message
+    let greeting = Greeting::of(path);
+    greeting_step(script, timeout, |script| {
+        // A script that exits without reading it is recognized from its answer, like one that answers something else
+        match script
+            .input()?
+            .write_all(&greeting.message())
+            .map_err(Error::from)
+        {
+            Err(e) if !is_disconnected(&e) => Err(e),
+            _ => Ok(()),
+        }
+    })?;
+    let json_lines = match greeting {
+        Greeting::JsonLines => true,
+        Greeting::Line => {
+            greeting_step(script, timeout, |script| Ok(script.output().peek()?))? == Some(b'{')
+        }
+        Greeting::Native => false,
+    };
+    if json_lines {
+        greet_json_lines(script, path, timeout).inspect_err(|_| script.kill())
+    } else {
+        // Receive the handshake, an incompatible script could send anything after it
+        let handshake = greeting_step(script, timeout, |script| {
+            script
+                .read_with(|bytes| Handshake::decode_prefix(bytes, path))
+                .map_err(|e| Handshake::missing(e, path))
+        })?;
+        let codec = handshake.check(path).inspect_err(|_| script.kill())?;
+        // Receive ScriptInfo
+        let metadata: ScriptInfo = greeting_step(script, timeout, |script| {
+            script.read_frame(codec, Kind::ScriptInfo)
+        })?;
+        Ok((metadata, Wire::Native(codec)))
+    }
+}
+
+/// Read the `info` line of a JSON-lines script
+#[cfg(feature = "json")]
+fn greet_json_lines(
+    script: &mut Process,
+    path: &Path,
+    timeout: Option<Duration>,
+) -> Result<(ScriptInfo, Wire), Error> {
+    let line = greeting_step(script, timeout, |script| {
+        script.read_with(json_lines::decode_line)
+    })?;
+    Ok((json_lines::script_info(line, path)?, Wire::JsonLines))
+}
+
+#[cfg(not(feature = "json"))]
+fn greet_json_lines(
+    _script: &mut Process,
+    path: &Path,
+    _timeout: Option<Duration>,
+) -> Result<(ScriptInfo, Wire), Error> {
+    Err(Error::UnsupportedCodec {
+        path: path.to_path_buf(),
+        codec: "json-lines".into(),
+    })
+}
+
+/// Run a part of the greeting with `step`, the script is killed if it fails, for example because it didn't read or answer within `timeout`
+fn greeting_step<T>(
+    script: &mut Process,
+    timeout: Option<Duration>,
+    step: impl FnOnce(&mut Process) -> Result<T, Error>,
+) -> Result<T, Error> {
+    match step(script) {
+        Ok(value) => Ok(value),
+        Err(_) if script.timed_out() => {
+            script.kill();
+            Err(Error::Timeout(
+                timeout.expect("only reads and writes with a deadline time out"),
+            ))
+        }
+        Err(e) => {
+            script.kill();
+            Err(e)
+        }
+    }
+}
+
+unsafe fn load_dynamic_library(path: &Path, version: &Version) -> Result<Script, Error> {
+    let (lib, metadata, codec) = open_dynamic_library(path, version)?;
+    Ok(Script {
+        script: ScriptTypeInternal::DynamicLib(lib),
+        metadata,
+        wire: Wire::Native(codec),
+        state: State::Active,
+        path: path.to_path_buf(),
+        version: version.clone(),
+        greeting_timeout: None,
+        launcher: Launcher::default(),
+        stderr: StderrSink::new(path),
+        restart_policy: None,
+        restarts: 0,
+        id: ScriptId::next(),
+        stamp: file_stamp(path),
+        events: VecDeque::new(),
+        mismatched_hooks: vec![],
+        hook_fingerprints: HashMap::new(),
+    })
+}
+
+/// Load a dynamic library and check its [ScriptInfo], returns the [Codec] it uses too
+unsafe fn open_dynamic_library(
+    path: &Path,
+    version: &Version,
+) -> Result<(libloading::Library, ScriptInfo, Codec), Error> {
+    let span = trace::dylib_load(path);
+    let opened = span.run(|| open_library(path, version))?;
+    span.record_script(&opened.1);
+    Ok(opened)
+}
+
+unsafe fn open_library(
+    path: &Path,
+    version: &Version,
+) -> Result<(libloading::Library, ScriptInfo, Codec), Error> {
+    let lib = libloading::Library::new(path)?;
+    let script: libloading::Symbol<&DynamicScript> = lib.get(DynamicScript::NAME)?;
+    // Check the handshake before calling anything, the rest of the layout might be different
+    let codec = script.handshake.check(path)?;
+    script.handshake.check_build(path)?;
+
+    let metadata: ScriptInfo = (script.script_info)()
+        .into_result()
+        .map_err(|message| Error::ScriptPanicked {
+            name: path.display().to_string(),
+            message,
+        })?
+        .deserialize()?;
+    check_version(&metadata, version)?;
+    Ok((lib, metadata, codec))
+}
+
+fn check_version(metadata: &ScriptInfo, version: &Version) -> Result<(), Error> {
+    if metadata.version_requirement.matches(version) {
+        Ok(())
+    } else {
+        Err(Error::ScriptVersionMismatch {
+            program_actual_version: version.clone(),
+            program_required_version: metadata.version_requirement.clone(),
+        })
+    }
+}
+
+/// Whether `error` means that the script closed its end of the channel, which is what a crashed script does\
+/// Other errors come from scripts that are still running, so they don't need to be checked for a crash
+fn is_disconnected(error: &Error) -> bool {
+    let error = match error {
+        Error::Io(error) => error,
+        Error::Bincode(error) => match &**error {
+            bincode::ErrorKind::Io(error) => error,
+            _ => return false,
+        },
+        _ => return false,
+    };
+    matches!(
+        error.kind(),
+        std::io::ErrorKind::UnexpectedEof | std::io::ErrorKind::BrokenPipe
+    )
+}
+
+/// Call the [DynamicScript] exported by `lib` with the hook `H` encoded with `codec`\
+/// `name` is the script name, used to report a panic
+unsafe fn trigger_dynamic_library<H: Hook>(
+    lib: &libloading::Library,
+    name: &str,
+    codec: Codec,
+    hook: &H,
+    host: &HostFunctions,
+) -> Result<<H as Hook>::Output, Error> {
+    let script: libloading::Symbol<&DynamicScript> = lib.get(DynamicScript::NAME)?;
+
+    let data = FFiData::serialize_from(codec, hook)?;
+    trace::hook_bytes(data.as_bytes().len());
+    let output = (script.script)(FFiStr::new(H::NAME), data, &host.as_ffi(codec))
+        .into_result()
+        .map_err(|message| Error::ScriptPanicked {
+            name: name.to_string(),
+            message,
+        })?;
+    trace::output_bytes(output.as_bytes().len());
+    output.deserialize()
+}
+
 impl Drop for ScriptManager {
     fn drop(&mut self) {
-        self.scripts.iter_mut().for_each(|script| script.end());
+        // Ask all the daemons first, so they can shutdown concurrently
+        self.scripts.iter_mut().for_each(Script::shutdown);
+        let deadline = Instant::now() + self.shutdown_grace();
+        self.scripts
+            .iter_mut()
+            .for_each(|script| script.end(deadline));
     }
 }
 
@@ -260,30 +1140,130 @@ impl Drop for ScriptManager {
 // The user should not be able to construct a Script manually
 #[derive(Debug)]
 pub struct Script {
+    id: ScriptId,
     metadata: ScriptInfo,
+    wire: Wire,
     script: ScriptTypeInternal,
     state: State,
+    path: PathBuf,
+    // Needed to greet the script again when restarting it
+    version: Version,
+    greeting_timeout: Option<Duration>,
+    launcher: Launcher,
+    // Kept across OneShot triggers and restarts
+    stderr: StderrSink,
+    restart_policy: Option<RestartPolicy>,
+    restarts: usize,
+    // Used to detect modified scripts when reloading
+    stamp: Option<FileStamp>,
+    // Events (name and serialized data) received but not yet returned by [ScriptManager::poll_events]
+    events: VecDeque<(String, Vec<u8>)>,
+    // Hooks disabled because the script was built with a different definition of them
+    mismatched_hooks: Vec<MismatchedHook>,
+    // Fingerprints of the hooks registered in the manager, checked again when the script is restarted
+    hook_fingerprints: HashMap<&'static str, u64>,
+}
+
+/// Identifies a [Script], it stays the same when the script is restarted or reloaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ScriptId(u64);
+
+impl ScriptId {
+    fn next() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// A hook output tagged with the script that produced it, see [ScriptManager::trigger_tagged]
+#[derive(Debug)]
+pub struct ScriptOutput<O> {
+    /// The script id
+    pub id: ScriptId,
+    /// The script name
+    pub name: String,
+    /// The output of the script or the error that happened while triggering it
+    pub result: Result<O, Error>,
+}
+
+/// Controls if and how a crashed [ScriptType::Daemon] script is restarted, see [Script::set_restart_policy]
+#[derive(Debug, Clone, Copy)]
+pub struct RestartPolicy {
+    /// How many times the script can be restarted, after that it's marked as failed
+    pub max_restarts: usize,
+    /// How long to wait before restarting the script
+    pub backoff: Duration,
+}
+
+/// The channel process scripts ([ScriptType::OneShot] and [ScriptType::Daemon]) speak the protocol on, see [ScriptManager::set_protocol_channel]\
+/// [Scripter](scripting::Scripter) scripts detect it on their own, so the same script works with both
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProtocolChannel {
+    /// The script stdin and stdout, the default\
+    /// The script can't use them for anything else, printing to stdout breaks the protocol
+    #[default]
+    Stdio,
+    /// A dedicated pair of pipes, their file descriptors are passed to the script in the `RSCRIPT_PROTOCOL_FDS` environment variable (Unix only)\
+    /// The script inherits the main program stdin and stdout, so it can print to the terminal and read user input
+    #[cfg(unix)]
+    Pipes,
 }
 
+/// Holds the file descriptors of [ProtocolChannel::Pipes] as seen by the script: `<read fd>,<write fd>`
+pub(crate) const PROTOCOL_FDS_VAR: &str = "RSCRIPT_PROTOCOL_FDS";
+
+/// How long to wait for a daemon that closed its end of the channel to exit, before deciding it didn't crash
+const CRASH_GRACE: Duration = Duration::from_millis(100);
+
 #[derive(Debug)]
 enum State {
     Active,
     Inactive,
+    Failed,
 }
 
 #[derive(Debug)]
 enum ScriptTypeInternal {
-    Daemon(Child),
+    Daemon(Box<Process>),
     OneShot(std::path::PathBuf),
     DynamicLib(libloading::Library),
 }
 
 impl Script {
     //public
+    /// Returns the script id
+    pub fn id(&self) -> ScriptId {
+        self.id
+    }
     /// Returns the script metadata
     pub fn metadata(&self) -> &ScriptInfo {
         &self.metadata
     }
+    /// Path of the script executable or dynamic library
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    /// The last lines the script wrote to its stderr, oldest first\
+    /// They are only kept if the script was started with [ScriptStderr::Capture], see [ScriptManager::set_launch_config]
+    pub fn stderr(&self) -> Vec<String> {
+        self.stderr.lines()
+    }
+    /// The [Codec] the script communicates with, it's chosen by the script\
+    /// Scripts that speak the JSON-lines protocol use JSON
+    pub fn codec(&self) -> Codec {
+        self.wire.codec()
+    }
+    /// Set the restart policy of a [ScriptType::Daemon] script, by default crashed scripts are not restarted\
+    /// When a daemon crashes, the trigger that noticed it returns [Error::ScriptCrashed], then the script is restarted and greeted again according to the policy\
+    /// A daemon that breaks the protocol ([Error::ProtocolViolation]) is killed and restarted the same way\
+    /// If it can't be restarted, it's marked as failed
+    pub fn set_restart_policy(&mut self, policy: Option<RestartPolicy>) {
+        self.restart_policy = policy;
+    }
+    /// How many times the script was restarted after crashing
+    pub fn restarts(&self) -> usize {
+        self.restarts
+    }
     /// Activate a script, inactive scripts will not react to hooks
     pub fn activate(&mut self) {
         self.state = State::Active;
@@ -296,6 +1276,11 @@ impl Script {
     pub fn is_active(&self) -> bool {
         matches!(self.state, State::Active)
     }
+    /// Check if the script was disabled because it failed, for example because it timed out\
+    /// Failed scripts will not react to hooks, unless they are activated again with [Script::activate]
+    pub fn has_failed(&self) -> bool {
+        matches!(self.state, State::Failed)
+    }
     /// Check if a script is listening for a hook
     pub fn is_listening_for<H: Hook>(&self) -> bool {
         self.metadata
@@ -303,11 +1288,28 @@ impl Script {
             .iter()
             .any(|hook| hook.as_str() == H::NAME)
     }
+    /// The hooks disabled because the script was built with a different definition of them, see [ScriptManager::register_hook]
+    pub fn mismatched_hooks(&self) -> &[MismatchedHook] {
+        &self.mismatched_hooks
+    }
     /// Trigger a hook on the script, this disregards the script state as in the hook will be triggered even if the script is inactive\
-    /// If the script is not listening for the specified hook, an error will be returned
+    /// If the script is not listening for the specified hook, an error will be returned\
+    /// The host functions registered on the [ScriptManager] are not available to the script when triggered this way
     pub fn trigger<H: Hook>(&mut self, hook: &H) -> Result<<H as Hook>::Output, Error> {
         if self.is_listening_for::<H>() {
-            self.trigger_internal(hook)
+            self.trigger_internal(hook, None, &HostFunctions::default())
+        } else {
+            Err(Error::ScriptIsNotListeningForHook)
+        }
+    }
+    /// Same as [Script::trigger] but gives up after `timeout`, see [ScriptManager::set_execute_timeout]
+    pub fn trigger_with_timeout<H: Hook>(
+        &mut self,
+        hook: &H,
+        timeout: Duration,
+    ) -> Result<<H as Hook>::Output, Error> {
+        if self.is_listening_for::<H>() {
+            self.trigger_internal(hook, Some(timeout), &HostFunctions::default())
         } else {
             Err(Error::ScriptIsNotListeningForHook)
         }
@@ -316,43 +1318,254 @@ impl Script {
 
 impl Script {
     // private
-    fn trigger_internal<H: Hook>(&mut self, hook: &H) -> Result<<H as Hook>::Output, Error> {
-        let trigger_hook_common =
-            |script: &mut Child| -> Result<<H as Hook>::Output, bincode::Error> {
-                let mut stdin = script.stdin.as_mut().expect("stdin is piped");
-                let stdout = script.stdout.as_mut().expect("stdout is piped");
+    fn trigger_tagged<H: Hook>(
+        &mut self,
+        hook: &H,
+        timeout: Option<Duration>,
+        host: &HostFunctions,
+    ) -> ScriptOutput<<H as Hook>::Output> {
+        // Take the name before triggering, the script might be restarted with a different one
+        let name = self.metadata.name.clone();
+        ScriptOutput {
+            id: self.id,
+            name,
+            result: self.trigger_internal(hook, timeout, host),
+        }
+    }
+    fn trigger_internal<H: Hook>(
+        &mut self,
+        hook: &H,
+        timeout: Option<Duration>,
+        host: &HostFunctions,
+    ) -> Result<<H as Hook>::Output, Error> {
+        let span = trace::trigger(&self.metadata, H::NAME);
+        span.run(|| self.trigger_inner(hook, timeout, host))
+    }
+    fn trigger_inner<H: Hook>(
+        &mut self,
+        hook: &H,
+        timeout: Option<Duration>,
+        host: &HostFunctions,
+    ) -> Result<<H as Hook>::Output, Error> {
+        if let (Some(&script_fingerprint), Some(program_fingerprint)) =
+            (self.metadata.fingerprints.get(H::NAME), H::FINGERPRINT)
+        {
+            self.check_fingerprint(H::NAME, script_fingerprint, program_fingerprint);
+        }
+        if self
+            .mismatched_hooks
+            .iter()
+            .any(|hook| hook.hook == H::NAME)
+        {
+            return Err(Error::HookMismatch {
+                script: self.metadata.name.clone(),
+                hook: H::NAME.into(),
+            });
+        }
+        let result = self.execute(hook, timeout, host);
+        if let (Err(Error::ProtocolViolation { .. }), ScriptTypeInternal::Daemon(_)) =
+            (&result, &self.script)
+        {
+            // It was killed, there is no way to find where its next message starts
+            self.restart();
+        } else if result.as_ref().is_err_and(is_disconnected) {
+            // A daemon that closed its end of the channel might have exited
+            if let ScriptTypeInternal::Daemon(ref mut script) = self.script {
+                if let Some(status) = script.exit_status(CRASH_GRACE) {
+                    return Err(self.crashed(status));
+                }
+            }
+        }
+        result
+    }
+    fn execute<H: Hook>(
+        &mut self,
+        hook: &H,
+        timeout: Option<Duration>,
+        host: &HostFunctions,
+    ) -> Result<<H as Hook>::Output, Error> {
+        let (events, wire) = (&mut self.events, self.wire);
+        // `first` is true if it's the first message the process receives
+        let mut trigger_hook_common =
+            |script: &mut Process, first: bool| -> Result<<H as Hook>::Output, Error> {
+                script.set_timeout(timeout);
+                #[cfg(feature = "json")]
+                if let Wire::JsonLines = wire {
+                    return json_lines::execute(script, hook, host, events);
+                }
+                let codec = wire.codec();
+                let mut input = script.input()?;
 
                 // Send Execute message
-                bincode::serialize_into(&mut stdin, &Message::Execute)?;
-                // bincode write hook type
-                bincode::serialize_into(&mut stdin, H::NAME)?;
-                // bincode write hook
-                bincode::serialize_into(stdin, hook)?;
-                // bincode read result -> O
-                bincode::deserialize_from(stdout)
+                if first {
+                    input.write_all(EXECUTE_LINE.as_bytes())?;
+                } else {
+                    frame::write(&mut input, codec, Kind::Message, &Message::Execute)?;
+                }
+                // write hook type
+                frame::write(&mut input, codec, Kind::HookName, H::NAME)?;
+                // write hook
+                frame::write(input, codec, Kind::Hook, hook)?;
+                // Answer the host functions calls until the script sends the output
+                loop {
+                    match script.read_frame(codec, Kind::ScriptMessage)? {
+                        ScriptMessage::Output => break,
+                        ScriptMessage::HostCall { name, data } => frame::write(
+                            script.input()?,
+                            codec,
+                            Kind::HostReply,
+                            &host.call(&name, codec, &data),
+                        )?,
+                        ScriptMessage::Event { name, data } => events.push_back((name, data)),
+                    }
+                }
+                // read result -> O
+                script.read_frame(codec, Kind::Output)
             };
+        // A script that timed out or broke the protocol is in an unknown state, so kill it
+        // It's marked as failed if it timed out, daemons that broke the protocol are restarted by [Script::trigger_internal]
+        let state = &mut self.state;
+        let mut handle_error = |script: &mut Process, error: Error| -> Error {
+            if script.timed_out() {
+                script.kill();
+                *state = State::Failed;
+                Error::Timeout(timeout.expect("only reads and writes with a deadline time out"))
+            } else {
+                if let Error::ProtocolViolation { .. } = error {
+                    script.kill();
+                }
+                error
+            }
+        };
 
         Ok(match &mut self.script {
-            ScriptTypeInternal::Daemon(ref mut script) => trigger_hook_common(script)?,
-            ScriptTypeInternal::OneShot(script_path) => trigger_hook_common(
-                &mut std::process::Command::new(script_path)
-                    .stdin(Stdio::piped())
-                    .stdout(Stdio::piped())
-                    .spawn()?,
-            )?,
+            ScriptTypeInternal::Daemon(ref mut script) => {
+                trigger_hook_common(script, false).map_err(|e| handle_error(script, e))?
+            }
+            ScriptTypeInternal::OneShot(script_path) => {
+                let mut script = Process::spawn(script_path, &self.launcher, &self.stderr)?;
+                trigger_hook_common(&mut script, true).map_err(|e| {
+                    let error = handle_error(&mut script, e);
+                    script.violation(script_path).unwrap_or(error)
+                })?
+            }
             ScriptTypeInternal::DynamicLib(lib) => unsafe {
-                let script: libloading::Symbol<&DynamicScript> = lib.get(DynamicScript::NAME)?;
-
-                let output = (script.script)(FFiStr::new(H::NAME), FFiData::serialize_from(hook)?);
-                output.deserialize()?
+                trigger_dynamic_library(lib, &self.metadata.name, wire.codec(), hook, host)?
             },
         })
     }
-    fn end(&mut self) {
-        // This errors if the script has already exited
-        // We don't care about this error
+    /// Report the crash and restart the script if its restart policy allows it
+    fn crashed(&mut self, status: ExitStatus) -> Error {
+        let violation = match &mut self.script {
+            ScriptTypeInternal::Daemon(script) => script.violation(&self.path),
+            _ => None,
+        };
+        self.restart();
+        violation.unwrap_or_else(|| Error::ScriptCrashed {
+            name: self.metadata.name.clone(),
+            status,
+        })
+    }
+    /// Restart a daemon that crashed or broke the protocol if its restart policy allows it, it's marked as failed otherwise
+    fn restart(&mut self) {
+        match self.restart_policy {
+            Some(policy) if self.restarts < policy.max_restarts => {
+                std::thread::sleep(policy.backoff);
+                self.restarts += 1;
+                match start_script(
+                    &self.path,
+                    &self.version,
+                    self.greeting_timeout,
+                    &self.launcher,
+                    self.stderr.clone(),
+                ) {
+                    Ok(script) => {
+                        self.script = script.script;
+                        self.metadata = script.metadata;
+                        self.wire = script.wire;
+                        // The script might have been rebuilt with other definitions of the hooks
+                        self.mismatched_hooks.clear();
+                        self.disable_mismatched_hooks();
+                    }
+                    Err(_) => self.state = State::Failed,
+                }
+            }
+            _ => self.state = State::Failed,
+        }
+    }
+    /// Disable the hooks whose advertised fingerprint is different from the `expected` one, returns the newly disabled ones
+    fn check_fingerprints(&mut self, expected: &HashMap<&'static str, u64>) -> Vec<MismatchedHook> {
+        self.hook_fingerprints = expected.clone();
+        self.disable_mismatched_hooks()
+    }
+    /// Same as [Script::check_fingerprints] with the fingerprints it was last checked against
+    fn disable_mismatched_hooks(&mut self) -> Vec<MismatchedHook> {
+        let advertised: Vec<(String, u64)> = self
+            .metadata
+            .fingerprints
+            .iter()
+            .map(|(hook, fingerprint)| (hook.clone(), *fingerprint))
+            .collect();
+        advertised
+            .into_iter()
+            .filter_map(|(hook, script_fingerprint)| {
+                let program_fingerprint = *self.hook_fingerprints.get(hook.as_str())?;
+                self.check_fingerprint(&hook, script_fingerprint, program_fingerprint)
+            })
+            .collect()
+    }
+    /// Disable `hook` if the script fingerprint is different from the main program one, returns the mismatch if it's new
+    fn check_fingerprint(
+        &mut self,
+        hook: &str,
+        script_fingerprint: u64,
+        program_fingerprint: u64,
+    ) -> Option<MismatchedHook> {
+        if script_fingerprint == program_fingerprint
+            || self
+                .mismatched_hooks
+                .iter()
+                .any(|mismatch| mismatch.hook == hook)
+        {
+            return None;
+        }
+        let mismatch = MismatchedHook {
+            hook: hook.into(),
+            program_fingerprint,
+            script_fingerprint,
+        };
+        self.mismatched_hooks.push(mismatch.clone());
+        Some(mismatch)
+    }
+    /// Ask a daemon to exit, see [Scripter::execute_with_shutdown](scripting::Scripter::execute_with_shutdown)
+    fn shutdown(&mut self) {
         if let ScriptTypeInternal::Daemon(ref mut script) = self.script {
-            let _ = script.kill();
+            // This errors if the script has already exited
+            // We don't care about this error
+            // It's not waited for, a hung daemon is killed once the shutdown grace elapses
+            let _ = self
+                .wire
+                .shutdown()
+                .and_then(|message| Ok(script.input()?.send(message)?));
+        }
+    }
+    /// Ask a daemon to exit and wait for it to do so for `grace` at most
+    fn stop(&mut self, grace: Duration) {
+        self.shutdown();
+        self.end(Instant::now() + grace);
+    }
+    fn is_dynamic(&self) -> bool {
+        matches!(self.script, ScriptTypeInternal::DynamicLib(_))
+    }
+    /// Wait for a daemon to exit until `deadline`, then kill it
+    fn end(&mut self, deadline: Instant) {
+        if let ScriptTypeInternal::Daemon(ref mut script) = self.script {
+            if script
+                .exit_status(deadline.saturating_duration_since(Instant::now()))
+                .is_none()
+            {
+                script.kill();
+            }
         }
     }
 }
@@ -361,8 +1574,9 @@ impl Script {
 /// Triggering the hook sends input to the script, and receive the output from it\
 /// The output type is declared on the hook associated type\
 /// The associated NAME is needed in order to differentiate the hooks received in the script\
-/// The hook struct is required to implement serde::Serialize+Deserialize, so it can be used by bincode\
+/// The hook struct is required to implement serde::Serialize+Deserialize, so it can be encoded with the script [Codec]\
 /// The hooks should be declared on an external crate (my-project-api for example) so they can be used both by the main crate and the script\
+/// With the `macros` feature, the trait can be implemented with `#[derive(Hook)]`, the derive documentation lists its options\
 /// ```rust
 /// #[derive(serde::Serialize, serde::Deserialize)]
 /// struct Eval(String);
@@ -375,4 +1589,42 @@ pub trait Hook: Serialize + DeserializeOwned {
     const NAME: &'static str;
     /// The output type of the script
     type Output: Serialize + DeserializeOwned;
+    /// Structural fingerprint of the hook and its output, scripts advertise it so the main program can detect that they were built with a different definition of the hook\
+    /// `#[derive(Hook)]` computes it from the hook name and the [Schema] of the hook and output types, `None` (the default) disables the check
+    const FINGERPRINT: Option<u64> = None;
+}
+
+/// Trait to mark the events that [ScriptType::Daemon] scripts can send to the main crate on their own, without being triggered\
+/// Scripts send them with [Scripter::send_event](scripting::Scripter::send_event), the main crate receives them with [ScriptManager::poll_events]\
+/// Like hooks, events should be declared on the api crate
+/// ```rust
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct FileChanged(std::path::PathBuf);
+/// impl rscript::Event for FileChanged {
+///     const NAME: &'static str = "FileChanged";
+/// }
+/// ```
+pub trait Event: Serialize + DeserializeOwned {
+    /// The name of the event, required to distinguish the received events on the main crate side
+    const NAME: &'static str;
+}
+
+/// Trait to mark the functions that the main crate provides to the scripts\
+/// It's the other direction of [Hook]: while handling a hook, the script can call a host function and receive its output\
+/// The main crate registers the function handlers with [ScriptManager::register_host_function]\
+/// Scripts call them with [Scripter::call](scripting::Scripter::call) or [DynamicScript::call]\
+/// Like hooks, host functions should be declared on the api crate
+/// ```rust
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct CurrentBuffer;
+/// impl rscript::HostFunction for CurrentBuffer {
+///     const NAME: &'static str = "CurrentBuffer";
+///     type Output = String;
+/// }
+/// ```
+pub trait HostFunction: Serialize + DeserializeOwned {
+    /// The name of the function, required to find its handler on the main crate side
+    const NAME: &'static str;
+    /// The output type of the function
+    type Output: Serialize + DeserializeOwned;
 }
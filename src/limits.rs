@@ -0,0 +1,114 @@
+//! Resource limits of process scripts, applied with `setrlimit` (Unix only)
+
+use crate::ResourceLimit;
+use std::{
+    io,
+    os::unix::process::{CommandExt, ExitStatusExt},
+    process::{Command, ExitStatus},
+    time::Duration,
+};
+
+/// Resource limits of a process script ([ScriptType::OneShot](crate::ScriptType::OneShot) or [ScriptType::Daemon](crate::ScriptType::Daemon)), see [ScriptManager::set_resource_limits](crate::ScriptManager::set_resource_limits)\
+/// Nothing is limited by default
+///
+/// A script that exceeds its CPU time is killed and reported with [Error::ResourceLimitExceeded](crate::Error::ResourceLimitExceeded)\
+/// A Rust script that fails to allocate memory prints `memory allocation of N bytes failed` and aborts, the stderr of a script with a memory limit is watched for that message (it's still routed according to its [ScriptStderr](crate::ScriptStderr))\
+/// An abort without it is reported as a crash, since the signal alone doesn't tell the memory limit apart from a panic or a failed assertion\
+/// Exceeding the other limits makes the corresponding calls fail inside the script
+///
+/// ```rust, no_run
+/// # use rscript::*;
+/// let mut limits = ResourceLimits::default();
+/// limits
+///     .memory(512 * 1024 * 1024)
+///     .cpu_time(std::time::Duration::from_secs(60))
+///     .open_files(64);
+/// let mut sm = ScriptManager::default();
+/// sm.set_resource_limits(Some(limits));
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResourceLimits {
+    memory: Option<u64>,
+    cpu_time: Option<Duration>,
+    open_files: Option<u64>,
+    processes: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// Limit the address space of the script to `bytes` (`RLIMIT_AS`)
+    pub fn memory(&mut self, bytes: u64) -> &mut Self {
+        self.memory = Some(bytes);
+        self
+    }
+    /// Limit the CPU time of the script, rounded up to the second (`RLIMIT_CPU`)
+    pub fn cpu_time(&mut self, time: Duration) -> &mut Self {
+        self.cpu_time = Some(time);
+        self
+    }
+    /// Limit how many files the script can have open at once (`RLIMIT_NOFILE`)
+    pub fn open_files(&mut self, count: u64) -> &mut Self {
+        self.open_files = Some(count);
+        self
+    }
+    /// Limit how many processes the script can create (`RLIMIT_NPROC`)\
+    /// The kernel counts all the processes of the user running the script, and doesn't apply it to root
+    pub fn processes(&mut self, count: u64) -> &mut Self {
+        self.processes = Some(count);
+        self
+    }
+
+    /// Limit the script spawned by `command`
+    pub(crate) fn apply(&self, command: &mut Command) {
+        let cpu_time = self
+            .cpu_time
+            .map(|time| time.as_secs() + u64::from(time.subsec_nanos() > 0));
+        let limits = [
+            // The script gets SIGXCPU when it reaches the soft limit, and SIGKILL one second later if it handles it
+            (libc::RLIMIT_CPU, cpu_time.map(|secs| (secs, secs + 1))),
+            (libc::RLIMIT_AS, self.memory.map(|bytes| (bytes, bytes))),
+            (
+                libc::RLIMIT_NOFILE,
+                self.open_files.map(|count| (count, count)),
+            ),
+            (
+                libc::RLIMIT_NPROC,
+                self.processes.map(|count| (count, count)),
+            ),
+        ];
+        unsafe {
+            command.pre_exec(move || {
+                for (resource, limit) in limits {
+                    if let Some((soft, hard)) = limit {
+                        let limit = libc::rlimit {
+                            rlim_cur: soft as libc::rlim_t,
+                            rlim_max: hard as libc::rlim_t,
+                        };
+                        if libc::setrlimit(resource, &limit) == -1 {
+                            return Err(io::Error::last_os_error());
+                        }
+                    }
+                }
+                Ok(())
+            });
+        }
+    }
+    /// Whether the address space of the script is limited
+    pub(crate) fn limits_memory(&self) -> bool {
+        self.memory.is_some()
+    }
+    /// The limit that a script which exited with `status` was killed for exceeding\
+    /// An abort only counts as the memory limit if the script reported an allocation failure on its stderr (`allocation_failed`)
+    pub(crate) fn exceeded(
+        &self,
+        status: ExitStatus,
+        allocation_failed: bool,
+    ) -> Option<ResourceLimit> {
+        match status.signal()? {
+            libc::SIGXCPU if self.cpu_time.is_some() => Some(ResourceLimit::CpuTime),
+            libc::SIGABRT if self.memory.is_some() && allocation_failed => {
+                Some(ResourceLimit::Memory)
+            }
+            _ => None,
+        }
+    }
+}
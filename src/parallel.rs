@@ -0,0 +1,123 @@
+use crate::{Hook, HostFunctions, Script, ScriptOutput};
+use std::{
+    collections::VecDeque,
+    sync::{mpsc, Arc},
+    thread,
+    time::Duration,
+};
+
+type Finished<O> = (usize, Script, ScriptOutput<O>);
+
+/// The outputs of [ScriptManager::trigger_parallel](crate::ScriptManager::trigger_parallel) in the order they are produced\
+/// The process scripts are moved to their own thread while they handle the hook, they are put back in `scripts` when the iterator is dropped
+pub(crate) struct ParallelOutputs<'a, H: Hook> {
+    scripts: &'a mut Vec<Script>,
+    // The scripts at their original position, `None` while a thread owns them
+    slots: Vec<Option<Script>>,
+    // Positions of the dynamic libraries that still have to be called
+    dynamic: VecDeque<usize>,
+    running: usize,
+    rx: mpsc::Receiver<Finished<<H as Hook>::Output>>,
+    hook: Arc<H>,
+    timeout: Option<Duration>,
+    host: Arc<HostFunctions>,
+}
+
+impl<'a, H> ParallelOutputs<'a, H>
+where
+    H: 'static + Hook + Send + Sync,
+    <H as Hook>::Output: Send,
+{
+    pub(crate) fn new(
+        scripts: &'a mut Vec<Script>,
+        hook: H,
+        timeout: Option<Duration>,
+        host: Arc<HostFunctions>,
+    ) -> Self {
+        let hook = Arc::new(hook);
+        let (tx, rx) = mpsc::channel();
+        let mut dynamic = VecDeque::new();
+        let mut running = 0;
+        let slots = std::mem::take(scripts)
+            .into_iter()
+            .enumerate()
+            .map(|(index, mut script)| {
+                if !script.is_active() || !script.is_listening_for::<H>() {
+                    return Some(script);
+                }
+                if script.is_dynamic() {
+                    dynamic.push_back(index);
+                    return Some(script);
+                }
+                let (tx, hook, host) = (tx.clone(), hook.clone(), host.clone());
+                thread::spawn(move || {
+                    let output = script.trigger_tagged(&*hook, timeout, &host);
+                    let _ = tx.send((index, script, output));
+                });
+                running += 1;
+                None
+            })
+            .collect();
+        Self {
+            scripts,
+            slots,
+            dynamic,
+            running,
+            rx,
+            hook,
+            timeout,
+            host,
+        }
+    }
+    fn finish(
+        &mut self,
+        (index, script, output): Finished<<H as Hook>::Output>,
+    ) -> ScriptOutput<<H as Hook>::Output> {
+        self.running -= 1;
+        self.slots[index] = Some(script);
+        output
+    }
+}
+
+impl<'a, H> Iterator for ParallelOutputs<'a, H>
+where
+    H: 'static + Hook + Send + Sync,
+    <H as Hook>::Output: Send,
+{
+    type Item = ScriptOutput<<H as Hook>::Output>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Ok(finished) = self.rx.try_recv() {
+            return Some(self.finish(finished));
+        }
+        // Call the dynamic libraries while the processes are busy
+        if let Some(index) = self.dynamic.pop_front() {
+            let script = self.slots[index]
+                .as_mut()
+                .expect("dynamic libraries stay on the caller thread");
+            return Some(script.trigger_tagged(&*self.hook, self.timeout, &self.host));
+        }
+        if self.running == 0 {
+            return None;
+        }
+        match self.rx.recv() {
+            Ok(finished) => Some(self.finish(finished)),
+            Err(_) => panic!("a script thread panicked"),
+        }
+    }
+}
+
+impl<'a, H: Hook> Drop for ParallelOutputs<'a, H> {
+    fn drop(&mut self) {
+        // Wait for the scripts that are still running, a script lost in a panicked thread is not put back
+        while self.running > 0 {
+            match self.rx.recv() {
+                Ok((index, script, _)) => {
+                    self.running -= 1;
+                    self.slots[index] = Some(script);
+                }
+                Err(_) => break,
+            }
+        }
+        *self.scripts = self.slots.drain(..).flatten().collect();
+    }
+}
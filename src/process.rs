@@ -0,0 +1,498 @@
+//! Process scripts ([ScriptType::OneShot](crate::ScriptType::OneShot) and [ScriptType::Daemon](crate::ScriptType::Daemon)) plumbing
+
+use crate::{
+    frame::{self, Kind},
+    stderr::StderrSink,
+    Codec, Error, ProtocolChannel, ScriptLaunchConfig,
+};
+#[cfg(all(feature = "sandbox", target_os = "linux"))]
+use crate::{sandbox, SandboxPolicy};
+#[cfg(unix)]
+use crate::{ResourceLimits, CRASH_GRACE};
+use serde::de::DeserializeOwned;
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, ExitStatus, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+/// A spawned script, the protocol is spoken on its stdin/stdout or on the pipes of [ProtocolChannel::Pipes]\
+/// The input is written and the output is read by background threads so writes and reads can be bounded by a deadline
+#[derive(Debug)]
+pub(crate) struct Process {
+    child: Child,
+    // `None` once the script is killed
+    input: Option<ChannelWriter>,
+    output: ChannelReader,
+    #[cfg(all(feature = "sandbox", target_os = "linux"))]
+    sandboxed: bool,
+    #[cfg(unix)]
+    limits: Option<ResourceLimits>,
+    // Returns whether the script reported an allocation failure on its stderr
+    #[cfg(unix)]
+    stderr: Option<JoinHandle<bool>>,
+}
+
+/// How process scripts are spawned, it's configured on the [ScriptManager](crate::ScriptManager)
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Launcher {
+    pub(crate) channel: ProtocolChannel,
+    // The launch config of the scripts that don't have their own
+    pub(crate) config: ScriptLaunchConfig,
+    // Launch configs of specific scripts, by canonical path
+    pub(crate) script_configs: HashMap<PathBuf, ScriptLaunchConfig>,
+    // The sandbox of the scripts that don't have their own
+    #[cfg(all(feature = "sandbox", target_os = "linux"))]
+    pub(crate) sandbox: Option<SandboxPolicy>,
+    // Sandboxes of specific scripts, by canonical path
+    #[cfg(all(feature = "sandbox", target_os = "linux"))]
+    pub(crate) script_sandboxes: HashMap<PathBuf, SandboxPolicy>,
+    // The limits of the scripts that don't have their own
+    #[cfg(unix)]
+    pub(crate) limits: Option<ResourceLimits>,
+    // Limits of specific scripts, by canonical path
+    #[cfg(unix)]
+    pub(crate) script_limits: HashMap<PathBuf, ResourceLimits>,
+}
+
+impl Launcher {
+    /// The launch config of the script at `path`
+    pub(crate) fn config(&self, path: &Path) -> &ScriptLaunchConfig {
+        self.script_configs
+            .get(&canonical(path))
+            .unwrap_or(&self.config)
+    }
+    /// The sandbox policy of the script at `path`
+    #[cfg(all(feature = "sandbox", target_os = "linux"))]
+    pub(crate) fn sandbox(&self, path: &Path) -> Option<&SandboxPolicy> {
+        self.script_sandboxes
+            .get(&canonical(path))
+            .or(self.sandbox.as_ref())
+    }
+    /// The resource limits of the script at `path`
+    #[cfg(unix)]
+    pub(crate) fn limits(&self, path: &Path) -> Option<ResourceLimits> {
+        self.script_limits
+            .get(&canonical(path))
+            .or(self.limits.as_ref())
+            .copied()
+    }
+}
+
+/// The key of a script in the per script settings of the [Launcher], the path itself if it can't be canonicalized
+pub(crate) fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+impl Process {
+    /// The piped stderr of the script goes to `stderr`
+    pub(crate) fn spawn(
+        path: &Path,
+        launcher: &Launcher,
+        stderr: &StderrSink,
+    ) -> Result<Self, Error> {
+        let config = launcher.config(path);
+        let mut command = config.command(path)?;
+        let pipes: Option<(io::PipeReader, io::PipeWriter)> = match launcher.channel {
+            ProtocolChannel::Stdio => {
+                command.stdin(Stdio::piped()).stdout(Stdio::piped());
+                None
+            }
+            #[cfg(unix)]
+            ProtocolChannel::Pipes => Some(pipes(&mut command)?),
+        };
+        #[cfg(unix)]
+        let limits = launcher.limits(path);
+        #[cfg(unix)]
+        if let Some(limits) = limits {
+            limits.apply(&mut command);
+            // The stderr is watched for allocation failures, see [ResourceLimits::exceeded]
+            if limits.limits_memory() {
+                command.stderr(Stdio::piped());
+            }
+        }
+        // Applied last, so the sandbox doesn't apply to the setup of the pipes and limits
+        #[cfg(all(feature = "sandbox", target_os = "linux"))]
+        let sandbox = launcher.sandbox(path);
+        #[cfg(all(feature = "sandbox", target_os = "linux"))]
+        if let Some(sandbox) = sandbox {
+            sandbox.apply(&mut command, path)?;
+        }
+        let mut child = command.spawn()?;
+        let stderr = child
+            .stderr
+            .take()
+            .map(|piped| stderr.capture(piped, config.stderr_mode()));
+        #[cfg(not(unix))]
+        drop(stderr);
+        let (input, output) = match pipes {
+            #[cfg(unix)]
+            Some((reader, writer)) => (Input::Pipe(writer), ChannelReader::new(reader)),
+            _ => (
+                Input::Stdin(child.stdin.take().expect("stdin is piped")),
+                ChannelReader::new(child.stdout.take().expect("stdout is piped")),
+            ),
+        };
+        Ok(Self {
+            child,
+            input: Some(ChannelWriter::new(input)),
+            output,
+            #[cfg(all(feature = "sandbox", target_os = "linux"))]
+            sandboxed: sandbox.is_some(),
+            #[cfg(unix)]
+            limits,
+            #[cfg(unix)]
+            stderr,
+        })
+    }
+    /// Where messages to the script are written\
+    /// Fails with [io::ErrorKind::BrokenPipe] if the script was killed
+    pub(crate) fn input(&mut self) -> io::Result<&mut ChannelWriter> {
+        self.input
+            .as_mut()
+            .ok_or_else(|| io::ErrorKind::BrokenPipe.into())
+    }
+    /// Writes to the input and reads from the output will fail with [io::ErrorKind::TimedOut] after `timeout` elapses
+    pub(crate) fn set_timeout(&mut self, timeout: Option<Duration>) {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        if let Some(input) = &mut self.input {
+            input.deadline = deadline;
+            input.timed_out = false;
+        }
+        self.output.deadline = deadline;
+        self.output.timed_out = false;
+    }
+    /// Where messages from the script are read
+    pub(crate) fn output(&mut self) -> &mut ChannelReader {
+        &mut self.output
+    }
+    /// Read a frame of `kind` from the output
+    pub(crate) fn read_frame<T: DeserializeOwned>(
+        &mut self,
+        codec: Codec,
+        kind: Kind,
+    ) -> Result<T, Error> {
+        self.read_with(|bytes| frame::decode_prefix(bytes, codec, kind))?
+    }
+    /// Decode a value from the output with `decode`, which returns the value and the number of bytes it spans, or `None` if it needs more bytes
+    pub(crate) fn read_with<T>(
+        &mut self,
+        mut decode: impl FnMut(&[u8]) -> Result<Option<(T, usize)>, Error>,
+    ) -> Result<T, Error> {
+        let output = &mut self.output;
+        loop {
+            if let Some((value, len)) = decode(&output.buf[output.pos..])? {
+                output.pos += len;
+                return Ok(value);
+            }
+            if !output.receive()? {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+        }
+    }
+    /// Same as [Process::read_with] but it never blocks, `None` is returned if the value is not fully received yet\
+    /// The bytes received so far are kept for the next read
+    pub(crate) fn try_read_with<T>(
+        &mut self,
+        decode: impl FnOnce(&[u8]) -> Result<Option<(T, usize)>, Error>,
+    ) -> Result<Option<T>, Error> {
+        let output = &mut self.output;
+        output.buf.drain(..output.pos);
+        output.pos = 0;
+        while let Ok(chunk) = output.rx.try_recv() {
+            output.buf.extend(chunk?);
+        }
+        Ok(decode(&output.buf[output.pos..])?.map(|(value, len)| {
+            output.pos += len;
+            value
+        }))
+    }
+    /// Whether the last write to the input or read from the output failed because its deadline passed
+    pub(crate) fn timed_out(&self) -> bool {
+        self.output.timed_out || self.input.as_ref().is_some_and(|input| input.timed_out)
+    }
+    /// Returns the exit status if the child exited, waiting at most `grace` for it to exit
+    pub(crate) fn exit_status(&mut self, grace: Duration) -> Option<ExitStatus> {
+        let deadline = Instant::now() + grace;
+        loop {
+            match self.child.try_wait() {
+                Ok(Some(status)) => return Some(status),
+                Ok(None) if Instant::now() < deadline => {
+                    std::thread::sleep(Duration::from_millis(10))
+                }
+                _ => return None,
+            }
+        }
+    }
+    /// The error to report if the script at `path` was killed for breaking its sandbox (see [SandboxPolicy]) or exceeding its [ResourceLimits]\
+    /// It waits [CRASH_GRACE] at most for a restricted script to exit
+    #[cfg(unix)]
+    pub(crate) fn violation(&mut self, path: &Path) -> Option<Error> {
+        #[cfg(all(feature = "sandbox", target_os = "linux"))]
+        let sandboxed = self.sandboxed;
+        #[cfg(not(all(feature = "sandbox", target_os = "linux")))]
+        let sandboxed = false;
+        if !sandboxed && self.limits.is_none() {
+            return None;
+        }
+        let status = self.exit_status(CRASH_GRACE)?;
+        #[cfg(all(feature = "sandbox", target_os = "linux"))]
+        if sandboxed && sandbox::killed_by_filter(status) {
+            return Some(Error::SandboxViolation {
+                path: path.to_path_buf(),
+            });
+        }
+        let allocation_failed = self.allocation_failed(CRASH_GRACE);
+        let limit = self.limits?.exceeded(status, allocation_failed)?;
+        Some(Error::ResourceLimitExceeded {
+            path: path.to_path_buf(),
+            limit,
+        })
+    }
+    #[cfg(not(unix))]
+    pub(crate) fn violation(&mut self, _path: &Path) -> Option<Error> {
+        None
+    }
+    /// Whether the exited script reported an allocation failure on its stderr\
+    /// It waits `grace` at most for the rest of the stderr to be read
+    #[cfg(unix)]
+    fn allocation_failed(&mut self, grace: Duration) -> bool {
+        let stderr = match self.stderr.take() {
+            Some(stderr) => stderr,
+            None => return false,
+        };
+        let deadline = Instant::now() + grace;
+        while !stderr.is_finished() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        stderr.is_finished() && stderr.join().unwrap_or(false)
+    }
+    /// Kill the child and reap it
+    pub(crate) fn kill(&mut self) {
+        self.input = None;
+        // This errors if the script has already exited
+        // We don't care about this error
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The main program end of the channel the script reads its messages from
+#[derive(Debug)]
+pub(crate) enum Input {
+    Stdin(ChildStdin),
+    #[cfg(unix)]
+    Pipe(io::PipeWriter),
+}
+
+impl Write for Input {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Stdin(stdin) => stdin.write(buf),
+            #[cfg(unix)]
+            Self::Pipe(pipe) => pipe.write(buf),
+        }
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Stdin(stdin) => stdin.flush(),
+            #[cfg(unix)]
+            Self::Pipe(pipe) => pipe.flush(),
+        }
+    }
+}
+
+/// Create the pipes of [ProtocolChannel::Pipes] and pass their script ends to `command`, returns the main program ends\
+/// The script inherits the main program stdin and stdout
+#[cfg(unix)]
+pub(crate) fn pipes(command: &mut Command) -> io::Result<(io::PipeReader, io::PipeWriter)> {
+    use crate::PROTOCOL_FDS_VAR;
+    use std::os::unix::{
+        io::{AsRawFd, OwnedFd},
+        process::CommandExt,
+    };
+    let (script_reader, writer) = io::pipe()?;
+    let (reader, script_writer) = io::pipe()?;
+    let (script_reader, script_writer) =
+        (OwnedFd::from(script_reader), OwnedFd::from(script_writer));
+    command.env(
+        PROTOCOL_FDS_VAR,
+        format!(
+            "{},{}",
+            script_reader.as_raw_fd(),
+            script_writer.as_raw_fd()
+        ),
+    );
+    // The pipes are close-on-exec, so only the script inherits its ends
+    // They are dropped with `command`, closing them in the main program so it notices when the script exits
+    unsafe {
+        command.pre_exec(move || {
+            for fd in [&script_reader, &script_writer] {
+                if libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, 0) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+    Ok((reader, writer))
+}
+
+/// Forwards the bytes to a background thread that owns the actual writer, each write waits for them to be written
+#[derive(Debug)]
+pub(crate) struct ChannelWriter {
+    // The bytes to write, and where to send the result
+    tx: Sender<(Vec<u8>, Sender<io::Result<()>>)>,
+    deadline: Option<Instant>,
+    timed_out: bool,
+}
+
+impl ChannelWriter {
+    fn new<W: Write + Send + 'static>(mut writer: W) -> Self {
+        let (tx, rx) = mpsc::channel::<(Vec<u8>, Sender<io::Result<()>>)>();
+        // The writer is dropped when the thread exits, the script reads EOF
+        std::thread::spawn(move || {
+            for (bytes, written) in rx {
+                let result = writer.write_all(&bytes).and_then(|_| writer.flush());
+                let failed = result.is_err();
+                let _ = written.send(result);
+                if failed {
+                    break;
+                }
+            }
+        });
+        Self {
+            tx,
+            deadline: None,
+            timed_out: false,
+        }
+    }
+    /// Queue `bytes` without waiting for the script to read them
+    pub(crate) fn send(&self, bytes: Vec<u8>) -> io::Result<()> {
+        let (written, _) = mpsc::channel();
+        self.tx
+            .send((bytes, written))
+            .map_err(|_| io::ErrorKind::BrokenPipe.into())
+    }
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let (written, result) = mpsc::channel();
+        self.tx
+            .send((buf.to_vec(), written))
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        let result = match self.deadline {
+            Some(deadline) => {
+                match result.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(result) => result,
+                    // The script stopped reading, the bytes are written if it reads again
+                    Err(RecvTimeoutError::Timeout) => {
+                        self.timed_out = true;
+                        return Err(io::ErrorKind::TimedOut.into());
+                    }
+                    Err(RecvTimeoutError::Disconnected) => Err(io::ErrorKind::BrokenPipe.into()),
+                }
+            }
+            None => result
+                .recv()
+                .unwrap_or_else(|_| Err(io::ErrorKind::BrokenPipe.into())),
+        };
+        result.map(|()| buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reads the bytes forwarded by a background thread that owns the actual reader
+#[derive(Debug)]
+pub(crate) struct ChannelReader {
+    rx: Receiver<io::Result<Vec<u8>>>,
+    buf: Vec<u8>,
+    pos: usize,
+    deadline: Option<Instant>,
+    timed_out: bool,
+}
+
+impl ChannelReader {
+    fn new<R: Read + Send + 'static>(mut reader: R) -> Self {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut buf = [0; 8 * 1024];
+            loop {
+                let chunk = match reader.read(&mut buf) {
+                    // EOF, dropping the sender signals it to the receiver
+                    Ok(0) => break,
+                    Ok(n) => Ok(buf[..n].to_vec()),
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => Err(e),
+                };
+                let failed = chunk.is_err();
+                if tx.send(chunk).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        Self {
+            rx,
+            buf: Vec::new(),
+            pos: 0,
+            deadline: None,
+            timed_out: false,
+        }
+    }
+    /// Returns the next byte without consuming it, `None` on EOF
+    pub(crate) fn peek(&mut self) -> io::Result<Option<u8>> {
+        Ok(self.fill()?.then(|| self.buf[self.pos]))
+    }
+    /// Wait for more bytes if all the received ones were read, returns false on EOF
+    fn fill(&mut self) -> io::Result<bool> {
+        if self.pos == self.buf.len() {
+            return self.receive();
+        }
+        Ok(true)
+    }
+    /// Wait for the next chunk and append it to the unread bytes, returns false on EOF
+    fn receive(&mut self) -> io::Result<bool> {
+        let chunk = match self.deadline {
+            Some(deadline) => {
+                match self
+                    .rx
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                {
+                    Ok(chunk) => chunk,
+                    Err(RecvTimeoutError::Timeout) => {
+                        self.timed_out = true;
+                        return Err(io::ErrorKind::TimedOut.into());
+                    }
+                    Err(RecvTimeoutError::Disconnected) => return Ok(false),
+                }
+            }
+            None => match self.rx.recv() {
+                Ok(chunk) => chunk,
+                Err(_) => return Ok(false),
+            },
+        };
+        let chunk = chunk?;
+        self.buf.drain(..self.pos);
+        self.pos = 0;
+        self.buf.extend(chunk);
+        Ok(true)
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if !self.fill()? {
+            return Ok(0);
+        }
+        let n = (&self.buf[self.pos..]).read(out)?;
+        self.pos += n;
+        Ok(n)
+    }
+}
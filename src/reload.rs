@@ -0,0 +1,287 @@
+use crate::{
+    is_dynamic_library, load_dynamic_library, script_paths, start_script, Error, MismatchedHook,
+    Script, ScriptManager, StderrSink, Version,
+};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::SystemTime,
+};
+
+/// What changed in the scripts folders, returned by [ScriptManager::reload] and [ScriptManager::reload_dynamic]
+#[derive(Debug)]
+pub enum ReloadEvent {
+    /// A new script was found and loaded
+    Added(PathBuf),
+    /// A script file was modified, the script was loaded again\
+    /// Its id, activation state and restart policy are kept
+    Reloaded(PathBuf),
+    /// A script file was removed, the script was removed from the [ScriptManager]
+    Removed(PathBuf),
+    /// A new or modified script could not be loaded, it will be tried again when its file changes\
+    /// A modified script keeps running its previous version
+    Failed(PathBuf, Error),
+    /// A script that was just added or reloaded was built with a different definition of a hook registered with [ScriptManager::register_hook], the hook is disabled for it
+    HookMismatch(PathBuf, MismatchedHook),
+}
+
+/// A folder that was passed to one of the methods that add scripts
+#[derive(Debug, Clone)]
+pub(crate) struct WatchedDir {
+    pub(crate) path: PathBuf,
+    pub(crate) version: Version,
+    pub(crate) dynamic: bool,
+}
+
+/// Identifies a version of a script file, see [file_stamp]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FileStamp {
+    len: u64,
+    modified: Option<SystemTime>,
+    #[cfg(unix)]
+    inode: u64,
+}
+
+/// The size and modification time of the file at `path`, and its inode on Unix so a file replaced by another one is noticed even if they match
+pub(crate) fn file_stamp(path: &Path) -> Option<FileStamp> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some(FileStamp {
+        len: metadata.len(),
+        modified: metadata.modified().ok(),
+        #[cfg(unix)]
+        inode: std::os::unix::fs::MetadataExt::ino(&metadata),
+    })
+}
+
+/// Load the library at `path` from a temporary copy\
+/// The loader returns the already loaded library for a path it knows, so the new version can't be loaded from the same path while the old one is still loaded
+unsafe fn load_dynamic_library_copy(path: &Path, version: &Version) -> Result<Script, Error> {
+    static COPIES: AtomicU64 = AtomicU64::new(0);
+    let copy = std::env::temp_dir().join(format!(
+        "rscript-{}-{}-{}",
+        std::process::id(),
+        COPIES.fetch_add(1, Ordering::Relaxed),
+        path.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    std::fs::copy(path, &copy)?;
+    let loaded = load_dynamic_library(&copy, version);
+    // A loaded library stays usable after its file is removed (on Unix, the copy is left behind elsewhere)
+    let _ = std::fs::remove_file(&copy);
+    let mut script = loaded?;
+    script.path = path.to_path_buf();
+    Ok(script)
+}
+
+impl ScriptManager {
+    /// Look again in the folders previously passed to [ScriptManager::add_scripts_by_path] and [ScriptManager::discover_scripts_by_path]\
+    /// - New scripts are loaded
+    /// - Modified scripts are greeted again, daemons are restarted
+    /// - Scripts whose file was removed are removed from the manager
+    ///
+    /// A file is considered modified if its size, modification time or inode (on Unix) changed\
+    /// A modified script is loaded before the old one is stopped, if it fails to load the old one keeps running and [ReloadEvent::Failed] is reported
+    pub fn reload(&mut self) -> Result<Vec<ReloadEvent>, Error> {
+        let (greeting_timeout, launcher) = (self.greeting_timeout, self.launcher.clone());
+        self.reload_internal(false, &mut |path, version| {
+            start_script(
+                path,
+                version,
+                greeting_timeout,
+                &launcher,
+                StderrSink::new(path),
+            )
+        })
+    }
+    /// Same as [ScriptManager::reload] but for the folders passed to [ScriptManager::add_dynamic_scripts_by_path] and [ScriptManager::discover_dynamic_scripts_by_path]\
+    /// Modified libraries are loaded from a temporary copy, since the loader would return the old library for the same path
+    ///
+    /// # Safety
+    /// See <https://docs.rs/libloading/0.7.1/libloading/struct.Library.html#safety>
+    pub unsafe fn reload_dynamic(&mut self) -> Result<Vec<ReloadEvent>, Error> {
+        self.reload_internal(true, &mut |path, version| {
+            load_dynamic_library_copy(path, version)
+        })
+    }
+
+    pub(crate) fn watch_dir(&mut self, path: &Path, version: &Version, dynamic: bool) {
+        if !self
+            .watched_dirs
+            .iter()
+            .any(|dir| dir.path == path && dir.dynamic == dynamic)
+        {
+            self.watched_dirs.push(WatchedDir {
+                path: path.to_path_buf(),
+                version: version.clone(),
+                dynamic,
+            });
+        }
+    }
+
+    fn reload_internal(
+        &mut self,
+        dynamic: bool,
+        load: &mut dyn FnMut(&Path, &Version) -> Result<Script, Error>,
+    ) -> Result<Vec<ReloadEvent>, Error> {
+        let dirs: Vec<WatchedDir> = self
+            .watched_dirs
+            .iter()
+            .filter(|dir| dir.dynamic == dynamic)
+            .cloned()
+            .collect();
+        let mut events = vec![];
+        let mut present = HashSet::new();
+
+        for dir in &dirs {
+            for path in script_paths(&dir.path)? {
+                if is_dynamic_library(&path) != dynamic {
+                    continue;
+                }
+                present.insert(path.clone());
+                let stamp = file_stamp(&path);
+
+                match self.scripts.iter().position(|script| script.path == path) {
+                    Some(idx) => {
+                        if self.scripts[idx].stamp == stamp {
+                            continue;
+                        }
+                        // The old script keeps running until the new one is ready
+                        match load(&path, &dir.version) {
+                            Ok(mut script) => {
+                                let mismatches = script.check_fingerprints(&self.hook_fingerprints);
+                                let old = &self.scripts[idx];
+                                if !old.is_active() && !old.has_failed() {
+                                    script.deactivate();
+                                }
+                                script.id = old.id;
+                                script.restart_policy = old.restart_policy;
+                                script.stamp = stamp;
+                                std::mem::replace(&mut self.scripts[idx], script)
+                                    .stop(self.shutdown_grace());
+                                events.push(ReloadEvent::Reloaded(path.clone()));
+                                events.extend(mismatches.into_iter().map(|mismatch| {
+                                    ReloadEvent::HookMismatch(path.clone(), mismatch)
+                                }));
+                            }
+                            Err(e) => {
+                                // Not retried until the file changes again
+                                self.scripts[idx].stamp = stamp;
+                                events.push(ReloadEvent::Failed(path, e));
+                            }
+                        }
+                    }
+                    None => {
+                        // Don't retry scripts that failed, until they change
+                        if self.failed_paths.get(&path) == Some(&stamp) {
+                            continue;
+                        }
+                        match load(&path, &dir.version) {
+                            Ok(mut script) => {
+                                let mismatches = script.check_fingerprints(&self.hook_fingerprints);
+                                script.stamp = stamp;
+                                self.failed_paths.remove(&path);
+                                self.scripts.push(script);
+                                events.push(ReloadEvent::Added(path.clone()));
+                                events.extend(mismatches.into_iter().map(|mismatch| {
+                                    ReloadEvent::HookMismatch(path.clone(), mismatch)
+                                }));
+                            }
+                            Err(e) => {
+                                self.failed_paths.insert(path.clone(), stamp);
+                                events.push(ReloadEvent::Failed(path, e));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // Remove the scripts whose file is gone
+        let grace = self.shutdown_grace();
+        let mut idx = 0;
+        while idx < self.scripts.len() {
+            let script = &self.scripts[idx];
+            let watched = dirs
+                .iter()
+                .any(|dir| script.path.parent() == Some(dir.path.as_path()));
+            if watched && script.is_dynamic() == dynamic && !present.contains(&script.path) {
+                let mut script = self.scripts.remove(idx);
+                script.stop(grace);
+                events.push(ReloadEvent::Removed(script.path.clone()));
+            } else {
+                idx += 1;
+            }
+        }
+        self.failed_paths.retain(|path, _| path.exists());
+
+        Ok(events)
+    }
+}
+
+/// Watches the scripts folders for changes, it can be created with [ScriptManager::watcher]\
+/// It only reports that something changed, the scripts are updated with [ScriptManager::reload] and [ScriptManager::reload_dynamic]
+///
+/// ```rust, no_run
+/// # use rscript::*;
+/// let mut sm = ScriptManager::default();
+/// sm.add_scripts_by_path("/path/to/scripts", Version::new(0, 1, 0)).unwrap();
+/// let mut watcher = sm.watcher().unwrap();
+/// loop {
+///     watcher.wait().unwrap();
+///     for event in sm.reload().unwrap() {
+///         println!("{:?}", event);
+///     }
+/// }
+/// ```
+#[cfg(all(feature = "watch", target_os = "linux"))]
+pub struct ScriptWatcher {
+    inotify: inotify::Inotify,
+    buffer: Vec<u8>,
+}
+
+#[cfg(all(feature = "watch", target_os = "linux"))]
+impl ScriptWatcher {
+    /// Returns true if any of the watched folders changed since the last call, it doesn't block
+    pub fn changed(&mut self) -> Result<bool, Error> {
+        let mut changed = false;
+        loop {
+            match self.inotify.read_events(&mut self.buffer) {
+                Ok(mut events) => changed |= events.next().is_some(),
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(changed),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+    /// Block until one of the watched folders changes
+    pub fn wait(&mut self) -> Result<(), Error> {
+        self.inotify.read_events_blocking(&mut self.buffer)?;
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "watch", target_os = "linux"))]
+impl ScriptManager {
+    /// Create a [ScriptWatcher] for all the folders that scripts were added from\
+    /// Folders added after creating the watcher are not watched\
+    /// Note that modifying the target of a symlink placed in a scripts folder is not detected
+    pub fn watcher(&self) -> Result<ScriptWatcher, Error> {
+        use inotify::{Inotify, WatchMask};
+
+        let inotify = Inotify::init()?;
+        for dir in &self.watched_dirs {
+            inotify.watches().add(
+                &dir.path,
+                WatchMask::CREATE
+                    | WatchMask::DELETE
+                    | WatchMask::CLOSE_WRITE
+                    | WatchMask::MOVED_TO
+                    | WatchMask::MOVED_FROM
+                    | WatchMask::ATTRIB,
+            )?;
+        }
+        Ok(ScriptWatcher {
+            inotify,
+            buffer: vec![0; 4096],
+        })
+    }
+}
@@ -0,0 +1,169 @@
+//! Sandbox of process scripts, enabled with the `sandbox` feature (Linux only)
+//! - The filesystem is restricted with [Landlock](https://docs.kernel.org/userspace-api/landlock.html), opening a file outside the allowed paths fails with a permission error inside the script
+//! - The network and the denied syscalls are restricted with a seccomp filter, a script that breaks it is killed and reported with [Error::SandboxViolation]
+
+use crate::Error;
+use landlock::{
+    path_beneath_rules, Access, AccessFs, CompatLevel, Compatible, Ruleset, RulesetAttr,
+    RulesetCreatedAttr, ABI,
+};
+use seccompiler::{
+    BpfProgram, SeccompAction, SeccompCmpArgLen, SeccompCmpOp, SeccompCondition, SeccompFilter,
+    SeccompRule,
+};
+use std::{
+    collections::BTreeMap,
+    convert::TryInto,
+    io,
+    os::unix::process::{CommandExt, ExitStatusExt},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus},
+};
+
+/// Restrictions applied to a process script ([ScriptType::OneShot](crate::ScriptType::OneShot) or [ScriptType::Daemon](crate::ScriptType::Daemon)), see [ScriptManager::set_sandbox_policy](crate::ScriptManager::set_sandbox_policy)\
+/// The default policy:
+/// - Allows reading and executing the script and the files under the folders needed to start a program: `/usr`, `/lib`, `/lib64`, `/bin` and `/etc`
+/// - Allows reading and writing `/dev/null`
+/// - Denies network access
+///
+/// ```rust, no_run
+/// # use rscript::*;
+/// let mut policy = SandboxPolicy::default();
+/// policy
+///     .allow_read("/usr/share/dict")
+///     .allow_write("/tmp/my-script")
+///     .deny_syscall(libc::SYS_ptrace);
+/// let mut sm = ScriptManager::default();
+/// sm.set_sandbox_policy(Some(policy));
+/// ```
+#[derive(Debug, Clone)]
+pub struct SandboxPolicy {
+    read: Vec<PathBuf>,
+    write: Vec<PathBuf>,
+    network: bool,
+    denied_syscalls: Vec<i64>,
+}
+
+impl Default for SandboxPolicy {
+    fn default() -> Self {
+        Self {
+            read: ["/usr", "/lib", "/lib64", "/bin", "/etc"]
+                .iter()
+                .map(PathBuf::from)
+                .collect(),
+            write: vec![PathBuf::from("/dev/null")],
+            network: false,
+            denied_syscalls: vec![],
+        }
+    }
+}
+
+/// The Landlock version whose access rights are restricted, the ones that the kernel doesn't support are left out
+const ABI: ABI = ABI::V3;
+
+impl SandboxPolicy {
+    /// Allow reading and executing the files under `path`
+    pub fn allow_read<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        self.read.push(path.into());
+        self
+    }
+    /// Allow reading, writing, creating and removing the files under `path`
+    pub fn allow_write<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        self.write.push(path.into());
+        self
+    }
+    /// Allow or deny network access (IPv4 and IPv6 sockets), it's denied by default\
+    /// Unix sockets are not restricted
+    pub fn allow_network(&mut self, allow: bool) -> &mut Self {
+        self.network = allow;
+        self
+    }
+    /// Kill the script if it calls the syscall `number`, for example `libc::SYS_ptrace`
+    pub fn deny_syscall(&mut self, number: i64) -> &mut Self {
+        self.denied_syscalls.push(number);
+        self
+    }
+
+    /// Restrict the script at `path` spawned by `command`\
+    /// Everything is prepared here, the spawned process only enforces it before running the script
+    pub(crate) fn apply(&self, command: &mut Command, path: &Path) -> Result<(), Error> {
+        let mut ruleset = Some(self.ruleset(path).map_err(setup_error)?);
+        let filter = self.filter()?;
+        unsafe {
+            command.pre_exec(move || {
+                if let Some(ruleset) = ruleset.take() {
+                    ruleset
+                        .restrict_self()
+                        .map_err(|_| io::Error::last_os_error())?;
+                }
+                if let Some(filter) = &filter {
+                    seccompiler::apply_filter(filter).map_err(|_| io::Error::last_os_error())?;
+                }
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+    fn ruleset(&self, path: &Path) -> Result<landlock::RulesetCreated, landlock::RulesetError> {
+        Ruleset::default()
+            // Fails if the kernel doesn't support Landlock at all, instead of running the script unrestricted
+            .set_compatibility(CompatLevel::HardRequirement)
+            .handle_access(AccessFs::from_all(ABI::V1))?
+            .set_compatibility(CompatLevel::BestEffort)
+            .handle_access(AccessFs::from_all(ABI))?
+            .create()?
+            .add_rules(path_beneath_rules(
+                self.read.iter().map(PathBuf::as_path).chain(Some(path)),
+                AccessFs::from_read(ABI),
+            ))?
+            .add_rules(path_beneath_rules(&self.write, AccessFs::from_all(ABI)))
+    }
+    /// The seccomp filter, `None` if there is nothing to filter
+    fn filter(&self) -> Result<Option<BpfProgram>, Error> {
+        let mut rules: BTreeMap<i64, Vec<SeccompRule>> = self
+            .denied_syscalls
+            .iter()
+            .map(|syscall| (*syscall, vec![]))
+            .collect();
+        if !self.network {
+            let domains = [libc::AF_INET, libc::AF_INET6]
+                .iter()
+                .map(|domain| {
+                    let condition = SeccompCondition::new(
+                        0,
+                        SeccompCmpArgLen::Dword,
+                        SeccompCmpOp::Eq,
+                        *domain as u64,
+                    )?;
+                    SeccompRule::new(vec![condition])
+                })
+                .collect::<Result<_, _>>()
+                .map_err(setup_error)?;
+            // A syscall that is denied entirely stays denied
+            rules.entry(libc::SYS_socket).or_insert(domains);
+            // io_uring can open sockets without the socket syscall
+            rules.entry(libc::SYS_io_uring_setup).or_default();
+        }
+        if rules.is_empty() {
+            return Ok(None);
+        }
+        let arch = std::env::consts::ARCH.try_into().map_err(setup_error)?;
+        let filter = SeccompFilter::new(
+            rules,
+            SeccompAction::Allow,
+            SeccompAction::KillProcess,
+            arch,
+        )
+        .map_err(setup_error)?;
+        Ok(Some(filter.try_into().map_err(setup_error)?))
+    }
+}
+
+/// Whether a sandboxed script was killed by its seccomp filter
+pub(crate) fn killed_by_filter(status: ExitStatus) -> bool {
+    status.signal() == Some(libc::SIGSYS)
+}
+
+fn setup_error(error: impl std::fmt::Display) -> Error {
+    Error::SandboxSetup(error.to_string())
+}
@@ -0,0 +1,190 @@
+//! Structural fingerprints of the types exchanged with scripts, see [Schema]
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    path::PathBuf,
+    time::Duration,
+};
+
+/// Types that have a structural fingerprint, it's used to compute [Hook::FINGERPRINT](crate::Hook::FINGERPRINT)\
+/// Two types with the same serialized structure (and the same field and variant names) have the same hash, so a script built against an older version of a hook can be detected\
+/// With the `macros` feature, it can be implemented with `#[derive(Schema)]` (`#[derive(Hook)]` implements it for the hook itself)\
+/// Recursive types are not supported
+///
+/// ```rust
+/// use rscript::{Schema, SchemaHasher};
+///
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+/// impl Schema for Point {
+///     const SCHEMA_HASH: u64 = SchemaHasher::new()
+///         .str("struct")
+///         .str("x")
+///         .hash(i32::SCHEMA_HASH)
+///         .str("y")
+///         .hash(i32::SCHEMA_HASH)
+///         .finish();
+/// }
+/// ```
+pub trait Schema {
+    /// The structural hash of the type
+    const SCHEMA_HASH: u64;
+}
+
+/// FNV-1a hasher usable in constants, to implement [Schema]
+#[derive(Debug, Clone, Copy)]
+pub struct SchemaHasher(u64);
+
+impl SchemaHasher {
+    /// Start a new hash
+    pub const fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+    /// Add a string, its length is added too so consecutive strings can't be confused
+    pub const fn str(self, string: &str) -> Self {
+        let mut hasher = self.hash(string.len() as u64);
+        let bytes = string.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            hasher = hasher.byte(bytes[i]);
+            i += 1;
+        }
+        hasher
+    }
+    /// Add a hash, usually the [Schema::SCHEMA_HASH] of a field
+    pub const fn hash(self, hash: u64) -> Self {
+        let bytes = hash.to_le_bytes();
+        let mut hasher = self;
+        let mut i = 0;
+        while i < bytes.len() {
+            hasher = hasher.byte(bytes[i]);
+            i += 1;
+        }
+        hasher
+    }
+    /// The resulting hash
+    pub const fn finish(self) -> u64 {
+        self.0
+    }
+    const fn byte(self, byte: u8) -> Self {
+        Self((self.0 ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+    }
+}
+
+impl Default for SchemaHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Types are hashed after their serialized form, so types that serialize the same way are compatible
+
+macro_rules! leaf_schema {
+    ($($ty: ty => $name: literal),* $(,)?) => {
+        $(
+            impl Schema for $ty {
+                const SCHEMA_HASH: u64 = SchemaHasher::new().str($name).finish();
+            }
+        )*
+    };
+}
+
+leaf_schema!(
+    () => "unit",
+    bool => "bool",
+    char => "char",
+    u8 => "u8",
+    u16 => "u16",
+    u32 => "u32",
+    u64 => "u64",
+    u128 => "u128",
+    usize => "u64",
+    i8 => "i8",
+    i16 => "i16",
+    i32 => "i32",
+    i64 => "i64",
+    i128 => "i128",
+    isize => "i64",
+    f32 => "f32",
+    f64 => "f64",
+    String => "string",
+    PathBuf => "string",
+    Duration => "duration",
+);
+
+macro_rules! seq_schema {
+    ($($ty: ty),* $(,)?) => {
+        $(
+            impl<T: Schema> Schema for $ty {
+                const SCHEMA_HASH: u64 = SchemaHasher::new().str("seq").hash(T::SCHEMA_HASH).finish();
+            }
+        )*
+    };
+}
+
+seq_schema!(Vec<T>, VecDeque<T>, Box<[T]>, HashSet<T>, BTreeSet<T>);
+
+macro_rules! map_schema {
+    ($($ty: ty),* $(,)?) => {
+        $(
+            impl<K: Schema, V: Schema> Schema for $ty {
+                const SCHEMA_HASH: u64 = SchemaHasher::new()
+                    .str("map")
+                    .hash(K::SCHEMA_HASH)
+                    .hash(V::SCHEMA_HASH)
+                    .finish();
+            }
+        )*
+    };
+}
+
+map_schema!(HashMap<K, V>, BTreeMap<K, V>);
+
+impl<T: Schema> Schema for Option<T> {
+    const SCHEMA_HASH: u64 = SchemaHasher::new()
+        .str("option")
+        .hash(T::SCHEMA_HASH)
+        .finish();
+}
+
+impl<T: Schema, E: Schema> Schema for Result<T, E> {
+    const SCHEMA_HASH: u64 = SchemaHasher::new()
+        .str("result")
+        .hash(T::SCHEMA_HASH)
+        .hash(E::SCHEMA_HASH)
+        .finish();
+}
+
+impl<T: Schema> Schema for Box<T> {
+    const SCHEMA_HASH: u64 = T::SCHEMA_HASH;
+}
+
+impl<T: Schema, const N: usize> Schema for [T; N] {
+    const SCHEMA_HASH: u64 = SchemaHasher::new()
+        .str("array")
+        .hash(N as u64)
+        .hash(T::SCHEMA_HASH)
+        .finish();
+}
+
+macro_rules! tuple_schema {
+    ($($name: ident)+) => {
+        impl<$($name: Schema),+> Schema for ($($name,)+) {
+            const SCHEMA_HASH: u64 = SchemaHasher::new()
+                .str("tuple")
+                $(.hash($name::SCHEMA_HASH))+
+                .finish();
+        }
+    };
+}
+
+tuple_schema!(A);
+tuple_schema!(A B);
+tuple_schema!(A B C);
+tuple_schema!(A B C D);
+tuple_schema!(A B C D E);
+tuple_schema!(A B C D E F);
+tuple_schema!(A B C D E F G);
+tuple_schema!(A B C D E F G H);
@@ -1,17 +1,28 @@
 //! This modules contains all what is needed to write scripts
 
-use crate::{Hook, VersionReq};
+use crate::{
+    frame::{self, Kind},
+    Codec, Error, Event, Hook, HostFunction, VersionReq,
+};
 
-use super::{Message, ScriptInfo, ScriptType};
-use std::io::Write;
+use super::{
+    is_greeting_line, BuildInfo, Handshake, Message, ScriptInfo, ScriptMessage, ScriptType,
+    EXECUTE_LINE, NATIVE_GREETING,
+};
+use std::any::Any;
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::io::{BufRead, BufReader, Write};
+use std::panic::AssertUnwindSafe;
 use std::ptr::slice_from_raw_parts;
+use std::sync::{Condvar, Mutex, MutexGuard, OnceLock};
 
 use serde::{de::DeserializeOwned, Serialize};
 
 /// Trait that should be implemented on a script abstraction struct\
 /// This concerns [ScriptType::OneShot] and [ScriptType::Daemon]\
 /// The implementer should provide [Scripter::script_type], [Scripter::name], [Scripter::hooks] and [Scripter::version_requirement]\
-///  The struct should call [Scripter::execute]\
+///  The struct should call [Scripter::execute], or [Scripter::run] which handles typed hooks (see [HookHandlers])\
 ///  ```rust, no_run
 ///  # use rscript::*;
 ///  # use rscript::scripting::Scripter;
@@ -46,34 +57,127 @@ use serde::{de::DeserializeOwned, Serialize};
 ///     }
 ///  }
 ///
-///  fn main() {
-///     let mut my_script = MyScript;
-///     MyScript::execute(&mut |hook_name|MyScript::run(&mut my_script, hook_name));
-///  }
+///  let mut my_script = MyScript;
+///  MyScript::execute(&mut |hook_name|MyScript::run(&mut my_script, hook_name)).unwrap();
 pub trait Scripter {
     // Required methods
     /// The name of the script
     fn name() -> &'static str;
     /// The script type Daemon/OneShot
     fn script_type() -> ScriptType;
-    /// The hooks that the script is interested in
-    fn hooks() -> &'static [&'static str];
     /// The version requirement of the program that the script will run against, when running the script with [Scripter::execute] it will use this version to check if there is an incompatibility between the script and the program
     fn version_requirement() -> VersionReq;
 
     // Provided methods
-    /// Read a hook from stdin
+    /// The hooks that the script is interested in\
+    /// It's only used by [Scripter::execute], [Scripter::run] listens to the hooks that have a handler\
+    /// The hooks are untyped here, so their [Hook::FINGERPRINT] is not advertised
+    fn hooks() -> &'static [&'static str] {
+        &[]
+    }
+    /// The [Codec] used to talk to the main program, it's advertised in the handshake\
+    /// Defaults to [Codec::Bincode], the main program must be built with the feature of the chosen codec
+    fn codec() -> Codec {
+        Codec::Bincode
+    }
+    /// Read a hook from the main program
     fn read<H: Hook>() -> H {
-        bincode::deserialize_from(std::io::stdin()).unwrap()
+        frame::read(&mut *input(), Self::codec(), Kind::Hook).unwrap()
     }
-    /// Write a value to stdout\
+    /// Write a value to the main program\
     /// It takes the hook as a type argument in-order to make sure that the output provided correspond to the hook's expected output
     fn write<H: Hook>(output: &<H as Hook>::Output) {
-        bincode::serialize_into(std::io::stdout(), output).unwrap()
+        write_output::<H>(Self::codec(), output).unwrap()
+    }
+    /// Call a function provided by the main program, it can be used while handling a hook (before calling [Scripter::write])\
+    /// An error is returned if the main program didn't register a handler for this function
+    ///
+    /// ```rust, no_run
+    /// # use rscript::{Hook, HostFunction, VersionReq, scripting::Scripter};
+    /// # #[derive(serde::Serialize, serde::Deserialize)]
+    /// # struct MyHook;
+    /// # impl Hook for MyHook {
+    /// #   const NAME: &'static str = "MyHook";
+    /// #   type Output = usize;
+    /// # }
+    /// // Declared on the api crate
+    /// #[derive(serde::Serialize, serde::Deserialize)]
+    /// struct CurrentBuffer;
+    /// impl HostFunction for CurrentBuffer {
+    ///     const NAME: &'static str = "CurrentBuffer";
+    ///     type Output = String;
+    /// }
+    /// # struct MyScript;
+    /// # impl Scripter for MyScript {
+    /// #   fn name() -> &'static str { todo!() }
+    /// #   fn script_type() -> rscript::ScriptType { todo!() }
+    /// #   fn hooks() -> &'static [&'static str] { todo!() }
+    /// #   fn version_requirement() -> VersionReq { todo!() }
+    /// # }
+    ///
+    /// fn run(hook_name: &str) {
+    ///     let _hook: MyHook = MyScript::read();
+    ///     let buffer = MyScript::call(&CurrentBuffer).unwrap();
+    ///     MyScript::write::<MyHook>(&buffer.len());
+    /// }
+    /// ```
+    fn call<F: HostFunction>(function: &F) -> Result<<F as HostFunction>::Output, Error> {
+        let codec = Self::codec();
+        let message = ScriptMessage::HostCall {
+            name: F::NAME.into(),
+            data: codec.encode(function)?,
+        };
+        let mut output = output();
+        frame::write(&mut *output, codec, Kind::ScriptMessage, &message)?;
+        output.flush()?;
+        drop(output);
+
+        let reply: Result<Vec<u8>, String> = frame::read(&mut *input(), codec, Kind::HostReply)?;
+        codec.decode(&reply.map_err(Error::HostFunction)?)
+    }
+    /// Send an [Event] to the main program, it receives it with [ScriptManager::poll_events](crate::ScriptManager::poll_events)\
+    /// [ScriptType::Daemon] scripts can send events at any time, even from another thread, [ScriptType::OneShot] scripts only while handling a hook\
+    /// Events sent before [Scripter::execute] answered the greeting wait until it does
+    ///
+    /// ```rust, no_run
+    /// # use rscript::{Event, VersionReq, scripting::Scripter};
+    /// // Declared on the api crate
+    /// #[derive(serde::Serialize, serde::Deserialize)]
+    /// struct FileChanged(std::path::PathBuf);
+    /// impl Event for FileChanged {
+    ///     const NAME: &'static str = "FileChanged";
+    /// }
+    /// # struct MyScript;
+    /// # impl Scripter for MyScript {
+    /// #   fn name() -> &'static str { todo!() }
+    /// #   fn script_type() -> rscript::ScriptType { todo!() }
+    /// #   fn hooks() -> &'static [&'static str] { todo!() }
+    /// #   fn version_requirement() -> VersionReq { todo!() }
+    /// # }
+    ///
+    /// std::thread::spawn(|| loop {
+    ///     let path = todo!(); // wait for a file to change
+    ///     MyScript::send_event(&FileChanged(path)).unwrap();
+    /// });
+    /// MyScript::execute(&mut |hook_name| todo!()).unwrap();
+    /// ```
+    fn send_event<E: Event>(event: &E) -> Result<(), Error> {
+        let codec = Self::codec();
+        let message = ScriptMessage::Event {
+            name: E::NAME.into(),
+            data: codec.encode(event)?,
+        };
+        GREETED.wait();
+        let mut output = output();
+        frame::write(&mut *output, codec, Kind::ScriptMessage, &message)?;
+        output.flush()?;
+        Ok(())
     }
     /// This function is the script entry point.\
     /// 1. It handles the initial greeting and exiting if the script type is [ScriptType::OneShot]
-    /// 2. It handles receiving hooks, the user is expected to provide a function that acts on a hook name, the user function should use the hook name to read the actual hook from stdin using [Scripter::read]
+    /// 2. It handles receiving hooks, the user is expected to provide a function that acts on a hook name, the user function should use the hook name to read the actual hook using [Scripter::read]
+    ///
+    /// The protocol is spoken on stdin/stdout, or on the pipes passed by the main program if it uses [ProtocolChannel::Pipes](crate::ProtocolChannel)
     ///
     /// Example of a user function:
     /// ```rust
@@ -104,68 +208,292 @@ pub trait Scripter {
     ///     }
     /// }
     fn execute(func: &mut dyn FnMut(&str)) -> Result<(), super::Error> {
-        // 1 - Handle greeting
-        let mut stdin = std::io::stdin();
-        let mut stdout = std::io::stdout();
-
-        let message: Message = bincode::deserialize_from(&mut stdin)?;
-
-        if message == Message::Greeting {
-            let metadata = ScriptInfo::new(
-                Self::name(),
-                Self::script_type(),
-                Self::hooks(),
-                Self::version_requirement(),
-            );
-            bincode::serialize_into(&mut stdout, &metadata)?;
-            stdout.flush()?;
-
-            // if the script is OneShot it should exit, it will be run again but with message == [Message::Execute]
-            if matches!(Self::script_type(), ScriptType::OneShot) {
-                std::process::exit(0);
-            }
-        } else {
-            // message == Message::Execute
-            // the script will continue its execution
+        Self::execute_with_shutdown(func, &mut || {})
+    }
+    /// Same as [Scripter::execute] but runs `shutdown` when the main program asks a [ScriptType::Daemon] script to exit, then returns\
+    /// This is the place to flush the script state, the main program waits for the script to exit for a grace period before killing it (see [ScriptManager::set_shutdown_grace](crate::ScriptManager::set_shutdown_grace))
+    fn execute_with_shutdown(
+        func: &mut dyn FnMut(&str),
+        shutdown: &mut dyn FnMut(),
+    ) -> Result<(), super::Error> {
+        let hooks = Self::hooks().iter().map(|hook| hook.to_string()).collect();
+        serve::<Self>(
+            hooks,
+            HashMap::new(),
+            &mut |hook_name| {
+                func(hook_name);
+                Ok(())
+            },
+            shutdown,
+        )
+    }
+    /// Same as [Scripter::execute] but each hook is read, passed to its handler and its output is written automatically\
+    /// The script listens to the hooks that have a handler, [Scripter::hooks] is not used, and advertises their [Hook::FINGERPRINT]
+    ///
+    /// ```rust, no_run
+    /// # use rscript::{Hook, VersionReq, ScriptType};
+    /// # use rscript::scripting::{HookHandlers, Scripter};
+    /// # #[derive(serde::Serialize, serde::Deserialize)]
+    /// # struct Eval(String);
+    /// # impl Hook for Eval {
+    /// #   const NAME: &'static str = "Eval";
+    /// #   type Output = usize;
+    /// # }
+    /// struct MyScript;
+    /// impl Scripter for MyScript {
+    ///     fn name() -> &'static str {
+    ///         "MyScript"
+    ///     }
+    ///     fn script_type() -> ScriptType {
+    ///         ScriptType::Daemon
+    ///     }
+    ///     fn version_requirement() -> VersionReq {
+    ///         VersionReq::parse(">=0.1.0").expect("version requirement is correct")
+    ///     }
+    /// }
+    ///
+    /// let mut evaluated = 0;
+    /// let mut handlers = HookHandlers::default();
+    /// handlers.on(|Eval(input): Eval| {
+    ///     evaluated += 1;
+    ///     input.len()
+    /// });
+    /// MyScript::run(&mut handlers).unwrap();
+    /// ```
+    fn run(handlers: &mut HookHandlers) -> Result<(), Error> {
+        Self::run_with_shutdown(handlers, &mut || {})
+    }
+    /// Same as [Scripter::run] but runs `shutdown` when the main program asks a [ScriptType::Daemon] script to exit, see [Scripter::execute_with_shutdown]
+    fn run_with_shutdown(
+        handlers: &mut HookHandlers,
+        shutdown: &mut dyn FnMut(),
+    ) -> Result<(), Error> {
+        let hooks = handlers
+            .handlers
+            .iter()
+            .map(|(hook, _, _)| hook.to_string())
+            .collect();
+        let fingerprints = handlers
+            .handlers
+            .iter()
+            .filter_map(|(hook, fingerprint, _)| Some((hook.to_string(), (*fingerprint)?)))
+            .collect();
+        serve::<Self>(
+            hooks,
+            fingerprints,
+            &mut |hook_name| {
+                match handlers
+                    .handlers
+                    .iter_mut()
+                    .find(|(hook, _, _)| *hook == hook_name)
+                {
+                    Some((_, _, handler)) => handler(Self::codec()),
+                    // The hook can't be read without knowing its type
+                    None => Err(Error::ScriptIsNotListeningForHook),
+                }
+            },
+            shutdown,
+        )
+    }
+}
+
+/// Typed hook handlers, used by [Scripter::run]\
+/// Each handler receives its hook and returns the hook output, reading the hook and writing the output is done by [Scripter::run], so they can't go out of sync
+#[derive(Default)]
+pub struct HookHandlers<'a> {
+    handlers: Vec<(&'static str, Option<u64>, Handler<'a>)>,
+}
+
+type Handler<'a> = Box<dyn FnMut(Codec) -> Result<(), Error> + 'a>;
+
+impl<'a> HookHandlers<'a> {
+    /// Handle the hook `H` with `handler`, it replaces the previous handler of `H`
+    pub fn on<H: Hook>(
+        &mut self,
+        mut handler: impl FnMut(H) -> <H as Hook>::Output + 'a,
+    ) -> &mut Self {
+        self.handlers.retain(|(hook, _, _)| *hook != H::NAME);
+        self.handlers.push((
+            H::NAME,
+            H::FINGERPRINT,
+            Box::new(move |codec| {
+                let hook: H = frame::read(&mut *input(), codec, Kind::Hook)?;
+                write_output::<H>(codec, &handler(hook))
+            }),
+        ));
+        self
+    }
+}
+
+fn write_output<H: Hook>(codec: Codec, output: &<H as Hook>::Output) -> Result<(), Error> {
+    let mut message = frame::encode(codec, Kind::ScriptMessage, &ScriptMessage::Output)?;
+    message.extend(frame::encode(codec, Kind::Output, output)?);
+    // Written at once so an event sent from another thread can't end up between the two values
+    self::output().write_all(&message)?;
+    Ok(())
+}
+
+/// The channel the protocol is spoken on, see [ProtocolChannel](crate::ProtocolChannel)
+struct Channel {
+    input: Mutex<Box<dyn BufRead + Send>>,
+    output: Mutex<Box<dyn Write + Send>>,
+}
+
+static CHANNEL: OnceLock<Channel> = OnceLock::new();
+
+impl Channel {
+    /// Use the pipes passed by the main program, or stdin/stdout if there are none\
+    /// The environment is left untouched (changing it while other threads might read it is unsound), the processes started by the script inherit `RSCRIPT_PROTOCOL_FDS` but the [ScriptManager](crate::ScriptManager) removes it from the scripts it starts
+    fn open() -> Self {
+        #[cfg(unix)]
+        if let Ok(fds) = std::env::var(crate::PROTOCOL_FDS_VAR) {
+            return Self::pipes(&fds)
+                .expect("RSCRIPT_PROTOCOL_FDS holds two open file descriptors");
+        }
+        Self {
+            input: Mutex::new(Box::new(BufReader::new(std::io::stdin()))),
+            output: Mutex::new(Box::new(std::io::stdout())),
         }
+    }
+    #[cfg(unix)]
+    fn pipes(fds: &str) -> Option<Self> {
+        use std::os::unix::io::{FromRawFd, OwnedFd, RawFd};
+        let (input, output) = fds.split_once(',')?;
+        let open = |fd: &str| -> Option<std::fs::File> {
+            let fd: RawFd = fd.parse().ok()?;
+            // The inherited file descriptors are replaced by close-on-exec copies, so the script children don't inherit them
+            let inherited = unsafe { OwnedFd::from_raw_fd(fd) };
+            Some(inherited.try_clone().ok()?.into())
+        };
+        Some(Self {
+            input: Mutex::new(Box::new(BufReader::new(open(input)?))),
+            output: Mutex::new(Box::new(open(output)?)),
+        })
+    }
+}
 
-        // 2 - Handle Executing
-        loop {
-            // OneShot scripts handles greeting each time they are run, so [Message] is already received
-            if matches!(Self::script_type(), ScriptType::Daemon) {
-                let _message: Message = bincode::deserialize_from(&mut stdin)?;
-            }
+fn input() -> MutexGuard<'static, Box<dyn BufRead + Send>> {
+    CHANNEL.get_or_init(Channel::open).input.lock().unwrap()
+}
+
+fn output() -> MutexGuard<'static, Box<dyn Write + Send>> {
+    CHANNEL.get_or_init(Channel::open).output.lock().unwrap()
+}
+
+/// The script main loop, `func` handles the hook with the given name
+fn serve<S: Scripter + ?Sized>(
+    hooks: Box<[String]>,
+    fingerprints: HashMap<String, u64>,
+    func: &mut dyn FnMut(&str) -> Result<(), Error>,
+    shutdown: &mut dyn FnMut(),
+) -> Result<(), Error> {
+    // 1 - Handle greeting
+    let codec = S::codec();
+    // The first message is a JSON line for scripts run by an interpreter, the main program doesn't know yet which protocol they speak, and NATIVE_GREETING for compiled scripts
+    let mut line = vec![];
+    let mut reader = input();
+    if reader.fill_buf()?.first() == Some(&b'{') {
+        reader.read_until(b'\n', &mut line)?;
+    } else {
+        line.resize(NATIVE_GREETING.len(), 0);
+        reader.read_exact(&mut line)?;
+    }
+    drop(reader);
 
-            let hook_name: String = bincode::deserialize_from(&mut stdin)?;
+    if is_greeting_line(&line) || line == NATIVE_GREETING {
+        let metadata = ScriptInfo {
+            name: S::name().into(),
+            script_type: S::script_type(),
+            hooks,
+            version_requirement: S::version_requirement(),
+            fingerprints,
+        };
+        let mut output = output();
+        Codec::Bincode.write(&mut *output, &Handshake::CURRENT.with_codec(codec))?;
+        frame::write(&mut *output, codec, Kind::ScriptInfo, &metadata)?;
+        output.flush()?;
+        drop(output);
+        GREETED.open();
 
-            func(&hook_name);
-            std::io::stdout().flush()?;
+        // if the script is OneShot it should exit, it will be run again but with an execute message
+        if matches!(S::script_type(), ScriptType::OneShot) {
+            std::process::exit(0);
+        }
+    } else if line == EXECUTE_LINE.as_bytes() {
+        // the script will continue its execution
+        GREETED.open();
+    } else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "expected a greeting or an execute message",
+        )
+        .into());
+    }
 
-            if matches!(Self::script_type(), ScriptType::OneShot) {
-                // if its OneShot we exit after one execution
+    // 2 - Handle Executing
+    loop {
+        // OneShot scripts handles greeting each time they are run, so the execute message is already received
+        if matches!(S::script_type(), ScriptType::Daemon) {
+            let message: Message = frame::read(&mut *input(), codec, Kind::Message)?;
+            if message == Message::Shutdown {
+                shutdown();
                 return Ok(());
             }
         }
+
+        let hook_name: String = frame::read(&mut *input(), codec, Kind::HookName)?;
+
+        func(&hook_name)?;
+        output().flush()?;
+
+        if matches!(S::script_type(), ScriptType::OneShot) {
+            // if its OneShot we exit after one execution
+            return Ok(());
+        }
     }
 }
 
-/// A [ScriptType::DynamicLib] script needs to export a static instance of this struct named [DynamicScript::NAME]
+/// Blocks [Scripter::send_event] until the greeting is answered, so events are not mixed with [ScriptInfo]
+static GREETED: Gate = Gate {
+    open: Mutex::new(false),
+    condvar: Condvar::new(),
+};
+
+struct Gate {
+    open: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl Gate {
+    fn open(&self) {
+        *self.open.lock().unwrap() = true;
+        self.condvar.notify_all();
+    }
+    fn wait(&self) {
+        let open = self.open.lock().unwrap();
+        drop(self.condvar.wait_while(open, |open| !*open).unwrap());
+    }
+}
+
+/// A [ScriptType::DynamicLib] script needs to export a static instance of this struct named [DynamicScript::NAME], created with [DynamicScript::new]
 /// ```rs
 /// // In a script file
 /// #[no_mangle]
-/// pub static SCRIPT: DynamicScript = DynamicScript { script_info: .., script: .. };
+/// pub static SCRIPT: DynamicScript = DynamicScript::new(script_info, script);
 /// ```
 ///
 ///
-/// `DynamicScript` contains also methods for writing scripts: [DynamicScript::read], [DynamicScript::write]
+/// `DynamicScript` contains also methods for writing scripts: [DynamicScript::read], [DynamicScript::write], [DynamicScript::write_with], [DynamicScript::call], [DynamicScript::catch_panic]
 #[repr(C)]
 pub struct DynamicScript {
-    /// A function that returns `ScriptInfo` serialized as `FFiData`\
+    // Must stay the first field, so it can be checked whatever the rest of the layout is
+    pub(crate) handshake: Handshake,
+    /// A function that returns `ScriptInfo` serialized as `FFiData`, wrapped with [DynamicScript::catch_panic]\
     /// *fn() -> ScriptInfo*
-    pub script_info: extern "C" fn() -> FFiData,
-    /// A function that accepts a hook name (casted to `FFiStr`) and the hook itself (serialized as `FFiData`)  and returns the hook output (serialized as `FFiData`)\
-    /// *fn<H: Hook>(hook: &str (H::Name), data: H) -> <H as Hook>::Output>*
-    pub script: extern "C" fn(FFiStr, FFiData) -> FFiData,
+    pub script_info: extern "C" fn() -> FFiResult,
+    /// A function that accepts a hook name (casted to `FFiStr`), the hook itself (serialized as `FFiData`) and the host functions (see [DynamicScript::call]) and returns the hook output (serialized as `FFiData`), wrapped with [DynamicScript::catch_panic]\
+    /// *fn<H: Hook>(hook: &str (H::Name), data: H, host: &FFiHost) -> <H as Hook>::Output>*
+    pub script: extern "C" fn(FFiStr, FFiData, &FFiHost) -> FFiResult,
 }
 impl DynamicScript {
     /// ```rust
@@ -173,17 +501,142 @@ impl DynamicScript {
     /// ```
     pub const NAME: &'static [u8] = b"SCRIPT";
 
-    /// Read a hook from an FFiData
+    /// Create the script export, it records the rscript protocol version the script is built against (see [PROTOCOL_VERSION](crate::PROTOCOL_VERSION)) and its [BuildInfo]
+    pub const fn new(
+        script_info: extern "C" fn() -> FFiResult,
+        script: extern "C" fn(FFiStr, FFiData, &FFiHost) -> FFiResult,
+    ) -> Self {
+        Self {
+            handshake: Handshake::CURRENT,
+            script_info,
+            script,
+        }
+    }
+    /// The rscript and rustc versions the script is built with, the main program refuses the script if they differ from its own
+    pub fn build_info(&self) -> BuildInfo {
+        self.handshake.build_info()
+    }
+    /// Ask the main program to send the hooks and the host functions outputs encoded with `codec` instead of [Codec::Bincode]
+    pub const fn with_codec(self, codec: Codec) -> Self {
+        Self {
+            handshake: self.handshake.with_codec(codec),
+            ..self
+        }
+    }
+
+    /// Read a hook from an FFiData\
+    /// It panics if the data is not an `H`
     pub fn read<H: Hook>(hook: FFiData) -> H {
         hook.deserialize().unwrap()
     }
-    /// Write a value to an FFiData
+    /// Write a value to an FFiData with [Codec::Bincode]\
     /// It takes the hook as a type argument in-order to make sure that the output provided correspond to the hook's expected output
     pub fn write<H: Hook>(output: &<H as Hook>::Output) -> FFiData {
-        FFiData::serialize_from(output).unwrap()
+        Self::write_with::<H>(Codec::Bincode, output)
+    }
+    /// Same as [DynamicScript::write] but encodes the output with `codec`, `FFiData` records its codec so the main program can decode it whatever the script codec is
+    pub fn write_with<H: Hook>(codec: Codec, output: &<H as Hook>::Output) -> FFiData {
+        FFiData::serialize_from(codec, output).unwrap()
+    }
+    /// Call a function provided by the main program, `host` is the one received by [DynamicScript::script]\
+    /// An error is returned if the main program didn't register a handler for this function
+    pub fn call<F: HostFunction>(
+        host: &FFiHost,
+        function: &F,
+    ) -> Result<<F as HostFunction>::Output, Error> {
+        // The main program sets the script codec, the script is necessarily built with it
+        let codec = Codec::from_id(host.codec).unwrap_or_default();
+        let reply = (host.call)(
+            host.ctx,
+            FFiStr::new(F::NAME),
+            FFiData::serialize_from(codec, function)?,
+        );
+        let reply: Result<Vec<u8>, String> = reply.deserialize()?;
+        codec.decode(&reply.map_err(Error::HostFunction)?)
+    }
+    /// Run the body of [DynamicScript::script_info] or [DynamicScript::script], a panic is caught and reported to the main program as [Error::ScriptPanicked] instead of unwinding across the C ABI (which aborts it)\
+    /// This only works if the script is compiled with `panic = "unwind"` (the default)
+    ///
+    /// ```rust
+    /// # use rscript::{Hook, scripting::{DynamicScript, FFiData, FFiHost, FFiResult, FFiStr}};
+    /// # #[derive(serde::Serialize, serde::Deserialize)]
+    /// # struct Eval(String);
+    /// # impl Hook for Eval {
+    /// #   const NAME: &'static str = "Eval";
+    /// #   type Output = String;
+    /// # }
+    /// pub extern "C" fn script(name: FFiStr, hook: FFiData, _host: &FFiHost) -> FFiResult {
+    ///     DynamicScript::catch_panic(|| match name.as_str() {
+    ///         Eval::NAME => {
+    ///             let hook: Eval = DynamicScript::read(hook);
+    ///             DynamicScript::write::<Eval>(&hook.0)
+    ///         }
+    ///         _ => unreachable!(),
+    ///     })
+    /// }
+    /// ```
+    pub fn catch_panic(script: impl FnOnce() -> FFiData) -> FFiResult {
+        match std::panic::catch_unwind(AssertUnwindSafe(script)) {
+            Ok(data) => FFiResult {
+                status: FFiStatus::Ok,
+                data,
+            },
+            Err(payload) => FFiResult {
+                status: FFiStatus::Panicked,
+                data: FFiData::serialize_from(Codec::Bincode, &panic_message(&*payload))
+                    .expect("String is always serialize-able"),
+            },
+        }
+    }
+}
+
+/// The message of a caught panic
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic payload".to_string()
+    }
+}
+
+/// `FFiResult` is returned by [ScriptType::DynamicLib] scripts, it's created with [DynamicScript::catch_panic]\
+/// It holds either the script output or the message of the panic that happened while producing it
+#[repr(C)]
+pub struct FFiResult {
+    status: FFiStatus,
+    data: FFiData,
+}
+impl FFiResult {
+    /// The output, or the panic message as an error
+    pub(crate) fn into_result(self) -> Result<FFiData, String> {
+        match self.status {
+            FFiStatus::Ok => Ok(self.data),
+            FFiStatus::Panicked => Err(self
+                .data
+                .deserialize()
+                .unwrap_or_else(|_| "unknown panic payload".to_string())),
+        }
     }
 }
 
+#[repr(C)]
+enum FFiStatus {
+    Ok,
+    Panicked,
+}
+
+/// `FFiHost` gives [ScriptType::DynamicLib] scripts access to the main program functions, see [DynamicScript::call]\
+/// It's only valid during the [DynamicScript::script] call it's passed to
+#[repr(C)]
+pub struct FFiHost {
+    pub(crate) ctx: *const c_void,
+    pub(crate) call: extern "C" fn(*const c_void, FFiStr, FFiData) -> FFiData,
+    // Id of the script codec
+    pub(crate) codec: u8,
+}
+
 #[repr(C)]
 /// `FFiStr` is used to send the hook name to [ScriptType::DynamicLib] script
 pub struct FFiStr {
@@ -204,27 +657,46 @@ impl FFiStr {
     }
 }
 
-/// `FFiData` is used for communicating arbitrary data between [ScriptType::DynamicLib] scripts and the main program
+/// `FFiData` is used for communicating arbitrary data between [ScriptType::DynamicLib] scripts and the main program\
+/// It records the [Codec] it's encoded with
 #[repr(C)]
 pub struct FFiData {
     ptr: *mut u8,
     len: usize,
     cap: usize,
+    codec: u8,
 }
 impl FFiData {
     /// Crate a new FFiData from any serialize-able data
-    pub(crate) fn serialize_from<D: Serialize>(data: &D) -> Result<Self, bincode::Error> {
-        let data = bincode::serialize(data)?;
+    pub(crate) fn serialize_from<D: Serialize>(codec: Codec, data: &D) -> Result<Self, Error> {
+        let data = codec.encode(data)?;
         let mut vec = std::mem::ManuallyDrop::new(data);
         let ptr = vec.as_mut_ptr();
         let len = vec.len();
         let cap = vec.capacity();
-        Ok(FFiData { ptr, len, cap })
+        Ok(FFiData {
+            ptr,
+            len,
+            cap,
+            codec: codec.id(),
+        })
     }
     /// De-serialize into a concrete type
-    pub(crate) fn deserialize<D: DeserializeOwned>(&self) -> Result<D, bincode::Error> {
-        let data: &[u8] = unsafe { &*slice_from_raw_parts(self.ptr, self.len) };
-        bincode::deserialize(data)
+    pub(crate) fn deserialize<D: DeserializeOwned>(&self) -> Result<D, Error> {
+        let codec = self.codec().map_err(|codec| {
+            Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("data encoded with the unsupported {} codec", codec),
+            ))
+        })?;
+        codec.decode(self.as_bytes())
+    }
+    /// The codec of the data, or the codec name if it's not enabled
+    pub(crate) fn codec(&self) -> Result<Codec, String> {
+        Codec::from_id(self.codec)
+    }
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        unsafe { &*slice_from_raw_parts(self.ptr, self.len) }
     }
 }
 impl Drop for FFiData {
@@ -0,0 +1,92 @@
+//! Stderr of process scripts routed through the main program, see [ScriptStderr](crate::ScriptStderr)
+
+use crate::ScriptStderr;
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader, Read, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+};
+
+/// Receives the stderr lines of every process of a script, so they are kept across OneShot triggers and restarts
+#[derive(Debug, Clone)]
+pub(crate) struct StderrSink(Arc<Mutex<Captured>>);
+
+#[derive(Debug)]
+struct Captured {
+    // The file name until the script sends its name
+    name: String,
+    lines: VecDeque<String>,
+}
+
+impl StderrSink {
+    pub(crate) fn new(path: &Path) -> Self {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Self(Arc::new(Mutex::new(Captured {
+            name,
+            lines: VecDeque::new(),
+        })))
+    }
+    /// Tag the next lines with the script name
+    pub(crate) fn set_name(&self, name: &str) {
+        self.0.lock().unwrap().name = name.to_string();
+    }
+    /// The lines kept with [ScriptStderr::Capture], oldest first
+    pub(crate) fn lines(&self) -> Vec<String> {
+        self.0.lock().unwrap().lines.iter().cloned().collect()
+    }
+    /// Route a line the script wrote according to `stderr`
+    pub(crate) fn push(&self, stderr: ScriptStderr, line: &[u8]) {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim_end_matches(['\n', '\r']);
+        let mut captured = self.0.lock().unwrap();
+        match stderr {
+            ScriptStderr::Capture { lines } => {
+                captured.lines.push_back(line.to_string());
+                while captured.lines.len() > lines {
+                    captured.lines.pop_front();
+                }
+            }
+            #[cfg(feature = "log")]
+            ScriptStderr::Log => {
+                log::info!(target: "rscript::stderr", "{}: {}", captured.name, line)
+            }
+            // Only piped to watch for allocation failures when the script has a memory limit
+            ScriptStderr::Inherit => {
+                let _ = writeln!(std::io::stderr(), "{}", line);
+            }
+            ScriptStderr::Discard => {}
+        }
+    }
+    /// Read the piped stderr of a process in a background thread until the process exits\
+    /// The thread returns whether the script reported an allocation failure
+    pub(crate) fn capture<R: Read + Send + 'static>(
+        &self,
+        stderr: R,
+        mode: ScriptStderr,
+    ) -> JoinHandle<bool> {
+        let sink = self.clone();
+        std::thread::spawn(move || {
+            let mut stderr = BufReader::new(stderr);
+            let mut line = Vec::new();
+            let mut allocation_failed = false;
+            while matches!(stderr.read_until(b'\n', &mut line), Ok(n) if n > 0) {
+                allocation_failed |= is_allocation_failure(&line);
+                sink.push(mode, &line);
+                line.clear();
+            }
+            allocation_failed
+        })
+    }
+}
+
+/// Whether `line` is the message the Rust standard library prints before aborting when an allocation fails
+fn is_allocation_failure(line: &[u8]) -> bool {
+    let line = String::from_utf8_lossy(line);
+    let line = line.trim_end();
+    line.starts_with("memory allocation of ") && line.ends_with(" bytes failed")
+}
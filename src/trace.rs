@@ -0,0 +1,164 @@
+//! Diagnostics emitted with the [tracing](https://docs.rs/tracing) crate, enabled with the `tracing` feature\
+//! Without it the spans are empty and cost nothing
+//!
+//! - `discovery` (`info`): looking for scripts in a folder, with the folder `dir`
+//! - `greeting` (`info`): starting a process script until it's ready, with its `path`, then its `script` name and `script_type`
+//! - `dylib_load` (`info`): loading a dynamic library script, with its `path`, then its `script` name and `script_type`
+//! - `trigger` (`debug`): triggering a hook on a script, with the `script` name and `script_type`, the `hook` name, and the sizes of the hook and output as they are sent (`hook_bytes` and `output_bytes`, whole lines for JSON-lines scripts)
+//!
+//! Every span records its `duration_us` when it ends, and the `error` if it failed (which is also emitted as a `warn` event)
+
+use crate::{Error, ScriptInfo};
+#[cfg(feature = "tokio")]
+use std::future::Future;
+use std::path::Path;
+#[cfg(feature = "tracing")]
+use std::time::Instant;
+#[cfg(feature = "tracing")]
+use tracing::field::Empty;
+
+/// A span of the `tracing` feature, it records its duration when dropped
+pub(crate) struct Span {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    #[cfg(feature = "tracing")]
+    start: Instant,
+}
+
+#[cfg(feature = "tracing")]
+impl Span {
+    fn new(span: tracing::Span) -> Self {
+        Self {
+            span,
+            start: Instant::now(),
+        }
+    }
+    /// Run `f` inside the span, and record its error
+    pub(crate) fn run<T>(&self, f: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+        let result = self.span.in_scope(f);
+        self.record_result(&result);
+        result
+    }
+    /// Same as [Span::run] for a future
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn run_async<T>(
+        &self,
+        future: impl Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        let result = tracing::Instrument::instrument(future, self.span.clone()).await;
+        self.record_result(&result);
+        result
+    }
+    pub(crate) fn record_script(&self, info: &ScriptInfo) {
+        self.span.record("script", info.name.as_str());
+        self.span
+            .record("script_type", tracing::field::debug(&info.script_type));
+    }
+    fn record_result<T>(&self, result: &Result<T, Error>) {
+        if let Err(error) = result {
+            self.span.record("error", tracing::field::display(error));
+            self.span
+                .in_scope(|| tracing::warn!(error = %error, "failed"));
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Drop for Span {
+    fn drop(&mut self) {
+        self.span
+            .record("duration_us", self.start.elapsed().as_micros() as u64);
+    }
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn discovery(dir: &Path) -> Span {
+    Span::new(tracing::info_span!(
+        "discovery",
+        dir = %dir.display(),
+        duration_us = Empty,
+        error = Empty
+    ))
+}
+#[cfg(feature = "tracing")]
+pub(crate) fn greeting(path: &Path) -> Span {
+    Span::new(tracing::info_span!(
+        "greeting",
+        path = %path.display(),
+        script = Empty,
+        script_type = Empty,
+        duration_us = Empty,
+        error = Empty
+    ))
+}
+#[cfg(feature = "tracing")]
+pub(crate) fn dylib_load(path: &Path) -> Span {
+    Span::new(tracing::info_span!(
+        "dylib_load",
+        path = %path.display(),
+        script = Empty,
+        script_type = Empty,
+        duration_us = Empty,
+        error = Empty
+    ))
+}
+#[cfg(feature = "tracing")]
+pub(crate) fn trigger(info: &ScriptInfo, hook: &str) -> Span {
+    Span::new(tracing::debug_span!(
+        "trigger",
+        script = info.name.as_str(),
+        script_type = ?info.script_type,
+        hook,
+        hook_bytes = Empty,
+        output_bytes = Empty,
+        duration_us = Empty,
+        error = Empty
+    ))
+}
+
+#[cfg(not(feature = "tracing"))]
+impl Span {
+    pub(crate) fn run<T>(&self, f: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+        f()
+    }
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn run_async<T>(
+        &self,
+        future: impl Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        future.await
+    }
+    pub(crate) fn record_script(&self, _info: &ScriptInfo) {}
+}
+
+/// Record the size of the encoded hook on the `trigger` span being run, the buffer sent to the script is measured so nothing is encoded twice
+#[cfg(feature = "tracing")]
+pub(crate) fn hook_bytes(len: usize) {
+    tracing::Span::current().record("hook_bytes", len);
+}
+/// Same as [hook_bytes] for the encoded output received from the script
+#[cfg(feature = "tracing")]
+pub(crate) fn output_bytes(len: usize) {
+    tracing::Span::current().record("output_bytes", len);
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn discovery(_dir: &Path) -> Span {
+    Span {}
+}
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn greeting(_path: &Path) -> Span {
+    Span {}
+}
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn dylib_load(_path: &Path) -> Span {
+    Span {}
+}
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn trigger(_info: &ScriptInfo, _hook: &str) -> Span {
+    Span {}
+}
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn hook_bytes(_len: usize) {}
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn output_bytes(_len: usize) {}
@@ -0,0 +1,38 @@
+//! The hooks and host functions shared by the library and the tests, like an api crate
+
+use rscript::{Hook, HostFunction};
+use serde::{Deserialize, Serialize};
+
+/// Answers with its input
+#[derive(Serialize, Deserialize)]
+pub struct Eval(pub String);
+impl Hook for Eval {
+    const NAME: &'static str = "Eval";
+    type Output = String;
+    // Set by hand, `#[derive(Hook)]` would compute it
+    const FINGERPRINT: Option<u64> = Some(1);
+}
+
+/// The script panics while handling it
+#[derive(Serialize, Deserialize)]
+pub struct Panic;
+impl Hook for Panic {
+    const NAME: &'static str = "Panic";
+    type Output = ();
+}
+
+/// Answers with the output of [Upper], or the error of the call
+#[derive(Serialize, Deserialize)]
+pub struct CallHost(pub String);
+impl Hook for CallHost {
+    const NAME: &'static str = "CallHost";
+    type Output = String;
+}
+
+/// Provided by the main program
+#[derive(Serialize, Deserialize)]
+pub struct Upper(pub String);
+impl HostFunction for Upper {
+    const NAME: &'static str = "Upper";
+    type Output = String;
+}
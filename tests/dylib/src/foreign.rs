@@ -0,0 +1,43 @@
+//! The layout of `DynamicScript`, with the ABI tag of a library built with other rscript and rustc versions
+
+use rscript::scripting::{FFiData, FFiHost, FFiResult, FFiStr};
+
+#[repr(C)]
+pub struct ForeignScript {
+    magic: u32,
+    protocol_version: u32,
+    codec: u8,
+    rscript_version: [u8; 32],
+    rustc_version: [u8; 32],
+    script_info: extern "C" fn() -> FFiResult,
+    script: extern "C" fn(FFiStr, FFiData, &FFiHost) -> FFiResult,
+}
+
+#[no_mangle]
+pub static SCRIPT: ForeignScript = ForeignScript {
+    magic: u32::from_le_bytes(*b"RSCR"),
+    protocol_version: rscript::PROTOCOL_VERSION,
+    codec: 0,
+    rscript_version: tag(b"0.0.0-foreign"),
+    rustc_version: tag(b"1.0.0 foreign"),
+    script_info,
+    script,
+};
+
+// The library is refused before they are called
+extern "C" fn script_info() -> FFiResult {
+    unreachable!()
+}
+extern "C" fn script(_name: FFiStr, _hook: FFiData, _host: &FFiHost) -> FFiResult {
+    unreachable!()
+}
+
+const fn tag(version: &[u8]) -> [u8; 32] {
+    let mut tag = [0; 32];
+    let mut i = 0;
+    while i < version.len() {
+        tag[i] = version[i];
+        i += 1;
+    }
+    tag
+}
@@ -0,0 +1,22 @@
+//! The script written with `#[dynamic_script]`
+
+use crate::api::{CallHost, Eval, Panic, Upper};
+use rscript::{
+    dynamic_script,
+    scripting::{DynamicScript, FFiHost},
+};
+
+pub struct Generated;
+
+#[dynamic_script(version_requirement = ">=0.1.0")]
+impl Generated {
+    fn eval(Eval(input): Eval) -> String {
+        input
+    }
+    fn panic(_hook: Panic) {
+        panic!("asked to panic")
+    }
+    fn call_host(CallHost(input): CallHost, host: &FFiHost) -> String {
+        DynamicScript::call(host, &Upper(input)).unwrap_or_else(|e| e.to_string())
+    }
+}
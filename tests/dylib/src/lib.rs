@@ -0,0 +1,12 @@
+//! A DynamicLib script for tests/dynamic.rs, the features select how it's written:
+//! - none: by hand with [DynamicScript::catch_panic](rscript::scripting::DynamicScript::catch_panic)
+//! - `macros`: with `#[dynamic_script]`
+//! - `foreign`: with the ABI tag of other rscript and rustc versions, it takes precedence
+
+pub mod api;
+#[cfg(feature = "foreign")]
+mod foreign;
+#[cfg(all(feature = "macros", not(feature = "foreign")))]
+mod generated;
+#[cfg(not(any(feature = "macros", feature = "foreign")))]
+mod manual;
@@ -0,0 +1,38 @@
+//! The script written by hand
+
+use crate::api::{CallHost, Eval, Panic, Upper};
+use rscript::{
+    scripting::{DynamicScript, FFiData, FFiHost, FFiResult, FFiStr},
+    Hook, ScriptInfo, ScriptType, VersionReq,
+};
+
+#[no_mangle]
+pub static SCRIPT: DynamicScript = DynamicScript::new(script_info, script);
+
+extern "C" fn script_info() -> FFiResult {
+    DynamicScript::catch_panic(|| {
+        ScriptInfo::new(
+            "manual",
+            ScriptType::DynamicLib,
+            &[Eval::NAME, Panic::NAME, CallHost::NAME],
+            VersionReq::parse(">=0.1.0").unwrap(),
+        )
+        .into_ffi_data()
+    })
+}
+
+extern "C" fn script(name: FFiStr, hook: FFiData, host: &FFiHost) -> FFiResult {
+    DynamicScript::catch_panic(|| match name.as_str() {
+        Eval::NAME => {
+            let Eval(input) = DynamicScript::read(hook);
+            DynamicScript::write::<Eval>(&input)
+        }
+        Panic::NAME => panic!("asked to panic"),
+        CallHost::NAME => {
+            let CallHost(input) = DynamicScript::read(hook);
+            let output = DynamicScript::call(host, &Upper(input)).unwrap_or_else(|e| e.to_string());
+            DynamicScript::write::<CallHost>(&output)
+        }
+        _ => unreachable!(),
+    })
+}
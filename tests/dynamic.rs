@@ -0,0 +1,286 @@
+//! DynamicLib scripts loaded from a real library, built from `tests/dylib` with the features each case needs
+
+#[path = "dylib/src/api.rs"]
+mod api;
+#[cfg(feature = "tracing")]
+#[path = "scripter/recorder.rs"]
+mod recorder;
+
+use api::{CallHost, Eval, Panic, Upper};
+use rscript::{
+    BuildInfo, Error, Hook, MismatchedHook, ReloadEvent, ScriptManager, ScriptType, Version,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    env::consts::{DLL_PREFIX, DLL_SUFFIX},
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Mutex,
+};
+
+/// The file name of the library
+fn library_file() -> String {
+    format!("{}rscript_test_dylib{}", DLL_PREFIX, DLL_SUFFIX)
+}
+
+/// Build the library with `features`, returns its path
+fn build(features: &[&str]) -> PathBuf {
+    // Cargo takes a lock on the target dir anyway, this keeps the cases from racing on the same variant
+    static BUILD: Mutex<()> = Mutex::new(());
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join("dylib")
+        .join(features.join("-"));
+    {
+        let _guard = BUILD.lock().unwrap_or_else(|e| e.into_inner());
+        let output = Command::new(env!("CARGO"))
+            .arg("build")
+            .arg("--manifest-path")
+            .arg(manifest_dir.join("tests/dylib/Cargo.toml"))
+            .arg("--target-dir")
+            .arg(&target_dir)
+            .arg("--features")
+            .arg(features.join(","))
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "failed to build tests/dylib:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    target_dir.join("debug").join(library_file())
+}
+
+/// Build the library with `features` and copy it alone in a folder for `case`
+fn library_dir(case: &str, features: &[&str]) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join("dynamic")
+        .join(case);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::copy(build(features), dir.join(library_file())).unwrap();
+    dir
+}
+
+fn load_with(
+    case: &str,
+    features: &[&str],
+    configure: impl FnOnce(&mut ScriptManager),
+) -> ScriptManager {
+    let mut sm = ScriptManager::default();
+    configure(&mut sm);
+    unsafe {
+        sm.add_dynamic_scripts_by_path(library_dir(case, features), Version::new(0, 1, 0))
+            .unwrap();
+    }
+    sm
+}
+
+fn load(case: &str) -> ScriptManager {
+    load_with(case, &[], |_| ())
+}
+
+#[test]
+fn hooks() {
+    let mut sm = load("hooks");
+    let script = &sm.scripts()[0];
+    assert_eq!(script.metadata().name, "manual");
+    assert!(matches!(
+        script.metadata().script_type,
+        ScriptType::DynamicLib
+    ));
+    assert_eq!(sm.trigger(Eval("a".into())).next().unwrap().unwrap(), "a");
+}
+
+/// The panic is caught in the library and reported, the main program and the script keep working
+#[test]
+fn panics() {
+    let mut sm = load("panics");
+    match sm.trigger(Panic).next().unwrap() {
+        Err(Error::ScriptPanicked { name, message }) => {
+            assert_eq!(name, "manual");
+            assert_eq!(message, "asked to panic");
+        }
+        other => panic!("expected ScriptPanicked, got {:?}", other),
+    }
+    assert_eq!(sm.trigger(Eval("a".into())).next().unwrap().unwrap(), "a");
+}
+
+/// A host function that panics or is not registered is an error for the script
+#[test]
+fn host_calls() {
+    let mut sm = load("host_calls");
+    assert_eq!(
+        sm.trigger(CallHost("a".into())).next().unwrap().unwrap(),
+        "Host function call failed: host function Upper is not registered"
+    );
+    sm.register_host_function(|Upper(input)| input.to_uppercase());
+    assert_eq!(
+        sm.trigger(CallHost("a".into())).next().unwrap().unwrap(),
+        "A"
+    );
+    sm.register_host_function(|Upper(_)| -> String { panic!("no upper case today") });
+    assert_eq!(
+        sm.trigger(CallHost("a".into())).next().unwrap().unwrap(),
+        "Host function call failed: host function Upper panicked: no upper case today"
+    );
+}
+
+/// The ABI tag of the library is checked before anything else is read from it
+#[test]
+fn foreign_build() {
+    let mut sm = ScriptManager::default();
+    let dir = library_dir("foreign_build", &["foreign"]);
+    match unsafe { sm.add_dynamic_scripts_by_path(&dir, Version::new(0, 1, 0)) } {
+        Err(Error::ProtocolMismatch {
+            script_build: Some(build),
+            ..
+        }) => {
+            assert_eq!(
+                build,
+                BuildInfo {
+                    rscript_version: "0.0.0-foreign".into(),
+                    rustc_version: "1.0.0 foreign".into(),
+                }
+            );
+            assert_ne!(build, BuildInfo::current());
+        }
+        other => panic!("expected ProtocolMismatch, got {:?}", other),
+    }
+    assert!(sm.scripts().is_empty());
+}
+
+/// The script written with `#[dynamic_script]` listens to its handled hooks and advertises their fingerprints
+#[test]
+fn generated() {
+    let mut sm = load_with("generated", &["macros"], |sm| {
+        sm.register_host_function(|Upper(input)| input.to_uppercase())
+    });
+    let metadata = sm.scripts()[0].metadata();
+    assert_eq!(metadata.name, "Generated");
+    assert_eq!(*metadata.hooks, ["Eval", "Panic", "CallHost"]);
+    assert_eq!(metadata.fingerprints.get("Eval"), Some(&1));
+    assert_eq!(metadata.fingerprints.len(), 1);
+    assert_eq!(sm.trigger(Eval("a".into())).next().unwrap().unwrap(), "a");
+    match sm.trigger(Panic).next().unwrap() {
+        Err(Error::ScriptPanicked { name, message }) => {
+            assert_eq!(name, "Generated");
+            assert_eq!(message, "asked to panic");
+        }
+        other => panic!("expected ScriptPanicked, got {:?}", other),
+    }
+    assert_eq!(
+        sm.trigger(CallHost("a".into())).next().unwrap().unwrap(),
+        "A"
+    );
+}
+
+/// The main program definition of [Eval], different from the one the library is built with
+#[derive(Serialize, Deserialize)]
+struct OtherEval(String);
+impl Hook for OtherEval {
+    const NAME: &'static str = "Eval";
+    type Output = String;
+    const FINGERPRINT: Option<u64> = Some(2);
+}
+
+#[test]
+fn generated_mismatched_hook() {
+    let mut sm = load_with("generated_mismatched_hook", &["macros"], |sm| {
+        sm.register_hook::<OtherEval>()
+    });
+    assert_eq!(
+        sm.scripts()[0].mismatched_hooks(),
+        [MismatchedHook {
+            hook: "Eval".into(),
+            program_fingerprint: 2,
+            script_fingerprint: 1,
+        }]
+    );
+    // The hook is disabled for the script
+    let output = sm.trigger(OtherEval("a".into())).next().unwrap();
+    assert!(matches!(output, Err(Error::HookMismatch { .. })));
+}
+
+/// Replace the library in `dir` with the one built with `features`, it's renamed over the loaded one since writing to it would corrupt its mapping
+fn replace_library(dir: &Path, features: &[&str]) {
+    let staged = dir.with_extension("staged");
+    fs::copy(build(features), &staged).unwrap();
+    fs::rename(&staged, dir.join(library_file())).unwrap();
+}
+
+/// The modified library is loaded from a copy, since the loader would return the old library for the same path
+#[test]
+fn reload() {
+    let dir = library_dir("reload", &[]);
+    let path = dir.join(library_file());
+    let mut sm = ScriptManager::default();
+    unsafe {
+        sm.add_dynamic_scripts_by_path(&dir, Version::new(0, 1, 0))
+            .unwrap();
+    }
+    let id = sm.scripts()[0].id();
+
+    replace_library(&dir, &["macros"]);
+    let events = unsafe { sm.reload_dynamic() }.unwrap();
+    assert!(matches!(&events[..], [ReloadEvent::Reloaded(reloaded)] if *reloaded == path));
+    let script = &sm.scripts()[0];
+    assert_eq!(script.metadata().name, "Generated");
+    assert_eq!((script.id(), script.path()), (id, &*path));
+
+    // The new library is refused, the previous one keeps running
+    replace_library(&dir, &["foreign"]);
+    let events = unsafe { sm.reload_dynamic() }.unwrap();
+    assert!(matches!(
+        &events[..],
+        [ReloadEvent::Failed(failed, Error::ProtocolMismatch { .. })] if *failed == path
+    ));
+    assert_eq!(sm.scripts()[0].metadata().name, "Generated");
+    assert_eq!(sm.trigger(Eval("a".into())).next().unwrap().unwrap(), "a");
+}
+
+/// The library is loaded in a `dylib_load` span, a caught panic fails the `trigger` span
+#[cfg(feature = "tracing")]
+#[test]
+fn traces() {
+    let dir = library_dir("traces", &[]);
+    let recorder = recorder::Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let mut sm = ScriptManager::default();
+        unsafe {
+            sm.add_dynamic_scripts_by_path(&dir, Version::new(0, 1, 0))
+                .unwrap();
+        }
+        assert_eq!(
+            sm.trigger(Eval("abc".into())).next().unwrap().unwrap(),
+            "abc"
+        );
+        sm.trigger(Panic).next().unwrap().unwrap_err();
+    });
+
+    let load = &recorder.spans("dylib_load")[0];
+    let path = dir.join(library_file()).display().to_string();
+    assert_eq!(load.field("path"), Some(&*path));
+    assert_eq!(load.field("script"), Some("manual"));
+    assert_eq!(load.field("script_type"), Some("DynamicLib"));
+    assert!(load.field("duration_us").is_some());
+
+    let triggers = recorder.spans("trigger");
+    let bytes = bincode::serialize("abc").unwrap().len().to_string();
+    assert_eq!(triggers[0].field("hook"), Some("Eval"));
+    assert_eq!(triggers[0].field("hook_bytes"), Some(&*bytes));
+    assert_eq!(triggers[0].field("output_bytes"), Some(&*bytes));
+    assert_eq!(triggers[0].field("error"), None);
+    assert_eq!(triggers[1].field("hook"), Some("Panic"));
+    let error = triggers[1].field("error").unwrap();
+    assert!(error.contains("asked to panic"), "{}", error);
+    let events = recorder.events();
+    assert!(matches!(
+        &events[..],
+        [event] if event.span == Some("trigger")
+            && event.level == tracing::Level::WARN
+            && event.fields.contains(&("error", error.to_string()))
+    ));
+}
@@ -0,0 +1,213 @@
+//! Conformance suite of the JSON-lines protocol (see JSON_LINES.md)\
+//! Each folder of `tests/json_lines` holds a reference `sh` script exercising a part of the protocol\
+//! The ones exercising how scripts are spawned are run by `tests/scripter`, next to their `Scripter` counterparts
+#![cfg(unix)]
+
+use rscript::{
+    Codec, Error, Event, Hook, HostFunction, RestartPolicy, ScriptManager, ScriptType, Version,
+    JSON_LINES_VERSION,
+};
+use serde::{Deserialize, Serialize};
+use std::{path::PathBuf, time::Duration};
+
+#[derive(Serialize, Deserialize)]
+struct Eval(String);
+impl Hook for Eval {
+    const NAME: &'static str = "Eval";
+    type Output = String;
+}
+
+#[derive(Serialize, Deserialize)]
+struct Ping;
+impl Hook for Ping {
+    const NAME: &'static str = "Ping";
+    type Output = ();
+}
+
+#[derive(Serialize, Deserialize)]
+struct AskHost;
+impl Hook for AskHost {
+    const NAME: &'static str = "AskHost";
+    type Output = Result<i64, String>;
+}
+
+#[derive(Serialize, Deserialize)]
+struct Double(i64);
+impl HostFunction for Double {
+    const NAME: &'static str = "Double";
+    type Output = i64;
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Greeted(String);
+impl Event for Greeted {
+    const NAME: &'static str = "Greeted";
+}
+
+fn scripts(case: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("json_lines")
+        .join(case)
+}
+
+fn load(case: &str) -> ScriptManager {
+    let mut sm = ScriptManager::default();
+    sm.add_scripts_by_path(scripts(case), Version::new(0, 1, 0))
+        .unwrap();
+    sm
+}
+
+fn greeted(sm: &mut ScriptManager) -> Vec<Greeted> {
+    sm.poll_events()
+        .map(|event| event.unwrap().get::<Greeted>().unwrap().unwrap())
+        .collect()
+}
+
+#[test]
+fn spec_documents_the_current_protocol_version() {
+    // The reference scripts and JSON_LINES.md hardcode the protocol version
+    assert_eq!(JSON_LINES_VERSION, 4);
+}
+
+#[test]
+fn greeting() {
+    let sm = load("daemon");
+    let script = &sm.scripts()[0];
+    assert_eq!(script.metadata().name, "daemon");
+    assert!(matches!(script.metadata().script_type, ScriptType::Daemon));
+    assert_eq!(&*script.metadata().hooks, ["Eval", "Ping"]);
+    assert_eq!(script.codec(), Codec::Json);
+}
+
+#[test]
+fn daemon_keeps_its_state() {
+    let mut sm = load("daemon");
+    assert_eq!(sm.trigger(Eval("a".into())).next().unwrap().unwrap(), "a:1");
+    assert_eq!(sm.trigger(Eval("b".into())).next().unwrap().unwrap(), "b:2");
+}
+
+#[test]
+fn output_defaults_to_null() {
+    let mut sm = load("daemon");
+    sm.trigger(Ping).next().unwrap().unwrap();
+}
+
+#[test]
+fn events() {
+    let mut sm = load("daemon");
+    // The first one is sent on its own right after the info line, the second one while handling the hook
+    sm.trigger(Eval("hook".into())).next().unwrap().unwrap();
+    assert_eq!(
+        greeted(&mut sm),
+        [Greeted("ready".into()), Greeted("hook".into())]
+    );
+    assert_eq!(greeted(&mut sm), []);
+}
+
+#[test]
+fn oneshot() {
+    let mut sm = load("oneshot");
+    assert!(matches!(
+        sm.scripts()[0].metadata().script_type,
+        ScriptType::OneShot
+    ));
+    // The script is started again for each trigger
+    for input in ["a", "b"] {
+        assert_eq!(
+            sm.trigger(Eval(input.into())).next().unwrap().unwrap(),
+            format!("oneshot:{}", input)
+        );
+    }
+}
+
+#[test]
+fn host_call() {
+    let mut sm = load("host_call");
+    sm.register_host_function(|Double(n)| n * 2);
+    assert_eq!(sm.trigger(AskHost).next().unwrap().unwrap(), Ok(42));
+}
+
+#[test]
+fn host_call_error() {
+    let mut sm = load("host_call");
+    assert_eq!(
+        sm.trigger(AskHost).next().unwrap().unwrap(),
+        Err("host call failed".into())
+    );
+}
+
+#[test]
+fn marker() {
+    let mut sm = load("marker");
+    assert_eq!(sm.scripts()[0].metadata().name, "marker");
+    sm.trigger(Ping).next().unwrap().unwrap();
+}
+
+#[test]
+fn protocol_mismatch() {
+    let mut sm = ScriptManager::default();
+    let error = sm
+        .add_scripts_by_path(scripts("protocol_mismatch"), Version::new(0, 1, 0))
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        Error::ProtocolMismatch {
+            script_version: Some(999),
+            program_version: JSON_LINES_VERSION,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn invalid_output() {
+    let mut sm = load("invalid_output");
+    let error = sm.trigger(Eval("a".into())).next().unwrap().unwrap_err();
+    assert!(matches!(
+        error,
+        Error::Codec {
+            codec: Codec::Json,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn stray_output() {
+    let mut sm = load("stray_output");
+    sm.scripts_mut()[0].set_restart_policy(Some(RestartPolicy {
+        max_restarts: 1,
+        backoff: Duration::ZERO,
+    }));
+
+    let error = sm
+        .trigger(Eval("stray".into()))
+        .next()
+        .unwrap()
+        .unwrap_err();
+    assert!(
+        matches!(&error, Error::ProtocolViolation { bytes } if bytes == b"debug: evaluating\n"),
+        "{:?}",
+        error
+    );
+    // The daemon was restarted, it's in sync again
+    assert_eq!(sm.scripts()[0].restarts(), 1);
+    assert_eq!(sm.trigger(Eval("a".into())).next().unwrap().unwrap(), "ok");
+
+    // No restart left
+    assert!(sm.trigger(Eval("stray".into())).next().unwrap().is_err());
+    assert!(sm.scripts()[0].has_failed());
+}
+
+#[test]
+fn shutdown() {
+    let file = std::env::temp_dir().join(format!(
+        "rscript-json-lines-shutdown-{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&file);
+    drop(load("shutdown"));
+    assert!(file.exists());
+    std::fs::remove_file(file).unwrap();
+}
@@ -0,0 +1,23 @@
+#!/bin/sh
+# Keeps a counter between hooks, sends events while handling a hook and on its own
+count=0
+while IFS= read -r line; do
+    case "$line" in
+    *'"type":"greeting"'*)
+        echo '{"type":"info","protocol":4,"name":"daemon","script_type":"Daemon","hooks":["Eval","Ping"],"version_requirement":">=0.1.0"}'
+        echo '{"type":"event","name":"Greeted","data":"ready"}'
+        ;;
+    *'"type":"execute","hook":"Eval"'*)
+        count=$((count + 1))
+        data=$(printf '%s' "$line" | sed 's/.*"data":"\([^"]*\)".*/\1/')
+        echo "{\"type\":\"event\",\"name\":\"Greeted\",\"data\":\"$data\"}"
+        echo "{\"type\":\"output\",\"data\":\"$data:$count\"}"
+        ;;
+    *'"type":"execute","hook":"Ping"'*)
+        echo '{"type":"output"}'
+        ;;
+    *'"type":"shutdown"'*)
+        exit 0
+        ;;
+    esac
+done
@@ -0,0 +1,25 @@
+#!/bin/sh
+# Calls the Double host function before answering
+while IFS= read -r line; do
+    case "$line" in
+    *'"type":"greeting"'*)
+        echo '{"type":"info","protocol":4,"name":"host_call","script_type":"Daemon","hooks":["AskHost"],"version_requirement":">=0.1.0"}'
+        ;;
+    *'"type":"execute","hook":"AskHost"'*)
+        echo '{"type":"host_call","name":"Double","data":21}'
+        IFS= read -r reply
+        case "$reply" in
+        *'"type":"host_reply","ok":'*)
+            value=$(printf '%s' "$reply" | sed 's/.*"ok":\([0-9]*\).*/\1/')
+            echo "{\"type\":\"output\",\"data\":{\"Ok\":$value}}"
+            ;;
+        *'"type":"host_reply","error":'*)
+            echo '{"type":"output","data":{"Err":"host call failed"}}'
+            ;;
+        esac
+        ;;
+    *'"type":"shutdown"'*)
+        exit 0
+        ;;
+    esac
+done
@@ -0,0 +1,15 @@
+#!/bin/sh
+# Answers Eval with an output that doesn't deserialize into a string
+while IFS= read -r line; do
+    case "$line" in
+    *'"type":"greeting"'*)
+        echo '{"type":"info","protocol":4,"name":"invalid_output","script_type":"Daemon","hooks":["Eval"],"version_requirement":">=0.1.0"}'
+        ;;
+    *'"type":"execute","hook":"Eval"'*)
+        echo '{"type":"output","data":42}'
+        ;;
+    *'"type":"shutdown"'*)
+        exit 0
+        ;;
+    esac
+done
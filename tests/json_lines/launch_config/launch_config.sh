@@ -0,0 +1,12 @@
+#!/bin/sh
+# Reports how it was started (`ScriptLaunchConfig`): its first argument is its name, the output holds its working directory and environment
+IFS= read -r line
+case "$line" in
+*'"type":"greeting"'*)
+    echo "{\"type\":\"info\",\"protocol\":4,\"name\":\"$1\",\"script_type\":\"OneShot\",\"hooks\":[\"Eval\"],\"version_requirement\":\">=0.1.0\"}"
+    ;;
+*'"type":"execute","hook":"Eval"'*)
+    data=$(printf '%s' "$line" | sed 's/.*"data":"\([^"]*\)".*/\1/')
+    echo "{\"type\":\"output\",\"data\":\"$PWD:${CARGO_PKG_NAME-unset}:$LAUNCH_VAR:$data\"}"
+    ;;
+esac
@@ -0,0 +1,16 @@
+#!/bin/sh
+# rscript: json-lines
+# The leading space of the info line is valid JSON, but only the marker tells the main program that it's a JSON-lines answer
+while IFS= read -r line; do
+    case "$line" in
+    *'"type":"greeting"'*)
+        echo ' {"type":"info","protocol":4,"name":"marker","script_type":"Daemon","hooks":["Ping"],"version_requirement":">=0.1.0"}'
+        ;;
+    *'"type":"execute","hook":"Ping"'*)
+        echo '{"type":"output"}'
+        ;;
+    *'"type":"shutdown"'*)
+        exit 0
+        ;;
+    esac
+done
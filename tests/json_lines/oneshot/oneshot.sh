@@ -0,0 +1,12 @@
+#!/bin/sh
+# Answers one message then exits, the execute line is the first message it receives when triggered
+IFS= read -r line
+case "$line" in
+*'"type":"greeting"'*)
+    echo '{"type":"info","protocol":4,"name":"oneshot","script_type":"OneShot","hooks":["Eval"],"version_requirement":">=0.1.0"}'
+    ;;
+*'"type":"execute","hook":"Eval"'*)
+    data=$(printf '%s' "$line" | sed 's/.*"data":"\([^"]*\)".*/\1/')
+    echo "{\"type\":\"output\",\"data\":\"oneshot:$data\"}"
+    ;;
+esac
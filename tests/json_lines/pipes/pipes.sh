@@ -0,0 +1,20 @@
+#!/bin/sh
+# Speaks the protocol on the pipes passed in RSCRIPT_PROTOCOL_FDS (`ProtocolChannel::Pipes`), so it can print to stdout
+# dash only redirects the descriptors 0 to 9, so the pipes are reopened as 3 and 4
+exec 3<"/dev/fd/${RSCRIPT_PROTOCOL_FDS%,*}" 4>"/dev/fd/${RSCRIPT_PROTOCOL_FDS#*,}"
+while IFS= read -r line <&3; do
+    case "$line" in
+    *'"type":"greeting"'*)
+        echo 'pipes: this is not a protocol message'
+        echo '{"type":"info","protocol":4,"name":"pipes","script_type":"Daemon","hooks":["Eval"],"version_requirement":">=0.1.0"}' >&4
+        ;;
+    *'"type":"execute","hook":"Eval"'*)
+        data=$(printf '%s' "$line" | sed 's/.*"data":"\([^"]*\)".*/\1/')
+        echo "pipes: evaluating $data"
+        echo "{\"type\":\"output\",\"data\":\"pipes:$data\"}" >&4
+        ;;
+    *'"type":"shutdown"'*)
+        exit 0
+        ;;
+    esac
+done
@@ -0,0 +1,4 @@
+#!/bin/sh
+# Speaks a protocol version that doesn't exist
+IFS= read -r line
+echo '{"type":"info","protocol":999,"name":"protocol_mismatch","script_type":"OneShot","hooks":[],"version_requirement":">=0.1.0"}'
@@ -0,0 +1,13 @@
+#!/bin/sh
+# Records that it received the shutdown message in a file named after the main program pid
+while IFS= read -r line; do
+    case "$line" in
+    *'"type":"greeting"'*)
+        echo '{"type":"info","protocol":4,"name":"shutdown","script_type":"Daemon","hooks":[],"version_requirement":">=0.1.0"}'
+        ;;
+    *'"type":"shutdown"'*)
+        touch "${TMPDIR:-/tmp}/rscript-json-lines-shutdown-$PPID"
+        exit 0
+        ;;
+    esac
+done
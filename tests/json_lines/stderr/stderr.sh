@@ -0,0 +1,14 @@
+#!/bin/sh
+# Logs every message it receives to stderr (`ScriptStderr::Capture`)
+IFS= read -r line
+case "$line" in
+*'"type":"greeting"'*)
+    echo 'greeted' >&2
+    echo '{"type":"info","protocol":4,"name":"stderr","script_type":"OneShot","hooks":["Eval"],"version_requirement":">=0.1.0"}'
+    ;;
+*'"type":"execute","hook":"Eval"'*)
+    data=$(printf '%s' "$line" | sed 's/.*"data":"\([^"]*\)".*/\1/')
+    echo "evaluating $data" >&2
+    echo "{\"type\":\"output\",\"data\":\"stderr:$data\"}"
+    ;;
+esac
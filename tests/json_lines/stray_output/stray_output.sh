@@ -0,0 +1,19 @@
+#!/bin/sh
+# Prints a line that is not a message before answering Eval("stray")
+while IFS= read -r line; do
+    case "$line" in
+    *'"type":"greeting"'*)
+        echo '{"type":"info","protocol":4,"name":"stray_output","script_type":"Daemon","hooks":["Eval"],"version_requirement":">=0.1.0"}'
+        ;;
+    *'"type":"execute","hook":"Eval","data":"stray"'*)
+        echo 'debug: evaluating'
+        echo '{"type":"output","data":"stray"}'
+        ;;
+    *'"type":"execute","hook":"Eval"'*)
+        echo '{"type":"output","data":"ok"}'
+        ;;
+    *'"type":"shutdown"'*)
+        exit 0
+        ;;
+    esac
+done
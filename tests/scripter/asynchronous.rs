@@ -0,0 +1,128 @@
+//! `AsyncScriptManager` drives the scripts concurrently on a tokio runtime
+
+use crate::{
+    parallel::SLOW, script_dir, shutdown::tidied, timeouts::TIMEOUT, Eval, OneShotScript, Script,
+    Test,
+};
+use futures_util::StreamExt;
+use rscript::{
+    scripting::{HookHandlers, Scripter},
+    AsyncScriptManager, Error, Version,
+};
+use std::{fs, future::Future, path::Path, time::Instant};
+
+pub const TESTS: &[Test] = &[
+    Test {
+        name: "asynchronous::concurrent",
+        run: concurrent,
+    },
+    Test {
+        name: "asynchronous::script_trigger",
+        run: script_trigger,
+    },
+    Test {
+        name: "asynchronous::timeout",
+        run: timeout,
+    },
+    Test {
+        name: "asynchronous::shutdown",
+        run: shutdown,
+    },
+];
+
+pub const SCRIPTS: &[Script] = &[Script {
+    name: "echo_once",
+    run: echo_once,
+}];
+
+/// Run `future` on a new tokio runtime
+pub fn block_on(future: impl Future<Output = ()>) {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+async fn load_with(
+    dir: &Path,
+    configure: impl FnOnce(&mut AsyncScriptManager),
+) -> AsyncScriptManager {
+    let mut sm = AsyncScriptManager::default();
+    configure(&mut sm);
+    sm.add_scripts_by_path(dir, Version::new(0, 1, 0))
+        .await
+        .unwrap();
+    sm
+}
+
+/// The outputs are yielded as they arrive, a slow script doesn't delay the others
+fn concurrent() {
+    let dir = script_dir("asynchronous_concurrent", &["slow", "fast", "echo_once"]);
+    block_on(async {
+        let mut sm = load_with(&dir, |_| ()).await;
+        let start = Instant::now();
+        let mut outputs = sm.trigger(Eval("a".into()));
+        let mut first: Vec<String> = vec![
+            outputs.next().await.unwrap().unwrap(),
+            outputs.next().await.unwrap().unwrap(),
+        ];
+        assert!(start.elapsed() < SLOW, "{:?}", start.elapsed());
+        first.sort();
+        assert_eq!(first, ["echo_once a", "fast a"]);
+        assert_eq!(outputs.next().await.unwrap().unwrap(), "slow a");
+        assert!(outputs.next().await.is_none());
+    });
+}
+
+fn script_trigger() {
+    let dir = script_dir("asynchronous_script_trigger", &["echo_once"]);
+    block_on(async {
+        let mut sm = load_with(&dir, |_| ()).await;
+        let script = &mut sm.scripts_mut()[0];
+        assert_eq!(script.metadata().name, "one_shot");
+        for input in ["a", "b"] {
+            assert_eq!(
+                script.trigger(&Eval(input.into())).await.unwrap(),
+                format!("echo_once {}", input)
+            );
+        }
+        script.deactivate();
+        assert!(sm.trigger(Eval("a".into())).next().await.is_none());
+    });
+}
+
+fn timeout() {
+    let dir = script_dir("asynchronous_timeout", &["hung"]);
+    block_on(async {
+        let mut sm = load_with(&dir, |sm| sm.set_execute_timeout(Some(TIMEOUT))).await;
+        let start = Instant::now();
+        let error = sm
+            .trigger(Eval("hang".into()))
+            .next()
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert!(matches!(error, Error::Timeout(TIMEOUT)), "{:?}", error);
+        assert!(start.elapsed() < TIMEOUT * 10, "{:?}", start.elapsed());
+        assert!(sm.scripts()[0].has_failed());
+    });
+}
+
+fn shutdown() {
+    let dir = script_dir("asynchronous_shutdown", &["tidy"]);
+    let _ = fs::remove_file(tidied(&dir));
+    block_on(async {
+        let mut sm = load_with(&dir, |_| ()).await;
+        sm.shutdown().await;
+        assert_eq!(fs::read_to_string(tidied(&dir)).unwrap(), "tidied");
+    });
+}
+
+/// A one shot script
+fn echo_once() {
+    OneShotScript::run(
+        HookHandlers::default().on(|Eval(input): Eval| format!("echo_once {}", input)),
+    )
+    .unwrap();
+}
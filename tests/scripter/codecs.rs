@@ -0,0 +1,157 @@
+//! Each script talks to the main program with the codec it advertises in its handshake, scripts using different codecs run side by side
+
+use crate::{load_with, script_dir, Script, Test};
+use rscript::{
+    scripting::{HookHandlers, Scripter},
+    Codec, Hook, HostFunction, ScriptType, VersionReq,
+};
+use serde::{Deserialize, Serialize};
+
+pub const TESTS: &[Test] = &[
+    #[cfg(feature = "json")]
+    Test {
+        name: "codecs::json",
+        run: || negotiated("json_script", Codec::Json),
+    },
+    #[cfg(feature = "msgpack")]
+    Test {
+        name: "codecs::msgpack",
+        run: || negotiated("msgpack_script", Codec::MessagePack),
+    },
+    #[cfg(feature = "cbor")]
+    Test {
+        name: "codecs::cbor",
+        run: || negotiated("cbor_script", Codec::Cbor),
+    },
+];
+
+pub const SCRIPTS: &[Script] = &[
+    Script {
+        name: "bincode_script",
+        run: run::<BincodeScript>,
+    },
+    #[cfg(feature = "json")]
+    Script {
+        name: "json_script",
+        run: run::<JsonScript>,
+    },
+    #[cfg(feature = "msgpack")]
+    Script {
+        name: "msgpack_script",
+        run: run::<MessagePackScript>,
+    },
+    #[cfg(feature = "cbor")]
+    Script {
+        name: "cbor_script",
+        run: run::<CborScript>,
+    },
+];
+
+/// Exercises the encoding of structs, enums, options and sequences
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct Entry {
+    name: String,
+    size: Option<u64>,
+    kind: Kind,
+    tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+enum Kind {
+    File,
+    Dir { entries: u32 },
+}
+
+/// Answers with the entry, its name passed through [Upper]
+#[derive(Serialize, Deserialize)]
+struct Describe(Entry);
+impl Hook for Describe {
+    const NAME: &'static str = "Describe";
+    type Output = Entry;
+}
+
+/// Provided by the main program
+#[derive(Serialize, Deserialize)]
+struct Upper(String);
+impl HostFunction for Upper {
+    const NAME: &'static str = "Upper";
+    type Output = String;
+}
+
+macro_rules! coded_script {
+    ($(#[$cfg:meta])* $scripter:ident, $codec:expr) => {
+        $(#[$cfg])*
+        struct $scripter;
+        $(#[$cfg])*
+        impl Scripter for $scripter {
+            fn name() -> &'static str {
+                stringify!($scripter)
+            }
+            fn script_type() -> ScriptType {
+                ScriptType::Daemon
+            }
+            fn version_requirement() -> VersionReq {
+                VersionReq::parse(">=0.1.0").unwrap()
+            }
+            fn codec() -> Codec {
+                $codec
+            }
+        }
+    };
+}
+
+coded_script!(BincodeScript, Codec::Bincode);
+coded_script!(
+    #[cfg(feature = "json")]
+    JsonScript,
+    Codec::Json
+);
+coded_script!(
+    #[cfg(feature = "msgpack")]
+    MessagePackScript,
+    Codec::MessagePack
+);
+coded_script!(
+    #[cfg(feature = "cbor")]
+    CborScript,
+    Codec::Cbor
+);
+
+fn entry(name: &str) -> Entry {
+    Entry {
+        name: name.into(),
+        size: None,
+        kind: Kind::Dir { entries: 2 },
+        tags: vec!["a".into(), "".into()],
+    }
+}
+
+/// `script` and a bincode script answer the same hook and call the same host function, each with its own codec
+fn negotiated(script: &str, codec: Codec) {
+    let dir = script_dir(script, &[script, "bincode_script"]);
+    let mut sm = load_with(&dir, |sm| {
+        sm.register_host_function(|Upper(input)| input.to_uppercase())
+    });
+    let codec_of = |name: &str| {
+        sm.scripts()
+            .iter()
+            .find(|script| script.path().ends_with(name))
+            .unwrap()
+            .codec()
+    };
+    assert_eq!(codec_of(script), codec);
+    assert_eq!(codec_of("bincode_script"), Codec::Bincode);
+    let outputs: Vec<Entry> = sm
+        .trigger(Describe(entry("file")))
+        .map(|output| output.unwrap())
+        .collect();
+    assert_eq!(outputs, [entry("FILE"), entry("FILE")]);
+}
+
+fn run<S: Scripter>() {
+    S::run(HookHandlers::default().on(|Describe(entry)| Entry {
+        name: S::call(&Upper(entry.name.clone())).unwrap(),
+        ..entry
+    }))
+    .unwrap();
+}
@@ -0,0 +1,111 @@
+//! `discover_scripts_by_path` loads every script it can and reports the others, `add_scripts_by_path` adds nothing if one fails
+
+use crate::{script_dir, Eval, Script, Test};
+use rscript::{
+    scripting::{HookHandlers, Scripter},
+    DiscoveryStatus, Error, ScriptManager, ScriptType, Version, VersionReq,
+};
+use std::{env, fs, process};
+
+pub const TESTS: &[Test] = &[
+    Test {
+        name: "discovery::report",
+        run: report,
+    },
+    Test {
+        name: "discovery::add_all_or_nothing",
+        run: add_all_or_nothing,
+    },
+];
+
+pub const SCRIPTS: &[Script] = &[
+    Script {
+        name: "picky",
+        run: picky,
+    },
+    Script {
+        name: "quitter",
+        run: quitter,
+    },
+];
+
+const VERSION: Version = Version::new(0, 1, 0);
+
+/// A folder with a script that loads, two that don't and a dynamic library
+fn mixed_dir(case: &str) -> std::path::PathBuf {
+    let dir = script_dir(case, &["fast", "picky", "quitter"]);
+    let library = dir
+        .join("library")
+        .with_extension(env::consts::DLL_EXTENSION);
+    fs::write(library, "").unwrap();
+    dir
+}
+
+fn report() {
+    let dir = mixed_dir("discovery_report");
+    let mut sm = ScriptManager::default();
+    let report = sm.discover_scripts_by_path(&dir, VERSION).unwrap();
+
+    assert_eq!(report.entries().len(), 4);
+    assert_eq!(report.loaded().collect::<Vec<_>>(), [dir.join("fast")]);
+    assert_eq!(
+        report.skipped().collect::<Vec<_>>(),
+        [dir.join("library")
+            .with_extension(env::consts::DLL_EXTENSION)]
+    );
+    let mut failed: Vec<_> = report.failed().collect();
+    failed.sort_by_key(|(path, _)| path.to_path_buf());
+    assert!(
+        matches!(&failed[..], [(picky, Error::ScriptVersionMismatch { .. }), (quitter, _)]
+        if *picky == dir.join("picky") && *quitter == dir.join("quitter")),
+        "{:?}",
+        failed
+    );
+    assert!(report
+        .entries()
+        .iter()
+        .all(|(_, status)| !matches!(status, DiscoveryStatus::HookMismatch(_))));
+
+    assert_eq!(sm.scripts().len(), 1);
+    assert_eq!(
+        sm.trigger(Eval("a".into())).next().unwrap().unwrap(),
+        "fast a"
+    );
+    // The failed scripts are not tried again until they change
+    assert!(sm.reload().unwrap().is_empty());
+}
+
+fn add_all_or_nothing() {
+    let dir = mixed_dir("discovery_add_all_or_nothing");
+    let mut sm = ScriptManager::default();
+    assert!(sm.add_scripts_by_path(&dir, VERSION).is_err());
+    assert!(sm.scripts().is_empty());
+
+    fs::remove_file(dir.join("picky")).unwrap();
+    fs::remove_file(dir.join("quitter")).unwrap();
+    sm.add_scripts_by_path(&dir, VERSION).unwrap();
+    assert_eq!(sm.scripts().len(), 1);
+}
+
+struct Picky;
+impl Scripter for Picky {
+    fn name() -> &'static str {
+        "picky"
+    }
+    fn script_type() -> ScriptType {
+        ScriptType::Daemon
+    }
+    fn version_requirement() -> VersionReq {
+        VersionReq::parse(">=9.0.0").unwrap()
+    }
+}
+
+/// Requires a newer version of the program, so it's refused after the greeting
+fn picky() {
+    let _ = Picky::run(HookHandlers::default().on(|Eval(input): Eval| input));
+}
+
+/// Exits before answering the greeting
+fn quitter() {
+    process::exit(1);
+}
@@ -0,0 +1,171 @@
+//! Events sent by `Scripter` scripts, while handling a hook or at any other time, are collected with `poll_events`
+
+use crate::{load, script_dir, DaemonScript, Eval, Script, Test};
+use rscript::{
+    scripting::{HookHandlers, Scripter},
+    Error, Event, Hook, RestartPolicy, ScriptEvent, ScriptType, VersionReq,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+pub const TESTS: &[Test] = &[
+    Test {
+        name: "events::received",
+        run: received,
+    },
+    Test {
+        name: "events::unexpected_message",
+        run: unexpected_message,
+    },
+    #[cfg(feature = "tokio")]
+    Test {
+        name: "events::async_unexpected_message",
+        run: async_unexpected_message,
+    },
+];
+
+pub const SCRIPTS: &[Script] = &[
+    Script {
+        name: "eventful",
+        run: eventful,
+    },
+    Script {
+        name: "chatty",
+        run: chatty,
+    },
+];
+
+/// Sent by `eventful` when it starts
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Started;
+impl Event for Started {
+    const NAME: &'static str = "Started";
+}
+
+/// Sent by `eventful` while handling [Eval]
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Evaluating(String);
+impl Event for Evaluating {
+    const NAME: &'static str = "Evaluating";
+}
+
+/// Poll `poll` until it returns something, for a few seconds at most
+fn poll_until<T>(mut poll: impl FnMut() -> Vec<T>) -> Vec<T> {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let received = poll();
+        if !received.is_empty() || Instant::now() > deadline {
+            return received;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn received() {
+    let dir = script_dir("events_received", &["eventful"]);
+    let mut sm = load(&dir);
+    let id = sm.scripts()[0].id();
+    let events: Vec<ScriptEvent> =
+        poll_until(|| sm.poll_events().map(Result::unwrap).collect::<Vec<_>>());
+    assert!(matches!(&events[..], [event] if event.get::<Started>().unwrap().unwrap() == Started));
+
+    assert_eq!(sm.trigger(Eval("a".into())).next().unwrap().unwrap(), "a");
+    // Received while the hook was triggered
+    let events: Vec<ScriptEvent> = sm.poll_events().map(Result::unwrap).collect();
+    assert_eq!(events.len(), 1);
+    assert_eq!((events[0].id, events[0].name.as_str()), (id, "daemon"));
+    assert_eq!(events[0].event_name(), "Evaluating");
+    assert!(events[0].get::<Started>().is_none());
+    assert_eq!(
+        events[0].get::<Evaluating>().unwrap().unwrap(),
+        Evaluating("a".into())
+    );
+    assert_eq!(sm.poll_events().count(), 0);
+}
+
+/// A message that is not an event breaks the protocol, the daemon is restarted like a crashed one
+fn unexpected_message() {
+    let dir = script_dir("events_unexpected_message", &["chatty"]);
+    let mut sm = load(&dir);
+    sm.scripts_mut()[0].set_restart_policy(Some(RestartPolicy {
+        max_restarts: 1,
+        backoff: Duration::ZERO,
+    }));
+    assert_eq!(sm.trigger(Eval("a".into())).next().unwrap().unwrap(), "a");
+    let errors = poll_until(|| sm.poll_events().collect::<Vec<_>>());
+    assert!(
+        matches!(&errors[..], [Err(Error::ProtocolViolation { .. })]),
+        "{:?}",
+        errors
+    );
+    assert_eq!(sm.scripts()[0].restarts(), 1);
+    assert_eq!(sm.trigger(Eval("b".into())).next().unwrap().unwrap(), "b");
+}
+
+/// Same as [unexpected_message] with the async manager, which marks the script as failed
+#[cfg(feature = "tokio")]
+fn async_unexpected_message() {
+    use futures_util::StreamExt;
+    let dir = script_dir("events_async_unexpected_message", &["chatty"]);
+    crate::asynchronous::block_on(async {
+        let mut sm = rscript::AsyncScriptManager::default();
+        sm.add_scripts_by_path(&dir, rscript::Version::new(0, 1, 0))
+            .await
+            .unwrap();
+        let output = sm.trigger(Eval("a".into())).next().await.unwrap();
+        assert_eq!(output.unwrap(), "a");
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let errors = loop {
+            let errors: Vec<_> = sm.poll_events().collect();
+            if !errors.is_empty() || Instant::now() > deadline {
+                break errors;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+        assert!(
+            matches!(&errors[..], [Err(Error::ProtocolViolation { .. })]),
+            "{:?}",
+            errors
+        );
+        assert!(sm.scripts()[0].has_failed());
+    });
+}
+
+/// Sends [Started] once it's greeted, and [Evaluating] while handling [Eval]
+fn eventful() {
+    thread::spawn(|| DaemonScript::send_event(&Started).unwrap());
+    DaemonScript::run(HookHandlers::default().on(|Eval(input): Eval| {
+        DaemonScript::send_event(&Evaluating(input.clone())).unwrap();
+        input
+    }))
+    .unwrap();
+}
+
+struct Chatty;
+impl Scripter for Chatty {
+    fn name() -> &'static str {
+        "chatty"
+    }
+    fn script_type() -> ScriptType {
+        ScriptType::Daemon
+    }
+    fn hooks() -> &'static [&'static str] {
+        &[Eval::NAME]
+    }
+    fn version_requirement() -> VersionReq {
+        VersionReq::parse(">=0.1.0").unwrap()
+    }
+}
+
+/// Answers [Eval] twice
+fn chatty() {
+    Chatty::execute(&mut |_| {
+        let Eval(input) = Chatty::read::<Eval>();
+        Chatty::write::<Eval>(&input);
+        Chatty::write::<Eval>(&input);
+    })
+    .unwrap();
+}
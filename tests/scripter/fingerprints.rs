@@ -0,0 +1,176 @@
+//! Hooks built with a different definition are reported when the scripts are loaded, and checked again when they are restarted
+
+use crate::{add_script, load_with, script_dir, DaemonScript, Script, Test};
+use rscript::{
+    scripting::{HookHandlers, Scripter},
+    DiscoveryStatus, Error, Hook, MismatchedHook, ReloadEvent, RestartPolicy, ScriptManager,
+    Version,
+};
+use serde::{Deserialize, Serialize};
+use std::{env, fs, process, time::Duration};
+
+pub const TESTS: &[Test] = &[
+    Test {
+        name: "fingerprints::discovery_reports_mismatched_hooks",
+        run: discovery_reports_mismatched_hooks,
+    },
+    Test {
+        name: "fingerprints::reload_reports_mismatched_hooks",
+        run: reload_reports_mismatched_hooks,
+    },
+    Test {
+        name: "fingerprints::checked_on_restart",
+        run: checked_on_restart,
+    },
+];
+
+pub const SCRIPTS: &[Script] = &[
+    Script {
+        name: "same_eval",
+        run: same_eval,
+    },
+    Script {
+        name: "other_eval",
+        run: other_eval,
+    },
+    Script {
+        name: "switching_eval",
+        run: switching_eval,
+    },
+];
+
+/// The program definition of the hook
+#[derive(Serialize, Deserialize)]
+struct Eval(String);
+impl Hook for Eval {
+    const NAME: &'static str = "Eval";
+    type Output = String;
+    const FINGERPRINT: Option<u64> = Some(1);
+}
+
+/// The same hook, built with another definition
+#[derive(Serialize, Deserialize)]
+struct OtherEval(String);
+impl Hook for OtherEval {
+    const NAME: &'static str = "Eval";
+    type Output = String;
+    const FINGERPRINT: Option<u64> = Some(2);
+}
+
+/// Makes `switching_eval` exit
+#[derive(Serialize, Deserialize)]
+struct Crash;
+impl Hook for Crash {
+    const NAME: &'static str = "Crash";
+    type Output = ();
+}
+
+fn expected_mismatch() -> MismatchedHook {
+    MismatchedHook {
+        hook: "Eval".into(),
+        program_fingerprint: 1,
+        script_fingerprint: 2,
+    }
+}
+
+fn discovery_reports_mismatched_hooks() {
+    let dir = script_dir("discovery_mismatch", &["same_eval", "other_eval"]);
+    let mut sm = ScriptManager::default();
+    sm.register_hook::<Eval>();
+    let report = sm
+        .discover_scripts_by_path(&dir, Version::new(0, 1, 0))
+        .unwrap();
+
+    assert_eq!(report.loaded().count(), 2);
+    let mismatches: Vec<_> = report.mismatched_hooks().collect();
+    assert_eq!(
+        mismatches,
+        [(dir.join("other_eval").as_path(), &expected_mismatch())]
+    );
+    // The mismatch entry follows the loaded one
+    let statuses: Vec<_> = report
+        .entries()
+        .iter()
+        .filter(|(path, _)| path.ends_with("other_eval"))
+        .map(|(_, status)| status)
+        .collect();
+    assert!(matches!(
+        statuses[..],
+        [DiscoveryStatus::Loaded, DiscoveryStatus::HookMismatch(_)]
+    ));
+
+    let outputs: Vec<_> = sm.trigger(Eval("1 + 1".into())).collect();
+    assert_eq!(outputs.len(), 2);
+    assert_eq!(outputs.iter().filter(|output| output.is_ok()).count(), 1);
+}
+
+fn reload_reports_mismatched_hooks() {
+    let dir = script_dir("reload_mismatch", &[]);
+    let mut sm = load_with(&dir, ScriptManager::register_hook::<Eval>);
+    let path = add_script(&dir, "other_eval");
+
+    let events = sm.reload().unwrap();
+    assert!(
+        matches!(&events[..], [ReloadEvent::Added(added), ReloadEvent::HookMismatch(mismatched, mismatch)]
+        if *added == path && *mismatched == path && *mismatch == expected_mismatch())
+    );
+    assert_eq!(sm.scripts()[0].mismatched_hooks(), [expected_mismatch()]);
+}
+
+fn checked_on_restart() {
+    let dir = script_dir("restart_mismatch", &["switching_eval"]);
+    // Outside of the folder, so it's not loaded as a script
+    let other = dir.with_extension("other");
+    fs::write(&other, "").unwrap();
+    let mut sm = load_with(&dir, ScriptManager::register_hook::<Eval>);
+    sm.scripts_mut()[0].set_restart_policy(Some(RestartPolicy {
+        max_restarts: 2,
+        backoff: Duration::ZERO,
+    }));
+    assert_eq!(sm.scripts()[0].mismatched_hooks(), [expected_mismatch()]);
+
+    // Rebuilt with the program definition
+    fs::remove_file(&other).unwrap();
+    let error = sm.trigger(Crash).next().unwrap().unwrap_err();
+    assert!(matches!(error, Error::ScriptCrashed { .. }), "{:?}", error);
+    assert_eq!(sm.scripts()[0].restarts(), 1);
+    assert!(sm.scripts()[0].mismatched_hooks().is_empty());
+    assert_eq!(sm.trigger(Eval("a".into())).next().unwrap().unwrap(), "a");
+
+    // Rebuilt with another definition again
+    fs::write(&other, "").unwrap();
+    sm.trigger(Crash).next().unwrap().unwrap_err();
+    assert_eq!(sm.scripts()[0].mismatched_hooks(), [expected_mismatch()]);
+    assert!(matches!(
+        sm.trigger(Eval("a".into())).next().unwrap(),
+        Err(Error::HookMismatch { .. })
+    ));
+}
+
+fn same_eval() {
+    DaemonScript::run(HookHandlers::default().on(|Eval(input): Eval| input)).unwrap();
+}
+
+fn other_eval() {
+    DaemonScript::run(HookHandlers::default().on(|OtherEval(input): OtherEval| input)).unwrap();
+}
+
+/// Uses [OtherEval] when its folder has an `.other` sibling, [Eval] otherwise, and exits on [Crash]
+fn switching_eval() {
+    let crash = |Crash| process::exit(1);
+    let exe = env::current_exe().unwrap();
+    if exe.parent().unwrap().with_extension("other").exists() {
+        DaemonScript::run(
+            HookHandlers::default()
+                .on(|OtherEval(input): OtherEval| input)
+                .on(crash),
+        )
+    } else {
+        DaemonScript::run(
+            HookHandlers::default()
+                .on(|Eval(input): Eval| input)
+                .on(crash),
+        )
+    }
+    .unwrap();
+}
@@ -0,0 +1,108 @@
+//! Anything a daemon prints to stdout besides its frames is a protocol violation, the daemon is restarted according to its `RestartPolicy`
+
+use crate::{load, script_dir, DaemonScript, Eval, Script, Test};
+use rscript::{
+    scripting::{HookHandlers, Scripter},
+    Error, Hook, RestartPolicy, ScriptManager,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+pub const TESTS: &[Test] = &[
+    Test {
+        name: "framing::stray_stdout",
+        run: stray_stdout,
+    },
+    Test {
+        name: "framing::oversized_frame",
+        run: oversized_frame,
+    },
+    Test {
+        name: "framing::undecodable_output",
+        run: undecodable_output,
+    },
+];
+
+pub const SCRIPTS: &[Script] = &[Script {
+    name: "noisy",
+    run: noisy,
+}];
+
+fn load_noisy(case: &str) -> ScriptManager {
+    let mut sm = load(&script_dir(case, &["noisy"]));
+    sm.scripts_mut()[0].set_restart_policy(Some(RestartPolicy {
+        max_restarts: 1,
+        backoff: Duration::ZERO,
+    }));
+    sm
+}
+
+/// Trigger `input`, which breaks the protocol, then check that the daemon was restarted and answers again
+fn recovers_from(sm: &mut ScriptManager, input: &str) -> Vec<u8> {
+    let error = sm.trigger(Eval(input.into())).next().unwrap().unwrap_err();
+    let bytes = match error {
+        Error::ProtocolViolation { bytes } => bytes,
+        error => panic!("{:?}", error),
+    };
+    assert_eq!(sm.scripts()[0].restarts(), 1);
+    assert_eq!(sm.trigger(Eval("a".into())).next().unwrap().unwrap(), "a");
+    bytes
+}
+
+fn stray_stdout() {
+    let mut sm = load_noisy("stray_stdout");
+    // The bytes received after it are reported too
+    assert!(recovers_from(&mut sm, "print").starts_with(b"debug: evaluating\n"));
+
+    // No restart left
+    assert!(sm.trigger(Eval("print".into())).next().unwrap().is_err());
+    assert!(sm.scripts()[0].has_failed());
+}
+
+fn oversized_frame() {
+    let mut sm = load_noisy("oversized_frame");
+    let bytes = recovers_from(&mut sm, "oversized");
+    assert_eq!(bytes[..3], [0xFF, b'R', 5]);
+}
+
+/// The main program's definition of `Eval` that doesn't match the script one
+#[derive(Serialize, Deserialize)]
+struct MistypedEval(String);
+impl Hook for MistypedEval {
+    const NAME: &'static str = "Eval";
+    type Output = (u64, u64);
+}
+
+/// An output that fails to decode is skipped, the daemon stays in sync
+fn undecodable_output() {
+    let mut sm = load_noisy("undecodable_output");
+    let error = sm
+        .trigger(MistypedEval("a".into()))
+        .next()
+        .unwrap()
+        .unwrap_err();
+    assert!(matches!(error, Error::Bincode(_)), "{:?}", error);
+    assert_eq!(sm.trigger(Eval("a".into())).next().unwrap().unwrap(), "a");
+    assert_eq!(sm.scripts()[0].restarts(), 0);
+    assert!(!sm.scripts()[0].has_failed());
+}
+
+/// Breaks the protocol before answering some inputs
+fn noisy() {
+    // It fails once the main program gives up on it
+    let _ = DaemonScript::run(HookHandlers::default().on(|Eval(input): Eval| {
+        match input.as_str() {
+            "print" => println!("debug: evaluating"),
+            // A frame header announcing a 4 GiB message
+            "oversized" => io::stdout()
+                .write_all(&[0xFF, b'R', 5, 0xFF, 0xFF, 0xFF, 0xFF])
+                .unwrap(),
+            _ => (),
+        }
+        io::stdout().flush().unwrap();
+        input
+    }));
+}
@@ -0,0 +1,134 @@
+//! `Scripter::run` reads each hook, passes it to its typed handler and writes the output, the script listens to the handled hooks
+
+use crate::{load, load_with, script_dir, DaemonScript, Eval, Script, Test};
+use rscript::{
+    scripting::{HookHandlers, Scripter},
+    Error, Hook, ScriptType, VersionReq,
+};
+use serde::{Deserialize, Serialize};
+
+pub const TESTS: &[Test] = &[
+    Test {
+        name: "handlers::listens_to_handled_hooks",
+        run: listens_to_handled_hooks,
+    },
+    Test {
+        name: "handlers::stateful",
+        run: stateful,
+    },
+    Test {
+        name: "handlers::unhandled",
+        run: unhandled,
+    },
+];
+
+pub const SCRIPTS: &[Script] = &[
+    Script {
+        name: "handled",
+        run: handled,
+    },
+    Script {
+        name: "counting",
+        run: counting,
+    },
+];
+
+/// Answers with the length of its input
+#[derive(Serialize, Deserialize)]
+struct Len(String);
+impl Hook for Len {
+    const NAME: &'static str = "Len";
+    type Output = usize;
+    const FINGERPRINT: Option<u64> = Some(1);
+}
+
+/// Counts the hooks the script received
+#[derive(Serialize, Deserialize)]
+struct Count;
+impl Hook for Count {
+    const NAME: &'static str = "Count";
+    type Output = usize;
+}
+
+/// Not handled by any script
+#[derive(Serialize, Deserialize)]
+struct Unhandled;
+impl Hook for Unhandled {
+    const NAME: &'static str = "Unhandled";
+    type Output = ();
+}
+
+/// Advertises hooks it doesn't handle, [Scripter::run] ignores them
+struct StaleHooks;
+impl Scripter for StaleHooks {
+    fn name() -> &'static str {
+        "stale_hooks"
+    }
+    fn script_type() -> ScriptType {
+        ScriptType::Daemon
+    }
+    fn hooks() -> &'static [&'static str] {
+        &[Unhandled::NAME]
+    }
+    fn version_requirement() -> VersionReq {
+        VersionReq::parse(">=0.1.0").unwrap()
+    }
+}
+
+/// The advertised hooks and fingerprints come from the handlers, a replaced handler is listed once
+fn listens_to_handled_hooks() {
+    let dir = script_dir("handlers_listens_to_handled_hooks", &["handled"]);
+    let mut sm = load_with(&dir, |sm| sm.register_hook::<Len>());
+    let metadata = sm.scripts()[0].metadata();
+    assert_eq!(*metadata.hooks, ["Len", "Eval"]);
+    assert_eq!(metadata.fingerprints.get("Len"), Some(&1));
+    assert_eq!(metadata.fingerprints.len(), 1);
+    assert!(sm.scripts()[0].mismatched_hooks().is_empty());
+    assert_eq!(sm.trigger(Len("abc".into())).next().unwrap().unwrap(), 3);
+    assert_eq!(
+        sm.trigger(Eval("a".into())).next().unwrap().unwrap(),
+        "replaced a"
+    );
+}
+
+/// A daemon handler keeps its state between hooks
+fn stateful() {
+    let dir = script_dir("handlers_stateful", &["counting"]);
+    let mut sm = load(&dir);
+    let counts: Vec<usize> = (0..3)
+        .map(|_| sm.trigger(Count).next().unwrap().unwrap())
+        .collect();
+    assert_eq!(counts, [1, 2, 3]);
+}
+
+/// A hook without a handler is never sent to the script
+fn unhandled() {
+    let dir = script_dir("handlers_unhandled", &["handled"]);
+    let mut sm = load(&dir);
+    assert_eq!(sm.trigger(Unhandled).count(), 0);
+    assert!(matches!(
+        sm.scripts_mut()[0].trigger(&Unhandled),
+        Err(Error::ScriptIsNotListeningForHook)
+    ));
+    // The script is still in sync
+    assert_eq!(sm.trigger(Len("ab".into())).next().unwrap().unwrap(), 2);
+}
+
+fn handled() {
+    StaleHooks::run(
+        HookHandlers::default()
+            .on(|Len(input)| input.len())
+            .on(|Eval(input)| input)
+            .on(|Eval(input)| format!("replaced {}", input)),
+    )
+    .unwrap();
+}
+
+fn counting() {
+    let mut count = 0;
+    DaemonScript::run(HookHandlers::default().on(|Count| {
+        count += 1;
+        count
+    }))
+    .unwrap();
+}
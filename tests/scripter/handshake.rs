@@ -0,0 +1,157 @@
+//! Scripts that don't send a handshake are refused with `Error::ProtocolMismatch`
+
+use crate::{load, script_dir, Eval, Script, Test};
+use rscript::{Error, ScriptManager, ScriptType, Version, VersionReq};
+use serde::Serialize;
+use std::io::{self, Read, Write};
+
+pub const TESTS: &[Test] = &[
+    Test {
+        name: "handshake::pre_handshake_daemon",
+        run: pre_handshake_daemon,
+    },
+    Test {
+        name: "handshake::pre_handshake_one_shot",
+        run: pre_handshake_one_shot,
+    },
+    Test {
+        name: "handshake::garbage",
+        run: garbage,
+    },
+    Test {
+        name: "handshake::exits_without_answering",
+        run: exits_without_answering,
+    },
+    #[cfg(unix)]
+    Test {
+        name: "handshake::interpreted_scripter",
+        run: interpreted_scripter,
+    },
+    #[cfg(feature = "tokio")]
+    Test {
+        name: "handshake::async_pre_handshake_daemon",
+        run: async_pre_handshake_daemon,
+    },
+];
+
+pub const SCRIPTS: &[Script] = &[
+    Script {
+        name: "pre_handshake_daemon",
+        run: || pre_handshake(ScriptType::Daemon),
+    },
+    Script {
+        name: "pre_handshake_one_shot",
+        run: || pre_handshake(ScriptType::OneShot),
+    },
+    Script {
+        name: "garbage",
+        run: || println!("hello"),
+    },
+    Script {
+        name: "silent",
+        run: || (),
+    },
+];
+
+fn assert_no_handshake(case: &str, script: &str) {
+    let dir = script_dir(case, &[script]);
+    let error = ScriptManager::default()
+        .add_scripts_by_path(&dir, Version::new(0, 1, 0))
+        .unwrap_err();
+    assert!(
+        matches!(error, Error::ProtocolMismatch { script_version: None, ref path, .. } if *path == dir.join(script)),
+        "{:?}",
+        error
+    );
+}
+
+fn pre_handshake_daemon() {
+    assert_no_handshake("pre_handshake_daemon", "pre_handshake_daemon");
+}
+
+fn pre_handshake_one_shot() {
+    assert_no_handshake("pre_handshake_one_shot", "pre_handshake_one_shot");
+}
+
+fn garbage() {
+    assert_no_handshake("garbage", "garbage");
+}
+
+fn exits_without_answering() {
+    assert_no_handshake("exits_without_answering", "silent");
+}
+
+/// A `Scripter` script run by an interpreter (rust-script for example) is greeted with a JSON line, it answers with its handshake
+#[cfg(unix)]
+fn interpreted_scripter() {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    let bin = script_dir("interpreted_scripter_bin", &["same_eval"]);
+    let dir = script_dir("interpreted_scripter", &[]);
+    let script = dir.join("interpreted");
+    fs::write(
+        &script,
+        format!("#!/bin/sh\nexec {}\n", bin.join("same_eval").display()),
+    )
+    .unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let mut sm = load(&dir);
+    let outputs: Vec<_> = sm.trigger(Eval("1 + 1".into())).collect();
+    assert_eq!(outputs.len(), 1);
+    assert_eq!(outputs[0].as_ref().unwrap(), "1 + 1");
+}
+
+#[cfg(feature = "tokio")]
+fn async_pre_handshake_daemon() {
+    let dir = script_dir("async_pre_handshake_daemon", &["pre_handshake_daemon"]);
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let error = runtime
+        .block_on(
+            rscript::AsyncScriptManager::default().add_scripts_by_path(&dir, Version::new(0, 1, 0)),
+        )
+        .unwrap_err();
+    assert!(
+        matches!(
+            error,
+            Error::ProtocolMismatch {
+                script_version: None,
+                ..
+            }
+        ),
+        "{:?}",
+        error
+    );
+}
+
+/// The `ScriptInfo` of the rscript versions that predate the handshake
+#[derive(Serialize)]
+struct PreHandshakeScriptInfo {
+    name: String,
+    script_type: ScriptType,
+    hooks: Vec<String>,
+    version_requirement: VersionReq,
+}
+
+/// Behaves like a script built with an rscript version that predates the handshake: it reads the bincode greeting and answers with its bare `ScriptInfo`
+fn pre_handshake(script_type: ScriptType) {
+    let mut greeting = [0; 4];
+    io::stdin().read_exact(&mut greeting).unwrap();
+    assert_eq!(greeting, [0; 4]);
+    let info = PreHandshakeScriptInfo {
+        name: "pre_handshake".into(),
+        script_type,
+        hooks: vec!["Eval".into()],
+        version_requirement: VersionReq::parse(">=0.1.0").unwrap(),
+    };
+    let mut stdout = io::stdout();
+    bincode::serialize_into(&mut stdout, &info).unwrap();
+    stdout.flush().unwrap();
+    if matches!(script_type, ScriptType::Daemon) {
+        // Wait for the next message
+        let _ = io::stdin().read(&mut [0; 4]);
+    }
+}
@@ -0,0 +1,102 @@
+//! `Scripter` scripts call the host functions registered by the main program while handling a hook
+
+use crate::{load_with, script_dir, DaemonScript, Eval, OneShotScript, Script, Test};
+use rscript::{
+    scripting::{HookHandlers, Scripter},
+    HostFunction, ScriptManager,
+};
+use serde::{Deserialize, Serialize};
+
+pub const TESTS: &[Test] = &[
+    Test {
+        name: "host::calls",
+        run: calls,
+    },
+    Test {
+        name: "host::unregistered",
+        run: unregistered,
+    },
+    #[cfg(feature = "tokio")]
+    Test {
+        name: "host::async_calls",
+        run: async_calls,
+    },
+];
+
+pub const SCRIPTS: &[Script] = &[
+    Script {
+        name: "caller",
+        run: caller,
+    },
+    Script {
+        name: "one_shot_caller",
+        run: one_shot_caller,
+    },
+];
+
+/// Provided by the main program
+#[derive(Serialize, Deserialize)]
+struct Upper(String);
+impl HostFunction for Upper {
+    const NAME: &'static str = "Upper";
+    type Output = String;
+}
+
+fn register(sm: &mut ScriptManager) {
+    sm.register_host_function(|Upper(input)| input.to_uppercase());
+}
+
+fn calls() {
+    let dir = script_dir("host_calls", &["caller", "one_shot_caller"]);
+    let mut sm = load_with(&dir, register);
+    let outputs: Vec<String> = sm
+        .trigger(Eval("a".into()))
+        .map(|output| output.unwrap())
+        .collect();
+    assert_eq!(outputs, ["A", "A"]);
+    // Registering the function again replaces the handler
+    sm.register_host_function(|Upper(input)| format!("{}!", input.to_uppercase()));
+    assert_eq!(sm.trigger(Eval("b".into())).next().unwrap().unwrap(), "B!");
+}
+
+/// The script receives an error, the main program keeps working
+fn unregistered() {
+    let dir = script_dir("host_unregistered", &["caller"]);
+    let mut sm = load_with(&dir, |_| ());
+    assert_eq!(
+        sm.trigger(Eval("a".into())).next().unwrap().unwrap(),
+        "Host function call failed: host function Upper is not registered"
+    );
+}
+
+#[cfg(feature = "tokio")]
+fn async_calls() {
+    use futures_util::StreamExt;
+    let dir = script_dir("host_async_calls", &["caller", "one_shot_caller"]);
+    crate::asynchronous::block_on(async {
+        let mut sm = rscript::AsyncScriptManager::default();
+        sm.register_host_function(|Upper(input)| input.to_uppercase());
+        sm.add_scripts_by_path(&dir, rscript::Version::new(0, 1, 0))
+            .await
+            .unwrap();
+        let outputs: Vec<String> = sm
+            .trigger(Eval("a".into()))
+            .map(|output| output.unwrap())
+            .collect()
+            .await;
+        assert_eq!(outputs, ["A", "A"]);
+    });
+}
+
+/// Answers with the output of [Upper], or the error of the call
+fn upper<S: Scripter>(Eval(input): Eval) -> String {
+    S::call(&Upper(input)).unwrap_or_else(|e| e.to_string())
+}
+
+fn caller() {
+    DaemonScript::run(HookHandlers::default().on(upper::<DaemonScript>)).unwrap();
+}
+
+fn one_shot_caller() {
+    OneShotScript::run(HookHandlers::default().on(upper::<OneShotScript>)).unwrap();
+}
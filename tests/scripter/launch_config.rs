@@ -0,0 +1,71 @@
+//! Scripts started with a `ScriptLaunchConfig`, it applies to the greeting and to every `OneShot` trigger
+
+use crate::{load_with, script_dir, Eval, OneShotScript, Script, Test};
+use rscript::{
+    scripting::{HookHandlers, Scripter},
+    ScriptLaunchConfig, ScriptManager,
+};
+use std::{env, path::Path};
+
+pub const TESTS: &[Test] = &[
+    Test {
+        name: "launch_config::scripter",
+        run: scripter,
+    },
+    #[cfg(all(feature = "json", unix))]
+    Test {
+        name: "launch_config::json_lines",
+        run: json_lines,
+    },
+];
+
+pub const SCRIPTS: &[Script] = &[Script {
+    name: "launched",
+    run: launched,
+}];
+
+fn load(dir: &Path) -> ScriptManager {
+    let mut config = ScriptLaunchConfig::default();
+    config
+        .env_allowlist(["PATH"])
+        .env("LAUNCH_VAR", "set")
+        .arg("configured")
+        .current_dir("/");
+    load_with(dir, |sm| sm.set_launch_config(config))
+}
+
+fn scripter() {
+    let mut sm = load(&script_dir("launch_config", &["launched"]));
+    for input in ["a", "b"] {
+        assert_eq!(
+            sm.trigger(Eval(input.into())).next().unwrap().unwrap(),
+            format!("configured:/:unset:set:{}", input)
+        );
+    }
+}
+
+/// The reference script of the JSON-lines protocol, its first argument is its name
+#[cfg(all(feature = "json", unix))]
+fn json_lines() {
+    let mut sm = load(&crate::json_lines_scripts("launch_config"));
+    assert_eq!(sm.scripts()[0].metadata().name, "configured");
+    assert_eq!(
+        sm.trigger(Eval("a".into())).next().unwrap().unwrap(),
+        "/:unset:set:a"
+    );
+}
+
+/// Reports how it was started: its first argument, working directory and environment
+fn launched() {
+    OneShotScript::run(HookHandlers::default().on(|Eval(input): Eval| {
+        format!(
+            "{}:{}:{}:{}:{}",
+            env::args().nth(1).unwrap_or_default(),
+            env::current_dir().unwrap().display(),
+            env::var("CARGO_PKG_NAME").unwrap_or_else(|_| "unset".into()),
+            env::var("LAUNCH_VAR").unwrap_or_default(),
+            input
+        )
+    }))
+    .unwrap();
+}
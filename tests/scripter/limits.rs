@@ -0,0 +1,109 @@
+//! Scripts killed for exceeding their `ResourceLimits` (Unix only)
+
+use crate::{load_with, script_dir, DaemonScript, Script, Test};
+use rscript::{
+    scripting::{HookHandlers, Scripter},
+    Error, Hook, ResourceLimit, ResourceLimits, ScriptLaunchConfig, ScriptManager, ScriptStderr,
+};
+use serde::{Deserialize, Serialize};
+use std::{hint::black_box, os::unix::process::ExitStatusExt, path::Path, time::Duration};
+
+pub const TESTS: &[Test] = &[
+    Test {
+        name: "limits::cpu_time",
+        run: cpu_time,
+    },
+    Test {
+        name: "limits::memory",
+        run: memory,
+    },
+    Test {
+        name: "limits::abort_is_a_crash",
+        run: abort_is_a_crash,
+    },
+];
+
+pub const SCRIPTS: &[Script] = &[Script {
+    name: "limited",
+    run: limited,
+}];
+
+/// Ask the script to exhaust a resource
+#[derive(Serialize, Deserialize)]
+enum Strain {
+    Spin,
+    Allocate,
+    Abort,
+}
+impl Hook for Strain {
+    const NAME: &'static str = "Strain";
+    type Output = ();
+}
+
+fn load(dir: &Path, limits: &ResourceLimits) -> ScriptManager {
+    load_with(dir, |sm| {
+        sm.set_resource_limits(Some(*limits));
+        // The allocation failure is still noticed
+        let mut config = ScriptLaunchConfig::default();
+        config.stderr(ScriptStderr::Discard);
+        sm.set_launch_config(config);
+    })
+}
+
+fn exceeded(case: &str, limits: &ResourceLimits, strain: Strain, limit: ResourceLimit) {
+    let dir = script_dir(case, &["limited"]);
+    let mut sm = load(&dir, limits);
+    let error = sm.trigger(strain).next().unwrap().unwrap_err();
+    assert!(
+        matches!(
+            &error,
+            Error::ResourceLimitExceeded { path, limit: exceeded }
+                if *path == dir.join("limited") && *exceeded == limit
+        ),
+        "{:?}",
+        error
+    );
+}
+
+fn cpu_time() {
+    exceeded(
+        "limits_cpu_time",
+        ResourceLimits::default().cpu_time(Duration::from_secs(1)),
+        Strain::Spin,
+        ResourceLimit::CpuTime,
+    );
+}
+
+fn memory() {
+    exceeded(
+        "limits_memory",
+        ResourceLimits::default().memory(256 << 20),
+        Strain::Allocate,
+        ResourceLimit::Memory,
+    );
+}
+
+/// Aborting for another reason than an allocation failure isn't blamed on the memory limit
+fn abort_is_a_crash() {
+    let dir = script_dir("limits_abort", &["limited"]);
+    let mut sm = load(&dir, ResourceLimits::default().memory(256 << 20));
+    let error = sm.trigger(Strain::Abort).next().unwrap().unwrap_err();
+    assert!(
+        matches!(&error, Error::ScriptCrashed { status, .. } if status.signal() == Some(libc::SIGABRT)),
+        "{:?}",
+        error
+    );
+}
+
+fn limited() {
+    DaemonScript::run(HookHandlers::default().on(|strain: Strain| match strain {
+        Strain::Spin => loop {
+            black_box(());
+        },
+        Strain::Allocate => {
+            black_box(vec![1u8; 1 << 30]);
+        }
+        Strain::Abort => std::process::abort(),
+    }))
+    .unwrap();
+}
@@ -0,0 +1,250 @@
+//! Tests with [Scripter](rscript::scripting::Scripter) scripts, and with the reference scripts of the JSON-lines protocol (`tests/json_lines`) when the `json` feature is enabled\
+//! The test binary is its own script: the scripts are hard links to it named after one of the `SCRIPTS` functions, which runs instead of the tests when the binary is started with that name
+
+#[cfg(feature = "tokio")]
+mod asynchronous;
+#[cfg(any(feature = "json", feature = "msgpack", feature = "cbor"))]
+mod codecs;
+mod discovery;
+mod events;
+mod fingerprints;
+mod framing;
+mod handlers;
+mod handshake;
+mod host;
+mod launch_config;
+#[cfg(unix)]
+mod limits;
+mod parallel;
+#[cfg(unix)]
+mod pipes;
+#[cfg(feature = "tracing")]
+mod recorder;
+mod reload;
+mod restarts;
+#[cfg(all(feature = "sandbox", target_os = "linux"))]
+mod sandbox;
+mod shutdown;
+mod stderr;
+mod timeouts;
+#[cfg(feature = "tracing")]
+mod traces;
+
+use rscript::{scripting::Scripter, Hook, ScriptManager, ScriptType, Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fs,
+    panic::{self, UnwindSafe},
+    path::{Path, PathBuf},
+    process,
+};
+
+/// A test case, run unless its name doesn't contain the filter passed on the command line
+pub struct Test {
+    pub name: &'static str,
+    pub run: fn(),
+}
+
+/// A script entry point, see [script_dir]
+pub struct Script {
+    pub name: &'static str,
+    pub run: fn(),
+}
+
+const TESTS: &[&[Test]] = &[
+    #[cfg(feature = "tokio")]
+    asynchronous::TESTS,
+    #[cfg(any(feature = "json", feature = "msgpack", feature = "cbor"))]
+    codecs::TESTS,
+    discovery::TESTS,
+    events::TESTS,
+    fingerprints::TESTS,
+    framing::TESTS,
+    handlers::TESTS,
+    host::TESTS,
+    handshake::TESTS,
+    launch_config::TESTS,
+    parallel::TESTS,
+    reload::TESTS,
+    restarts::TESTS,
+    #[cfg(unix)]
+    limits::TESTS,
+    #[cfg(unix)]
+    pipes::TESTS,
+    #[cfg(all(feature = "sandbox", target_os = "linux"))]
+    sandbox::TESTS,
+    shutdown::TESTS,
+    stderr::TESTS,
+    timeouts::TESTS,
+    #[cfg(feature = "tracing")]
+    traces::TESTS,
+];
+const SCRIPTS: &[&[Script]] = &[
+    #[cfg(feature = "tokio")]
+    asynchronous::SCRIPTS,
+    #[cfg(any(feature = "json", feature = "msgpack", feature = "cbor"))]
+    codecs::SCRIPTS,
+    discovery::SCRIPTS,
+    events::SCRIPTS,
+    fingerprints::SCRIPTS,
+    framing::SCRIPTS,
+    handlers::SCRIPTS,
+    host::SCRIPTS,
+    handshake::SCRIPTS,
+    launch_config::SCRIPTS,
+    parallel::SCRIPTS,
+    reload::SCRIPTS,
+    restarts::SCRIPTS,
+    #[cfg(unix)]
+    limits::SCRIPTS,
+    #[cfg(unix)]
+    pipes::SCRIPTS,
+    #[cfg(all(feature = "sandbox", target_os = "linux"))]
+    sandbox::SCRIPTS,
+    shutdown::SCRIPTS,
+    stderr::SCRIPTS,
+    timeouts::SCRIPTS,
+    #[cfg(feature = "tracing")]
+    traces::SCRIPTS,
+];
+
+fn main() {
+    let started_as = env::args_os()
+        .next()
+        .map(PathBuf::from)
+        .and_then(|path| Some(path.file_name()?.to_string_lossy().into_owned()));
+    if let Some(script) = SCRIPTS
+        .iter()
+        .flat_map(|scripts| scripts.iter())
+        .find(|script| Some(script.name) == started_as.as_deref())
+    {
+        (script.run)();
+        return;
+    }
+
+    let filter = env::args().skip(1).find(|arg| !arg.starts_with('-'));
+    let tests: Vec<&Test> = TESTS
+        .iter()
+        .flat_map(|tests| tests.iter())
+        .filter(|test| {
+            filter
+                .as_ref()
+                .is_none_or(|filter| test.name.contains(filter))
+        })
+        .collect();
+    println!("\nrunning {} tests", tests.len());
+    let failed: Vec<&str> = tests
+        .iter()
+        .filter(|test| {
+            let passed = passes(test.run);
+            println!(
+                "test {} ... {}",
+                test.name,
+                if passed { "ok" } else { "FAILED" }
+            );
+            !passed
+        })
+        .map(|test| test.name)
+        .collect();
+    println!(
+        "\ntest result: {}. {} passed; {} failed\n",
+        if failed.is_empty() { "ok" } else { "FAILED" },
+        tests.len() - failed.len(),
+        failed.len()
+    );
+    if !failed.is_empty() {
+        process::exit(101);
+    }
+}
+
+fn passes(test: impl FnOnce() + UnwindSafe) -> bool {
+    panic::catch_unwind(test).is_ok()
+}
+
+/// Create an empty folder for the test `case`, with the scripts named `scripts`
+pub fn script_dir(case: &str, scripts: &[&str]) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join("scripter")
+        .join(case);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for script in scripts {
+        add_script(&dir, script);
+    }
+    dir
+}
+
+/// The folder of the JSON-lines reference scripts of the test `case`
+#[cfg(all(feature = "json", unix))]
+pub fn json_lines_scripts(case: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("json_lines")
+        .join(case)
+}
+
+/// Add the script `name` to `dir`, a hard link to the test binary (or a copy if the folder is on another filesystem)
+pub fn add_script(dir: &Path, name: &str) -> PathBuf {
+    assert!(
+        SCRIPTS
+            .iter()
+            .any(|scripts| scripts.iter().any(|script| script.name == name)),
+        "unknown script {}",
+        name
+    );
+    let exe = env::current_exe().unwrap();
+    let path = dir.join(name);
+    if fs::hard_link(&exe, &path).is_err() {
+        fs::copy(&exe, &path).unwrap();
+    }
+    path
+}
+
+/// The hook of most test scripts, they answer with the input unless it asks them to misbehave
+#[derive(Serialize, Deserialize)]
+pub struct Eval(pub String);
+impl Hook for Eval {
+    const NAME: &'static str = "Eval";
+    type Output = String;
+}
+
+/// A manager with the scripts of `dir`, `configure` runs before they are added
+pub fn load_with(dir: &Path, configure: impl FnOnce(&mut ScriptManager)) -> ScriptManager {
+    let mut sm = ScriptManager::default();
+    configure(&mut sm);
+    sm.add_scripts_by_path(dir, Version::new(0, 1, 0)).unwrap();
+    sm
+}
+
+/// A manager with the scripts of `dir`
+pub fn load(dir: &Path) -> ScriptManager {
+    load_with(dir, |_| ())
+}
+
+/// The [Scripter] of the daemon test scripts
+pub struct DaemonScript;
+impl Scripter for DaemonScript {
+    fn name() -> &'static str {
+        "daemon"
+    }
+    fn script_type() -> ScriptType {
+        ScriptType::Daemon
+    }
+    fn version_requirement() -> VersionReq {
+        VersionReq::parse(">=0.1.0").unwrap()
+    }
+}
+
+/// The [Scripter] of the one shot test scripts
+pub struct OneShotScript;
+impl Scripter for OneShotScript {
+    fn name() -> &'static str {
+        "one_shot"
+    }
+    fn script_type() -> ScriptType {
+        ScriptType::OneShot
+    }
+    fn version_requirement() -> VersionReq {
+        VersionReq::parse(">=0.1.0").unwrap()
+    }
+}
@@ -0,0 +1,93 @@
+//! `ScriptManager::trigger_parallel` yields the outputs as the scripts answer, and `trigger_tagged` tags them with their script
+
+use crate::{load, script_dir, DaemonScript, Eval, Script, Test};
+use rscript::scripting::{HookHandlers, Scripter};
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+pub const TESTS: &[Test] = &[
+    Test {
+        name: "parallel::completion_order",
+        run: completion_order,
+    },
+    Test {
+        name: "parallel::dropped_early",
+        run: dropped_early,
+    },
+    Test {
+        name: "parallel::tagged",
+        run: tagged,
+    },
+];
+
+pub const SCRIPTS: &[Script] = &[
+    Script {
+        name: "slow",
+        run: slow,
+    },
+    Script {
+        name: "fast",
+        run: fast,
+    },
+];
+
+pub const SLOW: Duration = Duration::from_millis(500);
+
+fn completion_order() {
+    let dir = script_dir("parallel_completion_order", &["slow", "fast"]);
+    let mut sm = load(&dir);
+    let start = Instant::now();
+    let mut outputs = sm.trigger_parallel(Eval("a".into()));
+    assert_eq!(outputs.next().unwrap().result.unwrap(), "fast a");
+    assert!(start.elapsed() < SLOW, "{:?}", start.elapsed());
+    assert_eq!(outputs.next().unwrap().result.unwrap(), "slow a");
+    assert!(outputs.next().is_none());
+}
+
+/// The scripts still running are waited for and put back when the iterator is dropped
+fn dropped_early() {
+    let dir = script_dir("parallel_dropped_early", &["slow", "fast"]);
+    let mut sm = load(&dir);
+    let ids: Vec<_> = sm.scripts().iter().map(|script| script.id()).collect();
+    sm.trigger_parallel(Eval("a".into())).next().unwrap();
+    let after: Vec<_> = sm.scripts().iter().map(|script| script.id()).collect();
+    assert_eq!(ids, after);
+    let mut outputs: Vec<String> = sm
+        .trigger_parallel(Eval("b".into()))
+        .map(|output| output.result.unwrap())
+        .collect();
+    outputs.sort();
+    assert_eq!(outputs, ["fast b", "slow b"]);
+}
+
+fn tagged() {
+    let dir = script_dir("parallel_tagged", &["slow", "fast"]);
+    let mut sm = load(&dir);
+    let ids: Vec<_> = sm.scripts().iter().map(|script| script.id()).collect();
+    let outputs: Vec<_> = sm.trigger_tagged(Eval("a".into())).collect();
+    assert_eq!(
+        outputs.iter().map(|output| output.id).collect::<Vec<_>>(),
+        ids
+    );
+    for output in outputs {
+        assert_eq!(output.name, "daemon");
+        assert!(output.result.unwrap().ends_with(" a"));
+    }
+}
+
+/// Answers after [SLOW]
+fn slow() {
+    DaemonScript::run(HookHandlers::default().on(|Eval(input): Eval| {
+        thread::sleep(SLOW);
+        format!("slow {}", input)
+    }))
+    .unwrap();
+}
+
+/// Answers right away
+fn fast() {
+    DaemonScript::run(HookHandlers::default().on(|Eval(input): Eval| format!("fast {}", input)))
+        .unwrap();
+}
@@ -0,0 +1,96 @@
+//! Scripts speaking the protocol on `ProtocolChannel::Pipes` (Unix only), they can print to stdout
+
+use crate::{load, load_with, script_dir, DaemonScript, Eval, Script, Test};
+use rscript::{
+    scripting::{HookHandlers, Scripter},
+    ProtocolChannel,
+};
+use std::{env, path::Path};
+
+pub const TESTS: &[Test] = &[
+    Test {
+        name: "pipes::scripter",
+        run: scripter,
+    },
+    #[cfg(feature = "json")]
+    Test {
+        name: "pipes::json_lines",
+        run: json_lines,
+    },
+    Test {
+        name: "pipes::nested",
+        run: nested,
+    },
+];
+
+pub const SCRIPTS: &[Script] = &[
+    Script {
+        name: "piped",
+        run: piped,
+    },
+    Script {
+        name: "nesting",
+        run: nesting,
+    },
+];
+
+fn answers(dir: &Path) {
+    let mut sm = load_with(dir, |sm| sm.set_protocol_channel(ProtocolChannel::Pipes));
+    for input in ["a", "b"] {
+        assert_eq!(
+            sm.trigger(Eval(input.into())).next().unwrap().unwrap(),
+            format!("pipes:{}", input)
+        );
+    }
+}
+
+fn scripter() {
+    answers(&script_dir("pipes", &["piped"]));
+}
+
+#[cfg(feature = "json")]
+fn json_lines() {
+    answers(&crate::json_lines_scripts("pipes"));
+}
+
+/// A script can start scripts on the default channel, they don't inherit its pipes
+fn nested() {
+    let inner = script_dir("pipes_nested_inner", &["fast"]);
+    let mut sm = load_with(&script_dir("pipes_nested", &["nesting"]), |sm| {
+        sm.set_protocol_channel(ProtocolChannel::Pipes)
+    });
+    assert_eq!(
+        sm.trigger(Eval(inner.to_string_lossy().into_owned()))
+            .next()
+            .unwrap()
+            .unwrap(),
+        "RSCRIPT_PROTOCOL_FDS kept, fast a"
+    );
+}
+
+/// Prints to stdout before answering
+fn piped() {
+    println!("pipes: this is not a protocol message");
+    let _ = DaemonScript::run(HookHandlers::default().on(|Eval(input): Eval| {
+        println!("pipes: evaluating {}", input);
+        format!("pipes:{}", input)
+    }));
+}
+
+/// Loads the scripts of the folder it receives and triggers them
+fn nesting() {
+    DaemonScript::run(HookHandlers::default().on(|Eval(dir): Eval| {
+        let kept = env::var_os("RSCRIPT_PROTOCOL_FDS").is_some();
+        let output = load(Path::new(&dir))
+            .trigger(Eval("a".into()))
+            .next()
+            .unwrap()
+            .unwrap();
+        format!(
+            "RSCRIPT_PROTOCOL_FDS {}, {}",
+            if kept { "kept" } else { "removed" },
+            output
+        )
+    }))
+    .unwrap();
+}
@@ -0,0 +1,127 @@
+//! A `tracing` subscriber that records the spans and events, shared with tests/dynamic.rs
+
+use std::sync::{Arc, Mutex};
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Metadata, Subscriber,
+};
+use tracing_core::span::Current;
+
+/// A span and the fields recorded on it
+#[derive(Debug, Clone)]
+pub struct RecordedSpan {
+    pub name: &'static str,
+    pub fields: Vec<(&'static str, String)>,
+    metadata: &'static Metadata<'static>,
+}
+
+impl RecordedSpan {
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| *field == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// An event and the name of the span it was emitted in
+#[derive(Debug, Clone)]
+pub struct RecordedEvent {
+    pub span: Option<&'static str>,
+    pub level: tracing::Level,
+    pub fields: Vec<(&'static str, String)>,
+}
+
+#[derive(Clone, Default)]
+pub struct Recorder {
+    spans: Arc<Mutex<Vec<RecordedSpan>>>,
+    events: Arc<Mutex<Vec<RecordedEvent>>>,
+    // The entered spans, the recorder is used on a single thread
+    stack: Arc<Mutex<Vec<span::Id>>>,
+}
+
+impl Recorder {
+    /// The spans named `name`, in creation order
+    pub fn spans(&self, name: &str) -> Vec<RecordedSpan> {
+        let spans = self.spans.lock().unwrap();
+        spans
+            .iter()
+            .filter(|span| span.name == name)
+            .cloned()
+            .collect()
+    }
+    pub fn events(&self) -> Vec<RecordedEvent> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+struct Fields<'a>(&'a mut Vec<(&'static str, String)>);
+
+impl Visit for Fields<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.record(field, format!("{:?}", value));
+    }
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record(field, value.to_string());
+    }
+}
+
+impl Fields<'_> {
+    fn record(&mut self, field: &Field, value: String) {
+        self.0.retain(|(name, _)| *name != field.name());
+        self.0.push((field.name(), value));
+    }
+}
+
+fn index(id: &span::Id) -> usize {
+    id.into_u64() as usize - 1
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+    fn new_span(&self, attributes: &span::Attributes<'_>) -> span::Id {
+        let mut span = RecordedSpan {
+            name: attributes.metadata().name(),
+            fields: vec![],
+            metadata: attributes.metadata(),
+        };
+        attributes.record(&mut Fields(&mut span.fields));
+        let mut spans = self.spans.lock().unwrap();
+        spans.push(span);
+        span::Id::from_u64(spans.len() as u64)
+    }
+    fn record(&self, id: &span::Id, values: &span::Record<'_>) {
+        let mut spans = self.spans.lock().unwrap();
+        values.record(&mut Fields(&mut spans[index(id)].fields));
+    }
+    fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+    fn event(&self, event: &Event<'_>) {
+        let span = self
+            .stack
+            .lock()
+            .unwrap()
+            .last()
+            .map(|id| self.spans.lock().unwrap()[index(id)].name);
+        let mut fields = vec![];
+        event.record(&mut Fields(&mut fields));
+        self.events.lock().unwrap().push(RecordedEvent {
+            span,
+            level: *event.metadata().level(),
+            fields,
+        });
+    }
+    fn enter(&self, id: &span::Id) {
+        self.stack.lock().unwrap().push(id.clone());
+    }
+    fn exit(&self, _id: &span::Id) {
+        self.stack.lock().unwrap().pop();
+    }
+    fn current_span(&self) -> Current {
+        match self.stack.lock().unwrap().last() {
+            Some(id) => Current::new(id.clone(), self.spans.lock().unwrap()[index(id)].metadata),
+            None => Current::none(),
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! `ScriptManager::reload` picks up added, modified and removed scripts, a modified script that fails to load keeps the old one running
+
+use crate::{add_script, load, script_dir, DaemonScript, Eval, Script, Test};
+use rscript::{
+    scripting::{HookHandlers, Scripter},
+    ReloadEvent, ScriptManager,
+};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+pub const TESTS: &[Test] = &[
+    Test {
+        name: "reload::added_modified_removed",
+        run: added_modified_removed,
+    },
+    Test {
+        name: "reload::failed_change_keeps_old",
+        run: failed_change_keeps_old,
+    },
+    Test {
+        name: "reload::replaced_file",
+        run: replaced_file,
+    },
+    #[cfg(all(feature = "watch", target_os = "linux"))]
+    Test {
+        name: "reload::watcher",
+        run: watcher,
+    },
+];
+
+pub const SCRIPTS: &[Script] = &[Script {
+    name: "versioned",
+    run: versioned,
+}];
+
+/// The version `versioned` scripts of `dir` start with
+fn set_version(dir: &Path, version: &str) {
+    fs::write(dir.with_extension("version"), version).unwrap();
+}
+
+/// Replace the script file `name` of `dir` by a new copy of the test binary
+fn replace_script(dir: &Path, name: &str) -> PathBuf {
+    let path = dir.join(name);
+    fs::remove_file(&path).unwrap();
+    fs::copy(env::current_exe().unwrap(), &path).unwrap();
+    path
+}
+
+fn eval(sm: &mut ScriptManager) -> Vec<String> {
+    sm.trigger(Eval("a".into()))
+        .map(|output| output.unwrap())
+        .collect()
+}
+
+fn added_modified_removed() {
+    let dir = script_dir("reload_added_modified_removed", &["versioned"]);
+    set_version(&dir, "1");
+    let mut sm = load(&dir);
+    let id = sm.scripts()[0].id();
+    sm.scripts_mut()[0].deactivate();
+    assert!(sm.reload().unwrap().is_empty());
+
+    set_version(&dir, "2");
+    let path = replace_script(&dir, "versioned");
+    let events = sm.reload().unwrap();
+    assert!(matches!(&events[..], [ReloadEvent::Reloaded(reloaded)] if *reloaded == path));
+    // The id and the activation state are kept
+    assert_eq!(sm.scripts()[0].id(), id);
+    assert!(eval(&mut sm).is_empty());
+    sm.scripts_mut()[0].activate();
+    assert_eq!(eval(&mut sm), ["2:a"]);
+
+    fs::remove_file(&path).unwrap();
+    let events = sm.reload().unwrap();
+    assert!(matches!(&events[..], [ReloadEvent::Removed(removed)] if *removed == path));
+    assert!(sm.scripts().is_empty());
+
+    add_script(&dir, "versioned");
+    let events = sm.reload().unwrap();
+    assert!(matches!(&events[..], [ReloadEvent::Added(added)] if *added == path));
+    assert_ne!(sm.scripts()[0].id(), id);
+    assert_eq!(eval(&mut sm), ["2:a"]);
+}
+
+fn failed_change_keeps_old() {
+    let dir = script_dir("reload_failed_change_keeps_old", &["versioned"]);
+    set_version(&dir, "1");
+    let mut sm = load(&dir);
+
+    set_version(&dir, "broken");
+    let path = replace_script(&dir, "versioned");
+    let events = sm.reload().unwrap();
+    assert!(matches!(&events[..], [ReloadEvent::Failed(failed, _)] if *failed == path));
+    assert_eq!(eval(&mut sm), ["1:a"]);
+    // Not retried until the file changes again
+    assert!(sm.reload().unwrap().is_empty());
+
+    set_version(&dir, "2");
+    replace_script(&dir, "versioned");
+    assert!(matches!(
+        &sm.reload().unwrap()[..],
+        [ReloadEvent::Reloaded(_)]
+    ));
+    assert_eq!(eval(&mut sm), ["2:a"]);
+}
+
+/// A file replaced by another one with the same size and modification time is still a change
+fn replaced_file() {
+    let dir = script_dir("reload_replaced_file", &["versioned"]);
+    set_version(&dir, "1");
+    let mut sm = load(&dir);
+
+    let modified = fs::metadata(dir.join("versioned"))
+        .unwrap()
+        .modified()
+        .unwrap();
+    set_version(&dir, "2");
+    let path = replace_script(&dir, "versioned");
+    fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+    assert!(matches!(
+        &sm.reload().unwrap()[..],
+        [ReloadEvent::Reloaded(_)]
+    ));
+    assert_eq!(eval(&mut sm), ["2:a"]);
+}
+
+#[cfg(all(feature = "watch", target_os = "linux"))]
+fn watcher() {
+    let dir = script_dir("reload_watcher", &["versioned"]);
+    set_version(&dir, "1");
+    let mut sm = load(&dir);
+    let mut watcher = sm.watcher().unwrap();
+    assert!(!watcher.changed().unwrap());
+
+    fs::remove_file(dir.join("versioned")).unwrap();
+    watcher.wait().unwrap();
+    assert!(matches!(
+        &sm.reload().unwrap()[..],
+        [ReloadEvent::Removed(_)]
+    ));
+    // The events of the removal are consumed
+    assert!(!watcher.changed().unwrap());
+
+    add_script(&dir, "versioned");
+    assert!(watcher.changed().unwrap());
+    assert!(matches!(&sm.reload().unwrap()[..], [ReloadEvent::Added(_)]));
+}
+
+/// Answers [Eval] prefixed with the version of its folder, exits right away if it's `broken`
+fn versioned() {
+    let exe = env::current_exe().unwrap();
+    let version = fs::read_to_string(exe.parent().unwrap().with_extension("version")).unwrap();
+    if version == "broken" {
+        process::exit(1);
+    }
+    DaemonScript::run(
+        HookHandlers::default().on(move |Eval(input): Eval| format!("{}:{}", version, input)),
+    )
+    .unwrap();
+}
@@ -0,0 +1,104 @@
+//! Daemons that exit are reported with `Error::ScriptCrashed`, then restarted or marked as failed depending on their restart policy
+
+use crate::{load, script_dir, DaemonScript, Eval, Script, Test};
+use rscript::{
+    scripting::{HookHandlers, Scripter},
+    Error, RestartPolicy, ScriptManager,
+};
+use std::{env, process, time::Duration};
+
+pub const TESTS: &[Test] = &[
+    Test {
+        name: "restarts::no_policy",
+        run: no_policy,
+    },
+    Test {
+        name: "restarts::restarted",
+        run: restarted,
+    },
+    Test {
+        name: "restarts::failed_restart",
+        run: failed_restart,
+    },
+];
+
+pub const SCRIPTS: &[Script] = &[Script {
+    name: "crashy",
+    run: crashy,
+}];
+
+/// Set the restart policy of the only script of `sm`
+fn set_policy(sm: &mut ScriptManager, max_restarts: usize) {
+    sm.scripts_mut()[0].set_restart_policy(Some(RestartPolicy {
+        max_restarts,
+        backoff: Duration::from_millis(10),
+    }));
+}
+
+fn crash(sm: &mut ScriptManager) {
+    let error = sm
+        .trigger(Eval("crash".into()))
+        .next()
+        .unwrap()
+        .unwrap_err();
+    assert!(
+        matches!(&error, Error::ScriptCrashed { name, status } if name == "daemon" && status.code() == Some(3)),
+        "{:?}",
+        error
+    );
+}
+
+fn no_policy() {
+    let dir = script_dir("restarts_no_policy", &["crashy"]);
+    let mut sm = load(&dir);
+    crash(&mut sm);
+    assert!(sm.scripts()[0].has_failed());
+    assert_eq!(sm.scripts()[0].restarts(), 0);
+    assert!(sm.trigger(Eval("a".into())).next().is_none());
+}
+
+fn restarted() {
+    let dir = script_dir("restarts_restarted", &["crashy"]);
+    let mut sm = load(&dir);
+    set_policy(&mut sm, 2);
+    let id = sm.scripts()[0].id();
+    for restarts in 1..=2 {
+        crash(&mut sm);
+        let script = &sm.scripts()[0];
+        assert_eq!(script.restarts(), restarts);
+        assert!(!script.has_failed());
+        // Restarting keeps the id
+        assert_eq!(script.id(), id);
+        assert_eq!(sm.trigger(Eval("a".into())).next().unwrap().unwrap(), "a");
+    }
+    // No restart left
+    crash(&mut sm);
+    assert!(sm.scripts()[0].has_failed());
+    assert_eq!(sm.scripts()[0].restarts(), 2);
+}
+
+/// A script that can't be started again is marked as failed
+fn failed_restart() {
+    let dir = script_dir("restarts_failed_restart", &["crashy"]);
+    let mut sm = load(&dir);
+    set_policy(&mut sm, 3);
+    std::fs::write(dir.join("broken"), "").unwrap();
+    crash(&mut sm);
+    assert!(sm.scripts()[0].has_failed());
+    assert_eq!(sm.scripts()[0].restarts(), 1);
+}
+
+/// Exits on `Eval("crash")`, and right away when a `broken` file is next to it
+fn crashy() {
+    let exe = env::current_exe().unwrap();
+    if exe.with_file_name("broken").exists() {
+        process::exit(1);
+    }
+    DaemonScript::run(HookHandlers::default().on(|Eval(input): Eval| {
+        if input == "crash" {
+            process::exit(3);
+        }
+        input
+    }))
+    .unwrap();
+}
@@ -0,0 +1,81 @@
+//! Scripts restricted by a `SandboxPolicy` (`sandbox` feature, Linux only)
+
+use crate::{load_with, script_dir, DaemonScript, Script, Test};
+use rscript::{
+    scripting::{HookHandlers, Scripter},
+    Error, Hook, SandboxPolicy, ScriptManager,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    net::TcpStream,
+    path::{Path, PathBuf},
+};
+
+pub const TESTS: &[Test] = &[
+    Test {
+        name: "sandbox::network_is_a_violation",
+        run: network_is_a_violation,
+    },
+    Test {
+        name: "sandbox::denied_filesystem_access",
+        run: denied_filesystem_access,
+    },
+];
+
+pub const SCRIPTS: &[Script] = &[Script {
+    name: "sandboxed",
+    run: sandboxed,
+}];
+
+/// Ask the script to do something, it answers with the error kind if it failed
+#[derive(Serialize, Deserialize)]
+enum Probe {
+    Connect,
+    Write(PathBuf),
+}
+impl Hook for Probe {
+    const NAME: &'static str = "Probe";
+    type Output = Result<(), String>;
+}
+
+fn load(dir: &Path, policy: SandboxPolicy) -> ScriptManager {
+    load_with(dir, |sm| sm.set_sandbox_policy(Some(policy)))
+}
+
+fn network_is_a_violation() {
+    let dir = script_dir("sandbox_network", &["sandboxed"]);
+    let mut sm = load(&dir, SandboxPolicy::default());
+    let error = sm.trigger(Probe::Connect).next().unwrap().unwrap_err();
+    assert!(
+        matches!(&error, Error::SandboxViolation { path } if *path == dir.join("sandboxed")),
+        "{:?}",
+        error
+    );
+}
+
+fn denied_filesystem_access() {
+    let dir = script_dir("sandbox_filesystem", &["sandboxed"]);
+    // Outside of the scripts folder, so it's not taken for a script
+    let allowed = script_dir("sandbox_filesystem_allowed", &[]);
+    let mut policy = SandboxPolicy::default();
+    policy.allow_write(&allowed);
+    let mut sm = load(&dir, policy);
+
+    let mut write = |path: PathBuf| sm.trigger(Probe::Write(path)).next().unwrap().unwrap();
+    assert_eq!(write(dir.join("denied")), Err("PermissionDenied".into()));
+    assert!(!dir.join("denied").exists());
+    assert_eq!(write(allowed.join("file")), Ok(()));
+    assert!(allowed.join("file").exists());
+}
+
+fn sandboxed() {
+    // It's killed when it breaks its policy
+    let _ = DaemonScript::run(HookHandlers::default().on(|probe: Probe| {
+        let result = match probe {
+            Probe::Connect => TcpStream::connect("127.0.0.1:1").map(drop),
+            Probe::Write(path) => fs::write(path, "sandboxed"),
+        };
+        result.map_err(|e| format!("{:?}", e.kind()))
+    }));
+}
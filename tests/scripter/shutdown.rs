@@ -0,0 +1,77 @@
+//! Daemons are asked to exit when the `ScriptManager` is dropped, and killed if they don't within the shutdown grace
+
+use crate::{load_with, script_dir, DaemonScript, Eval, Script, Test};
+use rscript::scripting::{HookHandlers, Scripter};
+use std::{
+    env, fs,
+    path::PathBuf,
+    thread,
+    time::{Duration, Instant},
+};
+
+pub const TESTS: &[Test] = &[
+    Test {
+        name: "shutdown::graceful",
+        run: graceful,
+    },
+    Test {
+        name: "shutdown::killed_after_grace",
+        run: killed_after_grace,
+    },
+];
+
+pub const SCRIPTS: &[Script] = &[
+    Script {
+        name: "tidy",
+        run: tidy,
+    },
+    Script {
+        name: "stubborn",
+        run: stubborn,
+    },
+];
+
+const GRACE: Duration = Duration::from_millis(300);
+
+/// The file `tidy` scripts write when they are asked to exit, next to their folder
+pub fn tidied(dir: &std::path::Path) -> PathBuf {
+    dir.with_extension("tidied")
+}
+
+fn graceful() {
+    let dir = script_dir("shutdown_graceful", &["tidy"]);
+    let _ = fs::remove_file(tidied(&dir));
+    let mut sm = load_with(&dir, |sm| sm.set_shutdown_grace(Duration::from_secs(10)));
+    assert_eq!(sm.trigger(Eval("a".into())).next().unwrap().unwrap(), "a");
+    drop(sm);
+    // Dropping the manager waits for the script to exit
+    assert_eq!(fs::read_to_string(tidied(&dir)).unwrap(), "tidied");
+}
+
+fn killed_after_grace() {
+    let dir = script_dir("shutdown_killed_after_grace", &["stubborn"]);
+    let sm = load_with(&dir, |sm| sm.set_shutdown_grace(GRACE));
+    let start = Instant::now();
+    drop(sm);
+    let elapsed = start.elapsed();
+    assert!(elapsed >= GRACE && elapsed < GRACE * 10, "{:?}", elapsed);
+}
+
+/// Writes its `tidied` file when asked to exit
+fn tidy() {
+    let dir = env::current_exe().unwrap().parent().unwrap().to_path_buf();
+    DaemonScript::run_with_shutdown(
+        HookHandlers::default().on(|Eval(input): Eval| input),
+        &mut || fs::write(tidied(&dir), "tidied").unwrap(),
+    )
+    .unwrap();
+}
+
+/// Takes too long to exit
+fn stubborn() {
+    DaemonScript::run_with_shutdown(
+        HookHandlers::default().on(|Eval(input): Eval| input),
+        &mut || thread::sleep(Duration::from_secs(30)),
+    )
+    .unwrap();
+}
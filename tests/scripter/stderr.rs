@@ -0,0 +1,66 @@
+//! The stderr of scripts captured with `ScriptStderr::Capture`, across the greeting and every `OneShot` trigger
+
+use crate::{load_with, script_dir, Eval, OneShotScript, Script, Test};
+use rscript::{
+    scripting::{HookHandlers, Scripter},
+    ScriptLaunchConfig, ScriptStderr,
+};
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+pub const TESTS: &[Test] = &[
+    Test {
+        name: "stderr::scripter",
+        run: scripter,
+    },
+    #[cfg(all(feature = "json", unix))]
+    Test {
+        name: "stderr::json_lines",
+        run: json_lines,
+    },
+];
+
+pub const SCRIPTS: &[Script] = &[Script {
+    name: "logging",
+    run: logging,
+}];
+
+/// Trigger the script in `dir` twice, only the last two lines it logged are kept
+fn captures(dir: &Path) {
+    let mut config = ScriptLaunchConfig::default();
+    config.stderr(ScriptStderr::Capture { lines: 2 });
+    let mut sm = load_with(dir, |sm| sm.set_launch_config(config));
+    for input in ["a", "b"] {
+        assert_eq!(
+            sm.trigger(Eval(input.into())).next().unwrap().unwrap(),
+            format!("stderr:{}", input)
+        );
+    }
+    // The lines are read in the background
+    let expected = ["evaluating a", "evaluating b"];
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while sm.scripts()[0].stderr() != expected && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(sm.scripts()[0].stderr(), expected);
+}
+
+fn scripter() {
+    captures(&script_dir("stderr", &["logging"]));
+}
+
+#[cfg(all(feature = "json", unix))]
+fn json_lines() {
+    captures(&crate::json_lines_scripts("stderr"));
+}
+
+/// Logs every input to stderr
+fn logging() {
+    OneShotScript::run(HookHandlers::default().on(|Eval(input): Eval| {
+        eprintln!("evaluating {}", input);
+        format!("stderr:{}", input)
+    }))
+    .unwrap();
+}
@@ -0,0 +1,133 @@
+//! Scripts that don't answer in time are killed and reported with `Error::Timeout`, even if they stopped reading their input
+
+use crate::{load_with, script_dir, DaemonScript, Eval, Script, Test};
+use rscript::{
+    scripting::{HookHandlers, Scripter},
+    Error, Hook, ScriptManager, ScriptType, VersionReq,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+pub const TESTS: &[Test] = &[
+    Test {
+        name: "timeouts::greeting",
+        run: greeting,
+    },
+    Test {
+        name: "timeouts::hung_hook",
+        run: hung_hook,
+    },
+    Test {
+        name: "timeouts::hook_timeout",
+        run: hook_timeout,
+    },
+    Test {
+        name: "timeouts::stopped_reading",
+        run: stopped_reading,
+    },
+];
+
+pub const SCRIPTS: &[Script] = &[
+    Script {
+        name: "sleepy",
+        run: sleepy,
+    },
+    Script {
+        name: "hung",
+        run: hung,
+    },
+    Script {
+        name: "stalled",
+        run: stalled,
+    },
+];
+
+pub const TIMEOUT: Duration = Duration::from_millis(300);
+
+/// A hook that the `stalled` script never reads
+#[derive(Serialize, Deserialize)]
+struct Stall(Vec<u8>);
+impl Hook for Stall {
+    const NAME: &'static str = "Stall";
+    type Output = ();
+}
+
+/// Run `f` and check that it returned a timeout error quickly
+fn times_out<T: std::fmt::Debug>(f: impl FnOnce() -> Result<T, Error>) {
+    let start = Instant::now();
+    let error = f().unwrap_err();
+    assert!(matches!(error, Error::Timeout(TIMEOUT)), "{:?}", error);
+    assert!(start.elapsed() < TIMEOUT * 10, "{:?}", start.elapsed());
+}
+
+fn greeting() {
+    let dir = script_dir("timeouts_greeting", &["sleepy"]);
+    let mut sm = ScriptManager::default();
+    sm.set_greeting_timeout(Some(TIMEOUT));
+    times_out(|| sm.add_scripts_by_path(&dir, rscript::Version::new(0, 1, 0)));
+    assert!(sm.scripts().is_empty());
+}
+
+fn hung_hook() {
+    let dir = script_dir("timeouts_hung_hook", &["hung"]);
+    let mut sm = load_with(&dir, |sm| sm.set_execute_timeout(Some(TIMEOUT)));
+    assert_eq!(sm.trigger(Eval("a".into())).next().unwrap().unwrap(), "a");
+    times_out(|| sm.trigger(Eval("hang".into())).next().unwrap());
+    assert!(sm.scripts()[0].has_failed());
+    // A failed script is not triggered anymore
+    assert!(sm.trigger(Eval("a".into())).next().is_none());
+}
+
+fn hook_timeout() {
+    let dir = script_dir("timeouts_hook_timeout", &["hung"]);
+    let mut sm = load_with(&dir, |sm| sm.set_hook_timeout::<Eval>(Some(TIMEOUT)));
+    times_out(|| sm.trigger(Eval("hang".into())).next().unwrap());
+}
+
+/// Writing a hook bigger than the pipe buffer to a script that doesn't read it is bounded by the timeout too
+fn stopped_reading() {
+    let dir = script_dir("timeouts_stopped_reading", &["stalled"]);
+    let mut sm = load_with(&dir, |sm| sm.set_execute_timeout(Some(TIMEOUT)));
+    times_out(|| sm.trigger(Stall(vec![0; 4 << 20])).next().unwrap());
+    assert!(sm.scripts()[0].has_failed());
+}
+
+/// Takes too long to start
+fn sleepy() {
+    thread::sleep(Duration::from_secs(30));
+}
+
+/// Never answers the hook `Eval("hang")`
+fn hung() {
+    DaemonScript::run(HookHandlers::default().on(|Eval(input): Eval| {
+        if input == "hang" {
+            thread::sleep(Duration::from_secs(30));
+        }
+        input
+    }))
+    .unwrap();
+}
+
+struct Stalled;
+impl Scripter for Stalled {
+    fn name() -> &'static str {
+        "stalled"
+    }
+    fn script_type() -> ScriptType {
+        ScriptType::Daemon
+    }
+    fn hooks() -> &'static [&'static str] {
+        &[Stall::NAME]
+    }
+    fn version_requirement() -> VersionReq {
+        VersionReq::parse(">=0.1.0").unwrap()
+    }
+}
+
+/// Stops reading its input once it receives a hook name
+fn stalled() {
+    Stalled::execute(&mut |_| thread::sleep(Duration::from_secs(30))).unwrap();
+}
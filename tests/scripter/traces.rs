@@ -0,0 +1,96 @@
+//! The spans of the `tracing` feature, see `src/trace.rs`
+
+use crate::{recorder::Recorder, script_dir, DaemonScript, Eval, Script, Test};
+use rscript::{
+    scripting::{HookHandlers, Scripter},
+    ScriptManager, Version,
+};
+
+pub const TESTS: &[Test] = &[
+    Test {
+        name: "traces::spans",
+        run: spans,
+    },
+    Test {
+        name: "traces::errors",
+        run: errors,
+    },
+];
+
+pub const SCRIPTS: &[Script] = &[
+    Script {
+        name: "traced",
+        run: traced,
+    },
+    Script {
+        name: "mute",
+        run: mute,
+    },
+];
+
+fn spans() {
+    let dir = script_dir("traces_spans", &["traced"]);
+    let recorder = Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let mut sm = ScriptManager::default();
+        sm.add_scripts_by_path(&dir, Version::new(0, 1, 0)).unwrap();
+        assert_eq!(
+            sm.trigger(Eval("abc".into())).next().unwrap().unwrap(),
+            "abc"
+        );
+    });
+
+    let discovery = &recorder.spans("discovery")[0];
+    assert_eq!(discovery.field("dir"), Some(&*dir.display().to_string()));
+    assert!(discovery.field("duration_us").is_some());
+    assert_eq!(discovery.field("error"), None);
+
+    let greeting = &recorder.spans("greeting")[0];
+    let path = dir.join("traced").display().to_string();
+    assert_eq!(greeting.field("path"), Some(&*path));
+    assert_eq!(greeting.field("script"), Some("daemon"));
+    assert_eq!(greeting.field("script_type"), Some("Daemon"));
+    assert!(greeting.field("duration_us").is_some());
+
+    let trigger = &recorder.spans("trigger")[0];
+    assert_eq!(trigger.field("script"), Some("daemon"));
+    assert_eq!(trigger.field("script_type"), Some("Daemon"));
+    assert_eq!(trigger.field("hook"), Some("Eval"));
+    // Both are a bincode string of 3 bytes
+    let bytes = bincode::serialize("abc").unwrap().len().to_string();
+    assert_eq!(trigger.field("hook_bytes"), Some(&*bytes));
+    assert_eq!(trigger.field("output_bytes"), Some(&*bytes));
+    assert!(trigger.field("duration_us").is_some());
+    assert!(recorder.events().is_empty());
+}
+
+/// A failed span records the error and emits it as a `warn` event inside the span
+fn errors() {
+    let dir = script_dir("traces_errors", &["mute"]);
+    let recorder = Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let mut sm = ScriptManager::default();
+        sm.add_scripts_by_path(&dir, Version::new(0, 1, 0))
+            .unwrap_err();
+    });
+
+    let greeting = &recorder.spans("greeting")[0];
+    let error = greeting.field("error").unwrap();
+    assert!(recorder.spans("discovery")[0].field("error").is_some());
+    let events = recorder.events();
+    let warning = events
+        .iter()
+        .find(|event| event.span == Some("greeting"))
+        .unwrap();
+    assert_eq!(warning.level, tracing::Level::WARN);
+    assert!(warning.fields.contains(&("error", error.to_string())));
+}
+
+fn traced() {
+    DaemonScript::run(HookHandlers::default().on(|Eval(input)| input)).unwrap();
+}
+
+/// Exits without answering the greeting
+fn mute() {
+    std::process::exit(0);
+}